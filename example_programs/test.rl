@@ -1,17 +1,53 @@
-; This is a comment. Currently, comments may only occur at the beginning of a line.
-
-let Nat :: Type
-let zero :: Term Nat
-let succ :: Term Nat -> Nat
-let Eq :: Formula Nat Nat
-
-axiom Reflexivity = forall x: Nat. Eq x x
-axiom Symmetry = forall x: Nat. forall y: Nat. Eq x y -> Eq y x
-axiom Transitivity = forall x: Nat. forall y: Nat. forall z: Nat. Eq x y -> Eq y z -> Eq x z
-
-axiom SuccInjection = forall x: Nat. forall y: Nat. Eq (succ x) (succ y) -> Eq x y
-axiom ZeroNotSucc = forall x: Nat. Eq (succ x) zero -> false
-
-axiom Induction =
-    schema Phi :: Formula Nat.
-        Phi zero -> (forall x: Nat. Phi x -> Phi (succ x)) -> (forall x: Nat. Phi x)
\ No newline at end of file
+; This is a comment. Currently, comments may only occur at the beginning of a line.
+
+let Nat :: Type
+let zero :: Term Nat
+let succ :: Term Nat -> Nat
+let Eq :: Formula Nat Nat
+
+axiom Reflexivity = forall x: Nat. Eq x x
+axiom Symmetry = forall x: Nat. forall y: Nat. Eq x y -> Eq y x
+axiom Transitivity = forall x: Nat. forall y: Nat. forall z: Nat. Eq x y -> Eq y z -> Eq x z
+
+axiom SuccInjection = forall x: Nat. forall y: Nat. Eq (succ x) (succ y) -> Eq x y
+axiom ZeroNotSucc = forall x: Nat. Eq (succ x) zero -> false
+
+axiom Induction =
+    schema Phi :: Formula Nat.
+        Phi zero -> (forall x: Nat. Phi x -> Phi (succ x)) -> (forall x: Nat. Phi x)
+theorem ReflZero : Eq zero zero := Reflexivity zero
+theorem ZeroNotOne : Eq (succ zero) zero -> false := ZeroNotSucc zero
+theorem OneNotZero : Eq zero (succ zero) -> Eq (succ zero) zero := Symmetry zero (succ zero)
+theorem SuccReflexivity : forall x: Nat. Eq (succ x) (succ x) := given x: Nat. Reflexivity (succ x)
+theorem SuccNotZero : forall x: Nat. (Eq (succ x) zero -> false) -> (Eq zero (succ x) -> false) :=
+    given x: Nat. contrapositive. Symmetry zero (succ x)
+
+theorem ReflInduction :
+    Eq zero zero -> (forall x: Nat. Eq x x -> Eq (succ x) (succ x)) -> (forall x: Nat. Eq x x) :=
+        Induction [Formula lambda y: Nat. Eq y y]
+
+theorem HasZero : exists x: Nat. Eq x zero := exists_intro [exists x: Nat. Eq x zero] zero (Reflexivity zero)
+theorem HasZeroFlipped : exists x: Nat. Eq zero x :=
+    obtain w, h from HasZero. exists_intro [exists x: Nat. Eq zero x] w (Symmetry w zero $ h)
+
+theorem ZeroNotOneNeg : not Eq (succ zero) zero := not_intro ZeroNotOne
+theorem ReflBoth : Eq zero zero ∧ Eq (succ zero) (succ zero) := and_intro (Reflexivity zero) (Reflexivity (succ zero))
+theorem ReflEither : Eq zero zero ∨ Eq (succ zero) zero := or_intro_left [Eq (succ zero) zero] (and_elim_left ReflBoth)
+
+let add :: Term Nat -> Nat -> Nat
+axiom AddZero = forall x: Nat. add x zero = x
+theorem AddZeroSymm : forall x: Nat. x = add x zero := given x: Nat. symm (AddZero x)
+theorem AddZeroTwice : forall x: Nat. add (add x zero) zero = x := given x: Nat. trans (AddZero (add x zero)) (AddZero x)
+theorem SuccAddZero : forall x: Nat. succ (add x zero) = succ x := given x: Nat. congr succ (AddZero x)
+theorem ReflAddZero : Eq (add zero zero) (add zero zero) := rewrite (AddZeroSymm zero) in (Reflexivity zero)
+
+theorem SymmetryViaAssume : forall x: Nat. forall y: Nat. Eq x y -> Eq y x :=
+    given x: Nat. given y: Nat. assume h : Eq x y { Symmetry x y $ h }
+theorem AndComm : Eq zero zero ∧ Eq (succ zero) zero -> Eq (succ zero) zero ∧ Eq zero zero :=
+    assume h : Eq zero zero ∧ Eq (succ zero) zero { and_intro (and_elim_right h) (and_elim_left h) }
+
+proof TwoNotZero : Eq (succ (succ zero)) zero -> false {
+    have h1 : Eq (succ (succ zero)) zero -> false := ZeroNotSucc (succ zero);
+    have h2 : not Eq (succ (succ zero)) zero := not_intro h1;
+    show Eq (succ (succ zero)) zero -> false := assume h : Eq (succ (succ zero)) zero { not_elim h2 h }
+}
@@ -0,0 +1,15 @@
+-- Equality lemmas for terms of any type, restating the built-in rules as theorems.
+
+theorem EqRefl : forall A :: Type. forall x: A. x = x := given x: A. refl x
+
+theorem EqSymm : forall A :: Type. forall x: A. forall y: A. x = y -> y = x :=
+    given x: A. given y: A. assume h : x = y { symm h }
+
+theorem EqTrans : forall A :: Type. forall x: A. forall y: A. forall z: A. x = y -> y = z -> x = z :=
+    given x: A. given y: A. given z: A. assume xy : x = y { assume yz : y = z { trans xy yz } }
+
+theorem EqCongr : forall A :: Type. forall B :: Type. forall f: A -> B. forall x: A. forall y: A. x = y -> f x = f y :=
+    given f: A -> B. given x: A. given y: A. assume h : x = y { congr f h }
+
+theorem EqSubst : forall A :: Type. schema Phi :: Formula A. forall x: A. forall y: A. x = y -> Phi x -> Phi y :=
+    given x: A. given y: A. assume h : x = y { assume p : Phi x { leibniz [lambda z: A. Phi z] h p } }
@@ -0,0 +1,34 @@
+-- Propositional tautologies, stated as schemas over nullary formulae.
+
+theorem Id : schema P :: Formula. P -> P := assume h : P { h }
+
+theorem Const : schema P :: Formula. schema Q :: Formula. P -> Q -> P :=
+    assume hp : P { assume hq : Q { hp } }
+
+theorem ImpTrans : schema P :: Formula. schema Q :: Formula. schema R :: Formula.
+    (P -> Q) -> (Q -> R) -> P -> R :=
+        assume pq : P -> Q { assume qr : Q -> R { assume p : P { qr $ (pq $ p) } } }
+
+theorem ExFalso : schema P :: Formula. false -> P := assume h : false { false_elim [P] h }
+
+theorem AndComm : schema P :: Formula. schema Q :: Formula. P ∧ Q -> Q ∧ P :=
+    assume h : P ∧ Q { and_intro (and_elim_right h) (and_elim_left h) }
+
+theorem OrComm : schema P :: Formula. schema Q :: Formula. P ∨ Q -> Q ∨ P :=
+    assume h : P ∨ Q {
+        or_elim h (assume p : P { or_intro_right [Q] p }) (assume q : Q { or_intro_left [P] q })
+    }
+
+theorem DoubleNegIntro : schema P :: Formula. P -> not not P :=
+    assume p : P { not_intro (assume np : not P { not_elim np p }) }
+
+theorem ModusTollens : schema P :: Formula. schema Q :: Formula. (P -> Q) -> not Q -> not P :=
+    assume pq : P -> Q { assume nq : not Q { not_intro (assume p : P { not_elim nq (pq $ p) }) } }
+
+theorem NonContradiction : schema P :: Formula. not (P ∧ not P) :=
+    not_intro (assume h : P ∧ not P { not_elim (and_elim_right h) (and_elim_left h) })
+
+theorem IffRefl : schema P :: Formula. P <-> P := iff_intro (assume p : P { p }) (assume p : P { p })
+
+theorem IffSymm : schema P :: Formula. schema Q :: Formula. (P <-> Q) -> (Q <-> P) :=
+    assume h : P <-> Q { iff_intro (iff_elim_right h) (iff_elim_left h) }
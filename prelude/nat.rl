@@ -0,0 +1,39 @@
+-- Peano arithmetic with addition and multiplication.
+
+data Nat = zero | succ Nat
+let add :: Term Nat -> Nat -> Nat
+let mul :: Term Nat -> Nat -> Nat
+
+axiom AddZero = forall x: Nat. add x zero = x
+axiom AddSucc = forall x: Nat. forall y: Nat. add x (succ y) = succ (add x y)
+axiom MulZero = forall x: Nat. mul x zero = zero
+axiom MulSucc = forall x: Nat. forall y: Nat. mul x (succ y) = add (mul x y) x
+
+theorem SuccNotZero : forall x: Nat. not succ x = zero := given x: Nat. not_intro (assume h : succ x = zero { ZeroNotSucc x $ symm h })
+
+theorem ZeroNotOne : not zero = succ zero :=
+    not_intro (ZeroNotSucc zero)
+
+theorem SuccNeq : forall x: Nat. forall y: Nat. not x = y -> not succ x = succ y :=
+    given x: Nat. given y: Nat. assume h : not x = y {
+        not_intro (assume e : succ x = succ y { not_elim h (SuccInj x y $ e) })
+    }
+
+theorem AddOne : forall x: Nat. add x (succ zero) = succ x :=
+    given x: Nat. trans (AddSucc x zero) (congr succ (AddZero x))
+
+theorem ZeroAdd : forall x: Nat. add zero x = x :=
+    NatInduction [Formula lambda n: Nat. add zero n = n]
+        $ AddZero zero
+        $ (given n: Nat. assume h : add zero n = n { trans (AddSucc zero n) (congr succ h) })
+
+theorem SuccAdd : forall x: Nat. forall y: Nat. add (succ x) y = succ (add x y) :=
+    given x: Nat. NatInduction [Formula lambda n: Nat. add (succ x) n = succ (add x n)]
+        $ trans (AddZero (succ x)) (congr succ (symm (AddZero x)))
+        $ (given n: Nat. assume h : add (succ x) n = succ (add x n) {
+            trans (AddSucc (succ x) n) (trans (congr succ h) (congr succ (symm (AddSucc x n))))
+        })
+
+theorem MulOne : forall x: Nat. mul x (succ zero) = x :=
+    given x: Nat. trans (MulSucc x zero)
+        (trans (leibniz [lambda z: Nat. add (mul x zero) x = add z x] (MulZero x) (refl (add (mul x zero) x))) (ZeroAdd x))
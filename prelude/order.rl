@@ -0,0 +1,28 @@
+-- The usual order on the naturals, defined through addition.
+
+open import prelude.nat
+
+let le :: Formula Nat Nat
+let lt :: Formula Nat Nat
+
+axiom LeDef = forall x: Nat. forall y: Nat. le x y <-> (exists k: Nat. add x k = y)
+axiom LtDef = forall x: Nat. forall y: Nat. lt x y <-> le (succ x) y
+
+theorem LeRefl : forall x: Nat. le x x :=
+    given x: Nat. iff_elim_right (LeDef x x) $ exists_intro [exists k: Nat. add x k = x] zero (AddZero x)
+
+theorem LeSucc : forall x: Nat. le x (succ x) :=
+    given x: Nat. iff_elim_right (LeDef x (succ x)) $ exists_intro [exists k: Nat. add x k = succ x] (succ zero) (AddOne x)
+
+theorem ZeroLe : forall x: Nat. le zero x :=
+    given x: Nat. iff_elim_right (LeDef zero x) $ exists_intro [exists k: Nat. add zero k = x] x (ZeroAdd x)
+
+theorem LtSucc : forall x: Nat. lt x (succ x) :=
+    given x: Nat. iff_elim_right (LtDef x (succ x)) $ LeRefl (succ x)
+
+theorem LeSuccMono : forall x: Nat. forall y: Nat. le x y -> le (succ x) (succ y) :=
+    given x: Nat. given y: Nat. assume h : le x y {
+        obtain k, e from iff_elim_left (LeDef x y) $ h.
+            iff_elim_right (LeDef (succ x) (succ y))
+                $ exists_intro [exists j: Nat. add (succ x) j = succ y] k (trans (SuccAdd x k) (congr succ e))
+    }
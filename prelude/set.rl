@@ -0,0 +1,40 @@
+-- Sets of elements of a single sort, with membership, inclusion and the basic operations.
+
+let Elem :: Type
+let Set :: Type
+let mem :: Formula Elem Set
+let subset :: Formula Set Set
+let empty :: Term Set
+let union :: Term Set -> Set -> Set
+let inter :: Term Set -> Set -> Set
+
+axiom Extensionality = forall a: Set. forall b: Set. (forall x: Elem. mem x a <-> mem x b) -> a = b
+axiom SubsetDef = forall a: Set. forall b: Set. subset a b <-> (forall x: Elem. mem x a -> mem x b)
+axiom EmptyDef = forall x: Elem. not mem x empty
+axiom UnionDef = forall a: Set. forall b: Set. forall x: Elem. mem x (union a b) <-> mem x a ∨ mem x b
+axiom InterDef = forall a: Set. forall b: Set. forall x: Elem. mem x (inter a b) <-> mem x a ∧ mem x b
+
+theorem SubsetRefl : forall a: Set. subset a a :=
+    given a: Set. iff_elim_right (SubsetDef a a) $ (given x: Elem. assume h : mem x a { h })
+
+theorem SubsetTrans : forall a: Set. forall b: Set. forall c: Set. subset a b -> subset b c -> subset a c :=
+    given a: Set. given b: Set. given c: Set. assume ab : subset a b { assume bc : subset b c {
+        iff_elim_right (SubsetDef a c) $ (given x: Elem. assume h : mem x a {
+            (iff_elim_left (SubsetDef b c) $ bc) x $ ((iff_elim_left (SubsetDef a b) $ ab) x $ h)
+        })
+    } }
+
+theorem EmptySubset : forall a: Set. subset empty a :=
+    given a: Set. iff_elim_right (SubsetDef empty a) $ (given x: Elem. assume h : mem x empty {
+        false_elim [mem x a] (not_elim (EmptyDef x) h)
+    })
+
+theorem SubsetUnion : forall a: Set. forall b: Set. subset a (union a b) :=
+    given a: Set. given b: Set. iff_elim_right (SubsetDef a (union a b)) $ (given x: Elem. assume h : mem x a {
+        iff_elim_right (UnionDef a b x) $ or_intro_left [mem x b] h
+    })
+
+theorem InterSubset : forall a: Set. forall b: Set. subset (inter a b) a :=
+    given a: Set. given b: Set. iff_elim_right (SubsetDef (inter a b) a) $ (given x: Elem. assume h : mem x (inter a b) {
+        and_elim_left (iff_elim_left (InterDef a b x) $ h)
+    })
@@ -1,518 +1,1491 @@
-#![allow(dead_code)]
-
-// use std::boxed::Box;
-use std::collections::HashMap;
-
-use error;
-use error::FileLocation;
-use error::Error;
-use error::ErrorKind::*;
-pub use state::{Bindings, IDTracker, RLangRepr};
-pub use types::{InternalType, MetaType, MetaValue, Term, Formula, FormulaSchema};
-pub use types::Ident::*;
-
-
-pub struct LocalBindings {
-    glob_to_loc: HashMap<usize, usize>,
-    loc_to_glob: HashMap<usize, usize>,
-    local_types: HashMap<usize, MetaType>,
-}
-
-impl LocalBindings {
-    pub fn new() -> LocalBindings {
-        LocalBindings {
-            glob_to_loc: HashMap::new(),
-            loc_to_glob: HashMap::new(),
-            local_types: HashMap::new(),
-        }
-    }
-
-    pub fn is_empty(&self) -> bool {
-        self.glob_to_loc.is_empty()
-    }
-
-    pub fn get_local(&self, id: &usize) -> Option<usize> {
-        if let Some(&local_id) = self.glob_to_loc.get(id) { Some(local_id) } else { None }
-    }
-
-    pub fn get_global(&self, local_id: &usize) -> Option<usize> {
-        if let Some(&id) = self.loc_to_glob.get(local_id) { Some(id) } else { None }
-    }
-
-    pub fn get_type(&self, local_id: &usize) -> &MetaType {
-        self.local_types.get(local_id).unwrap()
-    }
-
-    pub fn insert(&mut self, id: usize, local_id: usize, mtype: MetaType) -> usize {
-        assert!(!self.glob_to_loc.contains_key(&id));
-        self.glob_to_loc.insert(id, local_id);
-        self.loc_to_glob.insert(local_id, id);
-        self.local_types.insert(local_id, mtype);
-        local_id
-    }
-
-    pub fn remove(&mut self, local_id: &usize) -> MetaType {
-        let id = self.loc_to_glob.remove(local_id).unwrap();
-        self.glob_to_loc.remove(&id).unwrap();
-        self.local_types.remove(local_id).unwrap()
-    }
-}
-
-
-pub struct TermBuilder {
-    itype: InternalType,
-    value: Term,
-    location: FileLocation,
-}
-
-impl TermBuilder {
-    pub fn symbol(id: usize, locals: &LocalBindings, globals: &Bindings, location: FileLocation) -> error::Result<TermBuilder> {
-        if let Some(local_id) = locals.get_local(&id) {
-            let mtype = locals.get_type(&local_id);
-            if let &MetaType::Term(ref itype) = mtype {
-                Ok(TermBuilder {
-                    itype: itype.clone(),
-                    value: Term::Symbol(Local(local_id)),
-                    location: location,
-                })
-            } else {
-                Err(Error::new(MTypeMismatch {
-                    found: mtype.repr(globals),
-                    expected: String::from("Term _"),
-                }, &location))
-            }
-        } else if let Some(MetaType::Term(ref itype)) = globals.get_type(&id) {
-            Ok(TermBuilder {
-                itype: itype.clone(),
-                value: Term::Symbol(Global(id)),
-                location: location,
-            })
-        } else {
-            Err(Error::new(NoBinding {
-                name: globals.get_name(&id).unwrap().clone(),
-            }, &location))
-        }
-    }
-
-    pub fn application(function: TermBuilder, argument: TermBuilder, globals: &Bindings) -> error::Result<TermBuilder> {
-        if let InternalType::Func(box arg_type, box ret_type) = function.itype {
-            if argument.itype == arg_type {
-                Ok(TermBuilder {
-                    itype: ret_type,
-                    value: Term::Application(Box::new(function.value), Box::new(argument.value)),
-                    location: function.location,
-                })
-            } else {
-                Err(Error::new(ITypeMismatch {
-                    found: arg_type.repr(globals),
-                    expected: argument.itype.repr(globals),
-                }, &argument.location))
-            }
-        } else {
-            Err(Error::new(ITypeMismatch {
-                found: function.itype.repr(globals),
-                expected: format!("{} -> _", argument.itype.repr(globals)),
-            }, &function.location))
-        }
-    }
-}
-
-
-pub struct FormulaBuilder {
-    arg_types: Vec<InternalType>,
-    value: Formula,
-    location: FileLocation,
-}
-
-impl FormulaBuilder {
-    pub fn false_(location: FileLocation) -> error::Result<FormulaBuilder> {
-        Ok(FormulaBuilder {
-            arg_types: Vec::new(),
-            value: Formula::False,
-            location: location,
-        })
-    }
-
-    pub fn relation(id: usize, locals: &LocalBindings, globals: &Bindings, location: FileLocation) -> error::Result<FormulaBuilder> {
-        if let Some(local_id) = locals.get_local(&id) {
-            let mtype = locals.get_type(&local_id);
-            if let &MetaType::Formula(ref arg_types) = mtype {
-                Ok(FormulaBuilder {
-                    arg_types: arg_types.clone(),
-                    value: Formula::Relation(Local(local_id)),
-                    location: location,
-                })
-            } else {
-                Err(Error::new(MTypeMismatch {
-                    found: mtype.repr(globals),
-                    expected: String::from("Formula _*"),
-                }, &location))
-            }
-        } else if let Some(MetaType::Formula(ref arg_types)) = globals.get_type(&id) {
-            Ok(FormulaBuilder {
-                arg_types: arg_types.clone(),
-                value: Formula::Relation(Global(id)),
-                location: location,
-            })
-        } else {
-            Err(Error::new(NoBinding {
-                name: globals.get_name(&id).unwrap().clone(),
-            }, &location))
-        }
-    }
-
-    pub fn application(mut predicate: FormulaBuilder, term: TermBuilder, globals: &Bindings) -> error::Result<FormulaBuilder> {
-        if let Some(term_type) = predicate.arg_types.pop() {
-            if term.itype == term_type {
-                Ok(FormulaBuilder {
-                    arg_types: predicate.arg_types,
-                    value: Formula::Application(Box::new(predicate.value), term.value),
-                    location: predicate.location,
-                })
-            } else {
-                Err(Error::new(ITypeMismatch {
-                    found: term.itype.repr(globals),
-                    expected: term_type.repr(globals),
-                }, &term.location))
-            }
-        } else {
-            Err(Error::new(MTypeMismatch {
-                found: MetaType::Formula(Vec::new()).repr(globals),
-                expected: format!("Formula {} _", term.itype.repr(globals)),
-            }, &predicate.location))
-        }
-    }
-
-    pub fn implication(lhs: FormulaBuilder, rhs: FormulaBuilder) -> error::Result<FormulaBuilder> {
-        if lhs.arg_types.len() == 0 && rhs.arg_types.len() == 0 {
-            Ok(FormulaBuilder {
-                arg_types: lhs.arg_types,
-                value: Formula::Implication(Box::new(lhs.value), Box::new(rhs.value)),
-                location: lhs.location,
-            })
-        } else {
-            Err(Error::new(UnboundImplication, &lhs.location))
-        }
-    }
-
-    pub fn quantifier_prep(id: usize, itype: InternalType, locals: &mut LocalBindings, globals: &mut Bindings, location: FileLocation) -> error::Result<()> {
-        if let None = locals.get_local(&id) {
-            locals.insert(id, globals.new_local(), MetaType::Term(itype));
-            Ok(())
-        } else {
-            Err(Error::new(BindingExists {
-                name: globals.get_name(&id).unwrap().clone(),
-            }, &location))
-        }
-    }
-
-    pub fn universal_q(id: usize, itype: InternalType, formula: FormulaBuilder, locals: &mut LocalBindings, location: FileLocation) -> error::Result<FormulaBuilder> {
-        let local_id = locals.get_local(&id).unwrap();
-        locals.remove(&local_id);
-        Ok(FormulaBuilder {
-            arg_types: formula.arg_types,
-            value: Formula::UniversalQ(local_id, itype, Box::new(formula.value)),
-            location: location,
-        })
-    }
-
-    pub fn value(self) -> Formula {
-        self.value
-    }
-
-    // pub fn contrapositive(self, location: FileLocation) -> error::Result<FormulaBuilder> {
-    //     if let Formula::Implication(box lhs, box rhs) = self.value {
-    //         Ok(FormulaBuilder {
-    //             arg_types: self.arg_types,
-    //             value: Formula::Implication(Box::new(rhs.negate()), Box::new(lhs.negate())),
-    //             location: location,
-    //         })
-    //     } else {
-    //         Err(Error::new(NotImplicationContra, &location))
-    //     }
-    // }
-
-    pub fn modus_ponens(self, antecedent: FormulaBuilder) -> error::Result<FormulaBuilder> {
-        if let Formula::Implication(box lhs, box rhs) = self.value {
-            let mut assoc_sto: HashMap<usize, usize> = HashMap::new();
-            let mut assoc_ots: HashMap<usize, usize> = HashMap::new();
-            if Formula::matches(&lhs, &antecedent.value, &mut assoc_sto, &mut assoc_ots) {
-                Ok(FormulaBuilder {
-                    arg_types: self.arg_types,
-                    value: rhs,
-                    location: self.location,
-                })
-            } else {
-                Err(Error::new(ModusPonensMismatch, &self.location))
-            }
-        } else {
-            Err(Error::new(NotImplicationMP, &self.location))
-        }
-    }
-
-    pub fn instantiate(self, term: TermBuilder, globals: &Bindings) -> error::Result<FormulaBuilder> {
-        if let Formula::UniversalQ(var, itype, box form) = self.value {
-            if term.itype == itype {
-                Ok(FormulaBuilder {
-                    arg_types: self.arg_types,
-                    value: form.substitute(var, &term.value),
-                    location: term.location,
-                })
-            } else {
-                Err(Error::new(ITypeMismatch {
-                    found: term.itype.repr(globals),
-                    expected: itype.repr(globals),
-                }, &term.location))
-            }
-        } else {
-            Err(Error::new(NotUniversalQInst, &term.location))
-        }
-    }
-}
-
-
-pub struct FSchemaBuilder {
-    // marg_types: Vec<MetaType>,
-    iarg_types: Vec<InternalType>,
-    value: FormulaSchema,
-    location: FileLocation,
-}
-
-impl FSchemaBuilder {
-    pub fn formula(formula: FormulaBuilder) -> error::Result<FSchemaBuilder> {
-        Ok(FSchemaBuilder {
-            // marg_types: Vec::new(),
-            iarg_types: formula.arg_types,
-            value: FormulaSchema::Formula(formula.value),
-            location: formula.location,
-        })
-    }
-
-    pub fn schema_prep(id: usize, mtype: MetaType, locals: &mut LocalBindings, globals: &mut Bindings, location: FileLocation) -> error::Result<()> {
-        if let None = locals.get_local(&id) {
-            locals.insert(id, globals.new_local(), mtype);
-            Ok(())
-        } else {
-            Err(Error::new(BindingExists {
-                name: globals.get_name(&id).unwrap().clone(),
-            }, &location))
-        }
-    }
-
-    pub fn schema(id: usize, mtype: MetaType, schema: FSchemaBuilder, locals: &mut LocalBindings, location: FileLocation) -> error::Result<FSchemaBuilder> {
-        let local_id = locals.get_local(&id).unwrap();
-        locals.remove(&local_id);
-        // schema.marg_types.push(mtype.clone());
-        Ok(FSchemaBuilder {
-            // marg_types: schema.marg_types,
-            iarg_types: schema.iarg_types,
-            value: FormulaSchema::Schema(local_id, mtype, Box::new(schema.value)),
-            location: location,
-        })
-    }
-
-    pub fn saved_theorem(id: usize, globals: &Bindings, location: FileLocation) -> error::Result<FSchemaBuilder> {
-        if let Some(theorem) = globals.get_theorem(&id) {
-            Ok(FSchemaBuilder {
-                iarg_types: Vec::new(),
-                value: theorem.clone(),
-                location: location,
-            })
-        } else {
-            Err(Error::new(NoBinding {
-                name: globals.get_name(&id).unwrap().clone(),
-            }, &location))
-        }
-    }
-
-    pub fn is_wff_schema(&self) -> bool {
-        self.iarg_types.len() == 0
-    }
-
-    pub fn value(self) -> FormulaSchema {
-        self.value
-    }
-
-    // pub fn get_formula(self) -> error::Result<FormulaBuilder> {
-    //     if let FormulaSchema::Formula(form) = self.value {
-    //         Ok(FormulaBuilder {
-    //             arg_types: Vec::new(),
-    //             value: form,
-    //             location: self.location,
-    //         })
-    //     } else {
-            
-    //     }
-    // }
-
-    // pub fn specify(self, formula: FormulaBuilder) -> error::Result<FSchemaBuilder> {
-    //     if formula_sch.marg_types.len() == 0 {
-    //         if let FSchemaBuilder { value: FormulaSchema::Formula(formula), .. } = formula_sch {
-
-    //         }
-    //     }
-    // }
-}
-
-
-// fn singleton<T>(x: T) -> Vec<T> {
-//     let mut lst = Vec::new();
-//     lst.push_front(x);
-//     lst
-// }
-
-
-// fn concat_bindings<T>(mut lhs: Vec<T>, rhs: Vec<T>) -> Vec<T> {
-//     'rloop: for id, val in rhs.iter() {
-//         for id_, _ in lhs.iter() {
-//             if id == id_ {
-//                 continue 'rloop;
-//             }
-//         }
-//         lhs.push_back((id, val));
-//     }
-//     lhs
-// }
-
-
-// fn change_binding<I: Debug + PartialEq, T>(mut bind: Vec<(I, T)>, id: &I, val: T) -> Result<Vec<(I, T)>, String> {
-//     let mut good = false;
-//     for id_, val_ in bind.iter_mut() {
-//         if id == id_ {
-//             *val_ = val;
-//             good = true;
-//             break;
-//         }
-//     }
-//     if good { Ok(bind) } else { Err(format!("binding {:?} not found", id)) }
-// }
-
-
-// enum TypeVar<'a> {
-//     Unknown,
-//     Concrete(InternalType),
-//     EqualTo(&'a TypeVar<'a>),
-// }
-
-
-// enum PartialIType<'a> {
-//     Just(&'a TypeVar<'a>),
-//     Func(Box<PartialIType<'a>>, Box<PartialIType<'a>>),
-// }
-
-
-// pub struct LocalBindings<'a> {
-//     types: HashMap<usize, PartialIType>,
-//     tvar_arena: TypedArena<TypeVar<'a>>,
-// }
-
-// impl<'a> LocalBindings<'a> {
-//     pub fn new() -> LocalBindings<'a> {
-//         LocalBindings {
-//             types: HashMap::new(),
-//             tvar_arena: TypedArena::new(),
-//         }
-//     }
-
-//     pub fn term_type(&mut self, term: &Term, bindings: &mut Bindings) -> PartialIType<'a> {
-//         match term {
-//             Symbol(id) => if let Some(itype) = 
-//         }
-//     }
-// }
-
-
-// pub struct TermTypes {
-//     term: Term,
-//     itype: PartialIType,
-//     vtypes: Vec<(usize, PartialIType)>,
-// }
-
-// impl TermTypes {
-//     pub fn symbol(id: usize, bindings: &Bindings) -> TermTypes {
-//         let known_type = bindings.get_term_type(id);
-//         TermTypes {
-//             term: Term::Symbol(id),
-//             itype: if let Some(itype) = known_type { Complete(itype) } else { Unknown },
-//             vtypes: if let Some(_) = known_type { vec![] } else { vec![(id, Unknown)] },
-//         }
-//     }
-
-//     pub fn application(lhs: TermTypes, rhs: TermTypes, bindings: &Bindings) -> Result<TermTypes, String> {
-//         match lhs.itype {
-//             Complete(ltype) => if let Func(box lltype, box lrtype) = ltype {
-//                 match rhs.itype {
-//                     Complete(rtype)  => if lltype == rtype {
-//                         Ok(TermTypes {
-//                             term: Term::Application(Box::new(lhs.term), Box::new(rhs.term)),
-//                             itype: Complete(lrtype),
-//                             vtypes: concat_bindings(lhs.vtypes, rhs.vtypes),
-//                         })
-//                     } else { Err(format!("type mismatch : expected type {}, found type {}", lltype, rtype)) },
-//                     FuncFrom(rltype) => if let Application(box Symbol(rlid), _) = rhs.term {
-//                         Ok(TermTypes {
-//                             term: Term::Application(Box::new(lhs.term), Box::new(rhs.term)),
-//                             itype: Complete(lrtype),
-//                             vtypes: change_binding(concat_bindings(lhs.vtypes, rhs.vtypes), &rlid, Complete(Func(Box::new(rltype), Box::new(lltype)))).unwrap(),
-//                         })
-//                     } else { Err(format!("i have no idea how i got here")) }
-//                     Unknown          => if let Symbol(rid) = rhs.term {
-//                         Ok(TermTypes {
-//                             term: Term::Application(Box::new(lhs.term), Box::new(rhs.term)),
-//                             itype: Complete(lrtype),
-//                             vtypes: change_binding(concat_bindings(lhs.vtypes, rhs.vtypes), &rid, Complete(lltype)).unwrap(),
-//                         })
-//                     } else {
-//                         Err(format!("this shouldn't happen"))
-//                     }
-//                 }
-//             } else { Err(format!("cannot apply non-function term to another term")) }
-//             FuncFrom(_)     => Err(format!("i think this is unreachable"))
-//             Unknown         => match rhs.itype {
-//                 Complete(rtype) => if let Symbol(lid) = lhs.term {
-//                     Ok(TermTypes {
-//                         term: Term::Application(Box::new(lhs.term), Box::new(rhs.term)),
-//                         itype: Unknown,
-//                         vtypes: change_binding(concat_bindings(lhs.vtypes, rhs.vtypes), &lid, FuncFrom(rtype)).unwrap(),
-//                     })
-//                 }
-//             }
-//         }
-//     }
-// }
-
-
-// #[derive(Debug)]
-// pub enum Expression {
-//     Formula(Formula),
-//     Schema(String, MetaType, Box<Expression>),
-//     Generalization(usize, InternalType, Box<Expression>),
-//     Assuming(Formula, Box<Expression>),
-//     Contrapositive(Formula),
-//     Block(StatementBlock),
-// }
-
-
-// #[derive(Debug)]
-// pub enum Pattern {
-//     Wildcard,
-//     Symbol(usize),
-//     Application(Box<Pattern>, usize),
-// }
-
-
-// #[derive(Debug)]
-// pub enum Statement {
-//     LetDec(String, MetaType),
-//     LetEq(Pattern, Expression),
-//     Axiom(Expression),
-//     Proof(String, Expression, Expression)
-//     Module(String, StatementBlock),
-//     Import(Vec<String>, bool),
-// }
-
-
-// #[derive(Debug)]
-// pub struct StatementBlock {
-//     stmts: Vec<Statement>,
-// }
+#![allow(dead_code)]
+
+// use std::boxed::Box;
+use std::collections::HashMap;
+
+use error;
+use error::FileLocation;
+use error::Error;
+use error::ErrorKind::*;
+use pretty::Printer;
+pub use state::{Bindings, IDTracker, RLangRepr};
+pub use types::{InternalType, MetaType, MetaValue, Term, Formula, FormulaSchema, DataType};
+pub use types::Ident::*;
+
+
+fn declared_here(error: Error, id: Option<usize>, globals: &Bindings) -> Error {
+    match id.and_then(|id| globals.get_site(&id).map(|site| (id, site))) {
+        Some((id, site)) => error.with_label(site, format!("`{}` declared here", globals.get_name(&id).unwrap())),
+        None             => error,
+    }
+}
+
+
+pub fn quantify_type(id: usize, mtype: error::Result<MetaType>, locals: &mut LocalBindings) -> error::Result<MetaType> {
+    let local_id = locals.get_local(&id).unwrap();
+    locals.remove(&local_id);
+    match mtype? {
+        MetaType::Poly(mut vars, mtype) => {
+            vars.insert(0, local_id);
+            Ok(MetaType::Poly(vars, mtype))
+        },
+        mtype                           => Ok(MetaType::Poly(vec![local_id], Box::new(mtype))),
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Declaration {
+    Object(usize),
+    Axiom(usize),
+    Theorem(usize),
+    Data(usize),
+}
+
+
+impl Declaration {
+    pub fn id(&self) -> usize {
+        match self {
+            &Declaration::Object(id)  => id,
+            &Declaration::Axiom(id)   => id,
+            &Declaration::Theorem(id) => id,
+            &Declaration::Data(id)    => id,
+        }
+    }
+}
+
+impl RLangRepr for Declaration {
+    fn repr(&self, globals: &Bindings) -> String {
+        match self {
+            &Declaration::Object(id)  => {
+                format!("let {} :: {}", globals.get_name(&id).unwrap(), globals.get_type(&id).unwrap().repr(globals))
+            },
+            &Declaration::Axiom(id)   => {
+                format!("axiom {} = {}", globals.get_name(&id).unwrap(), globals.get_theorem(&id).unwrap().repr(globals))
+            },
+            &Declaration::Theorem(id) => {
+                format!("theorem {} : {}", globals.get_name(&id).unwrap(), globals.get_theorem(&id).unwrap().repr(globals))
+            },
+            &Declaration::Data(id)    => {
+                let data = globals.get_data(&id).unwrap();
+                let constructors: Vec<String> = data.constructors.iter().map(|ctor| {
+                    let mut result = globals.get_name(ctor).unwrap().clone();
+                    let mut itype = if let Some(MetaType::Term(itype)) = globals.get_type(ctor) { itype } else { unreachable!() };
+                    while let InternalType::Func(box arg_type, box ret_type) = itype {
+                        result = format!("{} {}", result, Printer::new(globals).type_argument(&arg_type));
+                        itype = ret_type;
+                    }
+                    result
+                }).collect();
+                data.axioms.iter().fold(format!("data {} = {}", globals.get_name(&id).unwrap(), constructors.join(" | ")), |acc, axiom| {
+                    format!("{}\n    axiom {} = {}", acc, globals.get_name(axiom).unwrap(), globals.get_theorem(axiom).unwrap().repr(globals))
+                })
+            },
+        }
+    }
+}
+
+
+pub struct LocalBindings {
+    glob_to_loc: HashMap<usize, usize>,
+    loc_to_glob: HashMap<usize, usize>,
+    local_types: HashMap<usize, MetaType>,
+}
+
+impl LocalBindings {
+    pub fn new() -> LocalBindings {
+        LocalBindings {
+            glob_to_loc: HashMap::new(),
+            loc_to_glob: HashMap::new(),
+            local_types: HashMap::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.glob_to_loc.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.glob_to_loc.clear();
+        self.loc_to_glob.clear();
+        self.local_types.clear();
+    }
+
+    pub fn get_local(&self, id: &usize) -> Option<usize> {
+        if let Some(&local_id) = self.glob_to_loc.get(id) { Some(local_id) } else { None }
+    }
+
+    pub fn get_global(&self, local_id: &usize) -> Option<usize> {
+        if let Some(&id) = self.loc_to_glob.get(local_id) { Some(id) } else { None }
+    }
+
+    pub fn get_type(&self, local_id: &usize) -> &MetaType {
+        self.local_types.get(local_id).unwrap()
+    }
+
+    pub fn insert(&mut self, id: usize, local_id: usize, mtype: MetaType) -> usize {
+        assert!(!self.glob_to_loc.contains_key(&id));
+        self.glob_to_loc.insert(id, local_id);
+        self.loc_to_glob.insert(local_id, id);
+        self.local_types.insert(local_id, mtype);
+        local_id
+    }
+
+    pub fn remove(&mut self, local_id: &usize) -> MetaType {
+        let id = self.loc_to_glob.remove(local_id).unwrap();
+        self.glob_to_loc.remove(&id).unwrap();
+        self.local_types.remove(local_id).unwrap()
+    }
+}
+
+
+pub struct TermBuilder {
+    itype: InternalType,
+    value: Term,
+    location: FileLocation,
+}
+
+impl TermBuilder {
+    pub fn symbol(id: usize, locals: &LocalBindings, globals: &mut Bindings, location: FileLocation) -> error::Result<TermBuilder> {
+        if let Some(local_id) = locals.get_local(&id) {
+            let mtype = locals.get_type(&local_id);
+            if let &MetaType::Term(ref itype) = mtype {
+                Ok(TermBuilder {
+                    itype: itype.clone(),
+                    value: Term::Symbol(Local(local_id), Vec::new()),
+                    location: location,
+                })
+            } else {
+                Err(Error::new(MTypeMismatch {
+                    found: mtype.repr(globals),
+                    expected: String::from("Term _"),
+                }, &location))
+            }
+        } else if let Some((MetaType::Term(ref itype), instance)) = globals.get_type(&id).map(|mtype| globals.instantiate(id, mtype, &location)) {
+            Ok(TermBuilder {
+                itype: itype.clone(),
+                value: Term::Symbol(Global(id), instance),
+                location: location,
+            })
+        } else {
+            Err(globals.no_binding(&id, |other| locals.get_local(other).is_some(), &location))
+        }
+    }
+
+    pub fn application(function: TermBuilder, argument: TermBuilder, globals: &mut Bindings) -> error::Result<TermBuilder> {
+        if let InternalType::Func(box arg_type, box ret_type) = globals.function_type(&function.itype) {
+            if globals.unify(&argument.itype, &arg_type) {
+                Ok(TermBuilder {
+                    itype: ret_type,
+                    value: Term::Application(Box::new(function.value), Box::new(argument.value)),
+                    location: function.location.to(&argument.location),
+                })
+            } else {
+                let expected = globals.resolve_type(&arg_type);
+                let error = Error::new(ITypeMismatch {
+                    found: globals.resolve_type(&argument.itype).repr(globals),
+                    expected: expected.repr(globals),
+                }, &argument.location)
+                    .with_label(&function.location, format!("expects an argument of type `{}`", expected.repr(globals)));
+                let error = globals.explain_types(error, &[&argument.itype, &arg_type]);
+                Err(declared_here(error, function.value.head_global(), globals))
+            }
+        } else {
+            Err(Error::new(ITypeMismatch {
+                found: globals.resolve_type(&function.itype).repr(globals),
+                expected: format!("{} -> _", globals.resolve_type(&argument.itype).repr(globals)),
+            }, &function.location))
+        }
+    }
+}
+
+
+pub struct FormulaBuilder {
+    arg_types: Vec<InternalType>,
+    value: Formula,
+    location: FileLocation,
+}
+
+impl FormulaBuilder {
+    pub fn false_(location: FileLocation) -> error::Result<FormulaBuilder> {
+        Ok(FormulaBuilder {
+            arg_types: Vec::new(),
+            value: Formula::False,
+            location: location,
+        })
+    }
+
+    pub fn relation(id: usize, locals: &LocalBindings, globals: &mut Bindings, location: FileLocation) -> error::Result<FormulaBuilder> {
+        if let Some(local_id) = locals.get_local(&id) {
+            let mtype = locals.get_type(&local_id);
+            if let &MetaType::Formula(ref arg_types) = mtype {
+                Ok(FormulaBuilder {
+                    arg_types: arg_types.clone(),
+                    value: Formula::Relation(Local(local_id), Vec::new()),
+                    location: location,
+                })
+            } else {
+                Err(Error::new(MTypeMismatch {
+                    found: mtype.repr(globals),
+                    expected: String::from("Formula _*"),
+                }, &location))
+            }
+        } else if let Some((MetaType::Formula(ref arg_types), instance)) = globals.get_type(&id).map(|mtype| globals.instantiate(id, mtype, &location)) {
+            Ok(FormulaBuilder {
+                arg_types: arg_types.clone(),
+                value: Formula::Relation(Global(id), instance),
+                location: location,
+            })
+        } else {
+            Err(globals.no_binding(&id, |other| locals.get_local(other).is_some(), &location))
+        }
+    }
+
+    pub fn application(mut predicate: FormulaBuilder, term: TermBuilder, globals: &Bindings) -> error::Result<FormulaBuilder> {
+        if let Some(term_type) = predicate.arg_types.pop() {
+            if globals.unify(&term.itype, &term_type) {
+                Ok(FormulaBuilder {
+                    arg_types: predicate.arg_types,
+                    value: Formula::Application(Box::new(predicate.value), term.value),
+                    location: predicate.location.to(&term.location),
+                })
+            } else {
+                let expected = globals.resolve_type(&term_type);
+                let error = Error::new(ITypeMismatch {
+                    found: globals.resolve_type(&term.itype).repr(globals),
+                    expected: expected.repr(globals),
+                }, &term.location)
+                    .with_label(&predicate.location, format!("expects an argument of type `{}`", expected.repr(globals)));
+                let error = globals.explain_types(error, &[&term.itype, &term_type]);
+                Err(declared_here(error, predicate.value.head_global(), globals))
+            }
+        } else {
+            Err(Error::new(MTypeMismatch {
+                found: MetaType::Formula(Vec::new()).repr(globals),
+                expected: format!("Formula {} _", globals.resolve_type(&term.itype).repr(globals)),
+            }, &predicate.location))
+        }
+    }
+
+    pub fn equality(lhs: TermBuilder, rhs: TermBuilder, globals: &Bindings) -> error::Result<FormulaBuilder> {
+        if globals.unify(&lhs.itype, &rhs.itype) {
+            Ok(FormulaBuilder {
+                arg_types: Vec::new(),
+                value: Formula::Equality(globals.resolve_type(&lhs.itype), lhs.value, rhs.value),
+                location: lhs.location,
+            })
+        } else {
+            let error = Error::new(ITypeMismatch {
+                found: globals.resolve_type(&rhs.itype).repr(globals),
+                expected: globals.resolve_type(&lhs.itype).repr(globals),
+            }, &rhs.location)
+                .with_label(&lhs.location, format!("this has type `{}`", globals.resolve_type(&lhs.itype).repr(globals)));
+            Err(globals.explain_types(error, &[&lhs.itype, &rhs.itype]))
+        }
+    }
+
+    pub fn implication(lhs: FormulaBuilder, rhs: FormulaBuilder) -> error::Result<FormulaBuilder> {
+        if lhs.arg_types.len() == 0 && rhs.arg_types.len() == 0 {
+            Ok(FormulaBuilder {
+                arg_types: lhs.arg_types,
+                value: Formula::Implication(Box::new(lhs.value), Box::new(rhs.value)),
+                location: lhs.location,
+            })
+        } else {
+            Err(Error::new(UnboundImplication, &lhs.location))
+        }
+    }
+
+    pub fn negation(formula: FormulaBuilder, location: FileLocation) -> error::Result<FormulaBuilder> {
+        if formula.arg_types.len() == 0 {
+            Ok(FormulaBuilder {
+                arg_types: formula.arg_types,
+                value: Formula::Negation(Box::new(formula.value)),
+                location: location,
+            })
+        } else {
+            Err(Error::new(UnboundConnective, &formula.location))
+        }
+    }
+
+    pub fn conjunction(lhs: FormulaBuilder, rhs: FormulaBuilder) -> error::Result<FormulaBuilder> {
+        FormulaBuilder::connective(lhs, rhs, Formula::Conjunction)
+    }
+
+    pub fn disjunction(lhs: FormulaBuilder, rhs: FormulaBuilder) -> error::Result<FormulaBuilder> {
+        FormulaBuilder::connective(lhs, rhs, Formula::Disjunction)
+    }
+
+    pub fn biconditional(lhs: FormulaBuilder, rhs: FormulaBuilder) -> error::Result<FormulaBuilder> {
+        FormulaBuilder::connective(lhs, rhs, Formula::Biconditional)
+    }
+
+    fn connective(lhs: FormulaBuilder, rhs: FormulaBuilder, connective: fn(Box<Formula>, Box<Formula>) -> Formula) -> error::Result<FormulaBuilder> {
+        if lhs.arg_types.len() == 0 && rhs.arg_types.len() == 0 {
+            Ok(FormulaBuilder {
+                arg_types: lhs.arg_types,
+                value: connective(Box::new(lhs.value), Box::new(rhs.value)),
+                location: lhs.location,
+            })
+        } else {
+            Err(Error::new(UnboundConnective, &lhs.location))
+        }
+    }
+
+    pub fn quantifier_prep(id: usize, itype: InternalType, locals: &mut LocalBindings, globals: &mut Bindings, location: FileLocation) -> error::Result<()> {
+        if let None = locals.get_local(&id) {
+            locals.insert(id, globals.named_local(id), MetaType::Term(itype));
+            Ok(())
+        } else {
+            Err(Error::new(BindingExists {
+                name: globals.get_name(&id).unwrap().clone(),
+            }, &location))
+        }
+    }
+
+    pub fn inferred_prep(id: usize, locals: &mut LocalBindings, globals: &mut Bindings, location: FileLocation) -> error::Result<InternalType> {
+        let itype = globals.binder_type(id, &location);
+        FormulaBuilder::quantifier_prep(id, itype.clone(), locals, globals, location)?;
+        Ok(itype)
+    }
+
+    pub fn universal_q(id: usize, itype: InternalType, formula: error::Result<FormulaBuilder>, locals: &mut LocalBindings, globals: &Bindings, location: FileLocation) -> error::Result<FormulaBuilder> {
+        let local_id = locals.get_local(&id).unwrap();
+        locals.remove(&local_id);
+        let formula = formula?;
+        Ok(FormulaBuilder {
+            arg_types: formula.arg_types,
+            value: Formula::UniversalQ(local_id, globals.resolve_type(&itype), Box::new(globals.resolve_formula(formula.value))),
+            location: location,
+        })
+    }
+
+    pub fn existential_q(id: usize, itype: InternalType, formula: error::Result<FormulaBuilder>, locals: &mut LocalBindings, globals: &Bindings, location: FileLocation) -> error::Result<FormulaBuilder> {
+        let local_id = locals.get_local(&id).unwrap();
+        locals.remove(&local_id);
+        let formula = formula?;
+        Ok(FormulaBuilder {
+            arg_types: formula.arg_types,
+            value: Formula::ExistentialQ(local_id, globals.resolve_type(&itype), Box::new(globals.resolve_formula(formula.value))),
+            location: location,
+        })
+    }
+
+    pub fn abstraction(id: usize, itype: InternalType, formula: error::Result<FormulaBuilder>, locals: &mut LocalBindings, globals: &Bindings, location: FileLocation) -> error::Result<FormulaBuilder> {
+        let local_id = locals.get_local(&id).unwrap();
+        locals.remove(&local_id);
+        let mut formula = formula?;
+        formula.arg_types.push(itype.clone());
+        Ok(FormulaBuilder {
+            arg_types: formula.arg_types,
+            value: Formula::Abstraction(local_id, globals.resolve_type(&itype), Box::new(globals.resolve_formula(formula.value))),
+            location: location,
+        })
+    }
+
+    pub fn value(self) -> Formula {
+        self.value
+    }
+
+    pub fn prove(self, proof: FormulaBuilder, globals: &Bindings) -> error::Result<FormulaBuilder> {
+        if self.arg_types.len() != 0 || proof.arg_types.len() != 0 {
+            return Err(Error::new(UnboundTheorem, &self.location));
+        }
+        if globals.equivalent(&self.value, &proof.value) {
+            Ok(FormulaBuilder {
+                arg_types: self.arg_types,
+                value: globals.resolve_formula(self.value),
+                location: self.location,
+            })
+        } else {
+            Err(Error::new(ProofMismatch, &proof.location)
+                .with_label(&self.location, format!("the statement is `{}`", globals.resolve_formula(self.value).repr(globals)))
+                .with_note(format!("the proof establishes `{}`", globals.resolve_formula(proof.value).repr(globals))))
+        }
+    }
+
+    pub fn contrapositive(self, location: FileLocation) -> error::Result<FormulaBuilder> {
+        if let Formula::Implication(box lhs, box rhs) = self.value {
+            Ok(FormulaBuilder {
+                arg_types: self.arg_types,
+                value: Formula::Implication(Box::new(rhs.negate()), Box::new(lhs.negate())),
+                location: location,
+            })
+        } else {
+            Err(Error::new(NotImplicationContra, &location))
+        }
+    }
+
+    pub fn modus_ponens(self, antecedent: FormulaBuilder, globals: &Bindings) -> error::Result<FormulaBuilder> {
+        if let Formula::Implication(box lhs, box rhs) = self.value {
+            if globals.equivalent(&lhs, &antecedent.value) {
+                Ok(FormulaBuilder {
+                    arg_types: self.arg_types,
+                    value: globals.resolve_formula(rhs),
+                    location: self.location,
+                })
+            } else {
+                Err(Error::new(ModusPonensMismatch, &self.location)
+                    .with_label(&antecedent.location, format!("this proves `{}`", globals.resolve_formula(antecedent.value).repr(globals)))
+                    .with_note(format!("the implication requires `{}`", globals.resolve_formula(lhs).repr(globals))))
+            }
+        } else {
+            Err(Error::new(NotImplicationMP, &self.location))
+        }
+    }
+
+    pub fn assume_prep(hyp: usize, assumption: FormulaBuilder, globals: &mut Bindings, location: FileLocation) -> error::Result<FormulaBuilder> {
+        if assumption.arg_types.len() != 0 {
+            return Err(Error::new(UnboundImplication, &assumption.location));
+        }
+        globals.push_scope();
+        globals.insert_theorem(hyp, FormulaSchema::Formula(assumption.value.clone()), &location)?;
+        Ok(assumption)
+    }
+
+    pub fn assume(assumption: FormulaBuilder, formula: error::Result<FormulaBuilder>, globals: &mut Bindings, location: FileLocation) -> error::Result<FormulaBuilder> {
+        globals.pop_scope();
+        let formula = formula?;
+        Ok(FormulaBuilder {
+            arg_types: formula.arg_types,
+            value: Formula::Implication(Box::new(assumption.value), Box::new(formula.value)),
+            location: location,
+        })
+    }
+
+    pub fn have(hyp: usize, formula: error::Result<FormulaBuilder>, proof: error::Result<FormulaBuilder>, globals: &mut Bindings, location: FileLocation) -> error::Result<()> {
+        let formula = formula?;
+        let claim = formula.value.clone();
+        let checked = match proof.and_then(|proof| formula.prove(proof, globals)) {
+            Ok(proven) => globals.check_inferred(proven.value.free_vars(), hyp, &location),
+            Err(error) => Err(error),
+        };
+        let claim = FormulaSchema::Formula(globals.resolve_formula(claim));
+        globals.insert_theorem(hyp, claim, &location)?;
+        checked
+    }
+
+    pub fn and_intro(lhs: FormulaBuilder, rhs: FormulaBuilder, location: FileLocation) -> error::Result<FormulaBuilder> {
+        if rhs.arg_types.len() != 0 {
+            return Err(Error::new(UnboundConnective, &rhs.location));
+        }
+        Ok(FormulaBuilder {
+            arg_types: lhs.arg_types,
+            value: Formula::Conjunction(Box::new(lhs.value), Box::new(rhs.value)),
+            location: location,
+        })
+    }
+
+    pub fn and_elim_left(self, location: FileLocation) -> error::Result<FormulaBuilder> {
+        if let Formula::Conjunction(box lhs, _) = self.value {
+            Ok(FormulaBuilder {
+                arg_types: self.arg_types,
+                value: lhs,
+                location: location,
+            })
+        } else {
+            Err(Error::new(NotConjunctionElim, &location))
+        }
+    }
+
+    pub fn and_elim_right(self, location: FileLocation) -> error::Result<FormulaBuilder> {
+        if let Formula::Conjunction(_, box rhs) = self.value {
+            Ok(FormulaBuilder {
+                arg_types: self.arg_types,
+                value: rhs,
+                location: location,
+            })
+        } else {
+            Err(Error::new(NotConjunctionElim, &location))
+        }
+    }
+
+    pub fn or_intro_left(self, other: FormulaBuilder, location: FileLocation) -> error::Result<FormulaBuilder> {
+        if other.arg_types.len() != 0 {
+            return Err(Error::new(UnboundConnective, &other.location));
+        }
+        Ok(FormulaBuilder {
+            arg_types: self.arg_types,
+            value: Formula::Disjunction(Box::new(self.value), Box::new(other.value)),
+            location: location,
+        })
+    }
+
+    pub fn or_intro_right(self, other: FormulaBuilder, location: FileLocation) -> error::Result<FormulaBuilder> {
+        if other.arg_types.len() != 0 {
+            return Err(Error::new(UnboundConnective, &other.location));
+        }
+        Ok(FormulaBuilder {
+            arg_types: self.arg_types,
+            value: Formula::Disjunction(Box::new(other.value), Box::new(self.value)),
+            location: location,
+        })
+    }
+
+    pub fn or_elim(self, left_case: FormulaBuilder, right_case: FormulaBuilder, globals: &Bindings, location: FileLocation) -> error::Result<FormulaBuilder> {
+        if let Formula::Disjunction(box lhs, box rhs) = self.value {
+            if let (Formula::Implication(box lhs_, box result), Formula::Implication(box rhs_, box result_)) = (left_case.value, right_case.value) {
+                if globals.equivalent(&lhs, &lhs_) && globals.equivalent(&rhs, &rhs_) && globals.equivalent(&result, &result_) {
+                    Ok(FormulaBuilder {
+                        arg_types: self.arg_types,
+                        value: globals.resolve_formula(result),
+                        location: location,
+                    })
+                } else {
+                    Err(Error::new(PremiseMismatch, &location))
+                }
+            } else {
+                Err(Error::new(NotImplicationRule, &location))
+            }
+        } else {
+            Err(Error::new(NotDisjunctionElim, &location))
+        }
+    }
+
+    pub fn not_intro(self, location: FileLocation) -> error::Result<FormulaBuilder> {
+        if let Formula::Implication(box lhs, box Formula::False) = self.value {
+            Ok(FormulaBuilder {
+                arg_types: self.arg_types,
+                value: Formula::Negation(Box::new(lhs)),
+                location: location,
+            })
+        } else {
+            Err(Error::new(NotImplicationRule, &location))
+        }
+    }
+
+    pub fn not_elim(self, proof: FormulaBuilder, globals: &Bindings, location: FileLocation) -> error::Result<FormulaBuilder> {
+        if let Formula::Negation(box form) = self.value {
+            if globals.equivalent(&form, &proof.value) {
+                Ok(FormulaBuilder {
+                    arg_types: self.arg_types,
+                    value: Formula::False,
+                    location: location,
+                })
+            } else {
+                Err(Error::new(PremiseMismatch, &location))
+            }
+        } else {
+            Err(Error::new(NotNegationElim, &location))
+        }
+    }
+
+    pub fn iff_intro(self, converse: FormulaBuilder, globals: &Bindings, location: FileLocation) -> error::Result<FormulaBuilder> {
+        if let (Formula::Implication(box lhs, box rhs), Formula::Implication(box rhs_, box lhs_)) = (self.value, converse.value) {
+            if globals.equivalent(&lhs, &lhs_) && globals.equivalent(&rhs, &rhs_) {
+                Ok(FormulaBuilder {
+                    arg_types: self.arg_types,
+                    value: globals.resolve_formula(Formula::Biconditional(Box::new(lhs), Box::new(rhs))),
+                    location: location,
+                })
+            } else {
+                Err(Error::new(PremiseMismatch, &location))
+            }
+        } else {
+            Err(Error::new(NotImplicationRule, &location))
+        }
+    }
+
+    pub fn iff_elim_left(self, location: FileLocation) -> error::Result<FormulaBuilder> {
+        if let Formula::Biconditional(box lhs, box rhs) = self.value {
+            Ok(FormulaBuilder {
+                arg_types: self.arg_types,
+                value: Formula::Implication(Box::new(lhs), Box::new(rhs)),
+                location: location,
+            })
+        } else {
+            Err(Error::new(NotBiconditionalElim, &location))
+        }
+    }
+
+    pub fn iff_elim_right(self, location: FileLocation) -> error::Result<FormulaBuilder> {
+        if let Formula::Biconditional(box lhs, box rhs) = self.value {
+            Ok(FormulaBuilder {
+                arg_types: self.arg_types,
+                value: Formula::Implication(Box::new(rhs), Box::new(lhs)),
+                location: location,
+            })
+        } else {
+            Err(Error::new(NotBiconditionalElim, &location))
+        }
+    }
+
+    pub fn false_elim(self, target: FormulaBuilder, location: FileLocation) -> error::Result<FormulaBuilder> {
+        if target.arg_types.len() != 0 {
+            return Err(Error::new(UnboundConnective, &target.location));
+        }
+        if let Formula::False = self.value {
+            Ok(FormulaBuilder {
+                arg_types: target.arg_types,
+                value: target.value,
+                location: location,
+            })
+        } else {
+            Err(Error::new(PremiseMismatch, &location))
+        }
+    }
+
+    pub fn refl(term: TermBuilder, globals: &Bindings, location: FileLocation) -> error::Result<FormulaBuilder> {
+        Ok(FormulaBuilder {
+            arg_types: Vec::new(),
+            value: Formula::Equality(globals.resolve_type(&term.itype), term.value.clone(), term.value),
+            location: location,
+        })
+    }
+
+    pub fn symm(self, location: FileLocation) -> error::Result<FormulaBuilder> {
+        if let Formula::Equality(itype, lhs, rhs) = self.value {
+            Ok(FormulaBuilder {
+                arg_types: self.arg_types,
+                value: Formula::Equality(itype, rhs, lhs),
+                location: location,
+            })
+        } else {
+            Err(Error::new(NotEqualityRule, &location))
+        }
+    }
+
+    pub fn trans(self, other: FormulaBuilder, globals: &Bindings, location: FileLocation) -> error::Result<FormulaBuilder> {
+        if let (Formula::Equality(itype, lhs, mid), Formula::Equality(itype_, mid_, rhs)) = (self.value, other.value) {
+            if globals.tentatively(|| globals.unify(&itype, &itype_) && globals.equivalent_term(&mid, &mid_)) {
+                Ok(FormulaBuilder {
+                    arg_types: self.arg_types,
+                    value: globals.resolve_formula(Formula::Equality(itype, lhs, rhs)),
+                    location: location,
+                })
+            } else {
+                Err(Error::new(PremiseMismatch, &location))
+            }
+        } else {
+            Err(Error::new(NotEqualityRule, &location))
+        }
+    }
+
+    pub fn congr(self, function: TermBuilder, globals: &Bindings, location: FileLocation) -> error::Result<FormulaBuilder> {
+        if let Formula::Equality(itype, lhs, rhs) = self.value {
+            if let InternalType::Func(box arg_type, box ret_type) = globals.resolve_type(&function.itype) {
+                if globals.tentatively(|| globals.unify(&arg_type, &itype)) {
+                    Ok(FormulaBuilder {
+                        arg_types: self.arg_types,
+                        value: Formula::Equality(globals.resolve_type(&ret_type),
+                            Term::Application(Box::new(function.value.clone()), Box::new(lhs)),
+                            Term::Application(Box::new(function.value), Box::new(rhs))),
+                        location: location,
+                    })
+                } else {
+                    let error = Error::new(ITypeMismatch {
+                        found: globals.resolve_type(&itype).repr(globals),
+                        expected: globals.resolve_type(&arg_type).repr(globals),
+                    }, &location);
+                    Err(globals.explain_types(error, &[&itype, &arg_type]))
+                }
+            } else {
+                Err(Error::new(ITypeMismatch {
+                    found: globals.resolve_type(&function.itype).repr(globals),
+                    expected: format!("{} -> _", globals.resolve_type(&itype).repr(globals)),
+                }, &function.location))
+            }
+        } else {
+            Err(Error::new(NotEqualityRule, &location))
+        }
+    }
+
+    pub fn leibniz(predicate: FormulaBuilder, equality: FormulaBuilder, proof: FormulaBuilder, globals: &mut Bindings, location: FileLocation) -> error::Result<FormulaBuilder> {
+        if predicate.arg_types.len() != 1 {
+            return Err(Error::new(MTypeMismatch {
+                found: MetaType::Formula(predicate.arg_types).repr(globals),
+                expected: String::from("Formula _"),
+            }, &predicate.location));
+        }
+        if let Formula::Equality(itype, lhs, rhs) = equality.value {
+            if !globals.unify(&predicate.arg_types[0], &itype) {
+                let error = Error::new(ITypeMismatch {
+                    found: globals.resolve_type(&itype).repr(globals),
+                    expected: globals.resolve_type(&predicate.arg_types[0]).repr(globals),
+                }, &equality.location);
+                return Err(globals.explain_types(error, &[&itype, &predicate.arg_types[0]]));
+            }
+            let instance = predicate.value.clone().apply(&lhs, globals);
+            if globals.equivalent(&instance, &proof.value) {
+                let value = predicate.value.apply(&rhs, globals);
+                Ok(FormulaBuilder {
+                    arg_types: Vec::new(),
+                    value: globals.resolve_formula(value),
+                    location: location,
+                })
+            } else {
+                Err(Error::new(ProofMismatch, &proof.location)
+                    .with_note(format!("the proof establishes `{}`", globals.resolve_formula(proof.value).repr(globals)))
+                    .with_note(format!("substitution requires `{}`", globals.resolve_formula(instance).repr(globals))))
+            }
+        } else {
+            Err(Error::new(NotEqualityRule, &location))
+        }
+    }
+
+    pub fn rewrite(self, equality: FormulaBuilder, globals: &mut Bindings, location: FileLocation) -> error::Result<FormulaBuilder> {
+        if let Formula::Equality(_, lhs, rhs) = equality.value {
+            Ok(FormulaBuilder {
+                arg_types: self.arg_types,
+                value: globals.resolve_formula(self.value).rewrite(&globals.resolve_term(&lhs), &globals.resolve_term(&rhs), globals),
+                location: location,
+            })
+        } else {
+            Err(Error::new(NotEqualityRule, &equality.location))
+        }
+    }
+
+    pub fn exists_intro(self, witness: TermBuilder, proof: FormulaBuilder, globals: &mut Bindings, location: FileLocation) -> error::Result<FormulaBuilder> {
+        if let Formula::ExistentialQ(var, ref itype, box ref form) = self.value {
+            if !globals.unify(&witness.itype, itype) {
+                let error = Error::new(ITypeMismatch {
+                    found: globals.resolve_type(&witness.itype).repr(globals),
+                    expected: globals.resolve_type(itype).repr(globals),
+                }, &witness.location);
+                return Err(globals.explain_types(error, &[&witness.itype, itype]));
+            }
+            let instance = form.clone().substitute(var, &witness.value, globals);
+            if !globals.equivalent(&instance, &proof.value) {
+                return Err(Error::new(ProofMismatch, &proof.location)
+                    .with_label(&witness.location, format!("the witness requires `{}`", globals.resolve_formula(instance).repr(globals)))
+                    .with_note(format!("the proof establishes `{}`", globals.resolve_formula(proof.value).repr(globals))));
+            }
+        } else {
+            return Err(Error::new(NotExistentialQIntro, &location));
+        }
+        Ok(FormulaBuilder {
+            arg_types: self.arg_types,
+            value: self.value,
+            location: location,
+        })
+    }
+
+    pub fn exists_elim_prep(id: usize, hyp: usize, existential: FormulaBuilder, locals: &mut LocalBindings, globals: &mut Bindings, location: FileLocation) -> error::Result<()> {
+        if let Formula::ExistentialQ(var, itype, box form) = existential.value {
+            FormulaBuilder::quantifier_prep(id, itype, locals, globals, location.clone())?;
+            let witness = Term::Symbol(Local(locals.get_local(&id).unwrap()), Vec::new());
+            let hypothesis = form.substitute(var, &witness, globals);
+            globals.push_scope();
+            globals.insert_theorem(hyp, FormulaSchema::Formula(hypothesis), &location)?;
+            Ok(())
+        } else {
+            Err(Error::new(NotExistentialQElim, &existential.location))
+        }
+    }
+
+    pub fn exists_elim(id: usize, formula: error::Result<FormulaBuilder>, locals: &mut LocalBindings, globals: &mut Bindings, location: FileLocation) -> error::Result<FormulaBuilder> {
+        let local_id = locals.get_local(&id).unwrap();
+        locals.remove(&local_id);
+        globals.pop_scope();
+        let formula = formula?;
+        if formula.value.free_vars().contains(&local_id) {
+            Err(Error::new(WitnessEscapes, &location))
+        } else {
+            Ok(FormulaBuilder {
+                arg_types: formula.arg_types,
+                value: formula.value,
+                location: location,
+            })
+        }
+    }
+
+    pub fn instantiate(self, term: TermBuilder, globals: &mut Bindings) -> error::Result<FormulaBuilder> {
+        if let Formula::UniversalQ(var, itype, box form) = self.value {
+            if globals.unify(&term.itype, &itype) {
+                let value = form.substitute(var, &term.value, globals);
+                Ok(FormulaBuilder {
+                    arg_types: self.arg_types,
+                    value: globals.resolve_formula(value),
+                    location: term.location,
+                })
+            } else {
+                let error = Error::new(ITypeMismatch {
+                    found: globals.resolve_type(&term.itype).repr(globals),
+                    expected: globals.resolve_type(&itype).repr(globals),
+                }, &term.location);
+                Err(globals.explain_types(error, &[&term.itype, &itype]))
+            }
+        } else {
+            Err(Error::new(NotUniversalQInst, &term.location))
+        }
+    }
+}
+
+
+pub enum ExprBuilder {
+    Formula(FormulaBuilder),
+    Term(TermBuilder),
+}
+
+impl ExprBuilder {
+    pub fn symbol(id: usize, locals: &LocalBindings, globals: &mut Bindings, location: FileLocation) -> error::Result<ExprBuilder> {
+        let is_term = if let Some(local_id) = locals.get_local(&id) {
+            if let &MetaType::Term(_) = locals.get_type(&local_id) { true } else { false }
+        } else {
+            if let Some(MetaType::Term(_)) = globals.get_type(&id).map(MetaType::unquantified) { true } else { false }
+        };
+        if is_term {
+            Ok(ExprBuilder::Term(TermBuilder::symbol(id, locals, globals, location)?))
+        } else {
+            Ok(ExprBuilder::Formula(FormulaBuilder::relation(id, locals, globals, location)?))
+        }
+    }
+
+    pub fn application(head: ExprBuilder, argument: ExprBuilder, globals: &mut Bindings) -> error::Result<ExprBuilder> {
+        let argument = argument.term(globals)?;
+        match head {
+            ExprBuilder::Formula(pred) => Ok(ExprBuilder::Formula(FormulaBuilder::application(pred, argument, globals)?)),
+            ExprBuilder::Term(func)    => Ok(ExprBuilder::Term(TermBuilder::application(func, argument, globals)?)),
+        }
+    }
+
+    pub fn mtype(&self, globals: &Bindings) -> MetaType {
+        match self {
+            &ExprBuilder::Formula(ref form) => MetaType::Formula(form.arg_types.iter().map(|itype| globals.resolve_type(itype)).collect()),
+            &ExprBuilder::Term(ref term)    => MetaType::Term(globals.resolve_type(&term.itype)),
+        }
+    }
+
+    pub fn formula(self, globals: &Bindings) -> error::Result<FormulaBuilder> {
+        match self {
+            ExprBuilder::Formula(form) => Ok(form),
+            ExprBuilder::Term(term)    => {
+                Err(Error::new(MTypeMismatch {
+                    found: MetaType::Term(globals.resolve_type(&term.itype)).repr(globals),
+                    expected: String::from("Formula _*"),
+                }, &term.location))
+            },
+        }
+    }
+
+    pub fn term(self, globals: &Bindings) -> error::Result<TermBuilder> {
+        match self {
+            ExprBuilder::Formula(form) => {
+                Err(Error::new(MTypeMismatch {
+                    found: MetaType::Formula(form.arg_types.iter().map(|itype| globals.resolve_type(itype)).collect()).repr(globals),
+                    expected: String::from("Term _"),
+                }, &form.location))
+            },
+            ExprBuilder::Term(term)    => Ok(term),
+        }
+    }
+}
+
+
+pub struct MetaValueBuilder {
+    mtype: MetaType,
+    value: MetaValue,
+    location: FileLocation,
+}
+
+impl MetaValueBuilder {
+    pub fn type_(itype: InternalType, location: FileLocation) -> error::Result<MetaValueBuilder> {
+        Ok(MetaValueBuilder {
+            mtype: MetaType::Type,
+            value: MetaValue::Type(itype),
+            location: location,
+        })
+    }
+
+    pub fn term(term: TermBuilder) -> error::Result<MetaValueBuilder> {
+        Ok(MetaValueBuilder {
+            mtype: MetaType::Term(term.itype),
+            value: MetaValue::Term(term.value),
+            location: term.location,
+        })
+    }
+
+    pub fn formula(formula: FormulaBuilder) -> error::Result<MetaValueBuilder> {
+        Ok(MetaValueBuilder {
+            mtype: MetaType::Formula(formula.arg_types),
+            value: MetaValue::Formula(formula.value),
+            location: formula.location,
+        })
+    }
+}
+
+
+pub struct FSchemaBuilder {
+    // marg_types: Vec<MetaType>,
+    iarg_types: Vec<InternalType>,
+    value: FormulaSchema,
+    location: FileLocation,
+}
+
+impl FSchemaBuilder {
+    pub fn formula(formula: FormulaBuilder) -> error::Result<FSchemaBuilder> {
+        Ok(FSchemaBuilder {
+            // marg_types: Vec::new(),
+            iarg_types: formula.arg_types,
+            value: FormulaSchema::Formula(formula.value),
+            location: formula.location,
+        })
+    }
+
+    pub fn schema_prep(id: usize, mtype: MetaType, locals: &mut LocalBindings, globals: &mut Bindings, location: FileLocation) -> error::Result<()> {
+        if let None = locals.get_local(&id) {
+            locals.insert(id, globals.named_local(id), mtype);
+            Ok(())
+        } else {
+            Err(Error::new(BindingExists {
+                name: globals.get_name(&id).unwrap().clone(),
+            }, &location))
+        }
+    }
+
+    pub fn schema(id: usize, mtype: MetaType, schema: error::Result<FSchemaBuilder>, locals: &mut LocalBindings, location: FileLocation) -> error::Result<FSchemaBuilder> {
+        let local_id = locals.get_local(&id).unwrap();
+        locals.remove(&local_id);
+        let schema = schema?;
+        // schema.marg_types.push(mtype.clone());
+        Ok(FSchemaBuilder {
+            // marg_types: schema.marg_types,
+            iarg_types: schema.iarg_types,
+            value: FormulaSchema::Schema(local_id, mtype, Box::new(schema.value)),
+            location: location,
+        })
+    }
+
+    pub fn poly(id: usize, schema: error::Result<FSchemaBuilder>, locals: &mut LocalBindings, location: FileLocation) -> error::Result<FSchemaBuilder> {
+        let local_id = locals.get_local(&id).unwrap();
+        locals.remove(&local_id);
+        let schema = schema?;
+        Ok(FSchemaBuilder {
+            iarg_types: schema.iarg_types,
+            value: FormulaSchema::Poly(local_id, Box::new(schema.value)),
+            location: location,
+        })
+    }
+
+    fn instantiate_types(self, globals: &mut Bindings) -> FSchemaBuilder {
+        let mut value = self.value;
+        while let FormulaSchema::Poly(..) = value {
+            let fresh = globals.fresh_type();
+            value = value.specify(&MetaValue::Type(fresh), globals).unwrap();
+        }
+        FSchemaBuilder {
+            iarg_types: self.iarg_types,
+            value: value,
+            location: self.location,
+        }
+    }
+
+    pub fn saved_theorem(id: usize, globals: &Bindings, location: FileLocation) -> error::Result<FSchemaBuilder> {
+        if let Some(theorem) = globals.get_theorem(&id) {
+            Ok(FSchemaBuilder {
+                iarg_types: Vec::new(),
+                value: theorem.clone(),
+                location: location,
+            })
+        } else {
+            Err(globals.no_binding(&id, |_| false, &location))
+        }
+    }
+
+    pub fn is_wff_schema(&self) -> bool {
+        self.iarg_types.len() == 0
+    }
+
+    pub fn save(self, id: usize, globals: &mut Bindings, location: &FileLocation) -> error::Result<usize> {
+        globals.check_inferred(self.value.free_vars(), id, location)?;
+        if self.is_wff_schema() {
+            globals.insert_theorem(id, self.value, location)
+        } else {
+            Err(Error::new(UnboundTheorem, location))
+        }
+    }
+
+    pub fn value(self) -> FormulaSchema {
+        self.value
+    }
+
+    pub fn get_formula(self, globals: &mut Bindings) -> error::Result<FormulaBuilder> {
+        let schema = self.instantiate_types(globals);
+        if let FormulaSchema::Formula(form) = schema.value {
+            Ok(FormulaBuilder {
+                arg_types: schema.iarg_types,
+                value: form,
+                location: schema.location,
+            })
+        } else {
+            Err(Error::new(UnspecifiedSchema, &schema.location))
+        }
+    }
+
+    pub fn specify(self, arg: MetaValueBuilder, globals: &mut Bindings) -> error::Result<FSchemaBuilder> {
+        let explicit = if let (&FormulaSchema::Poly(..), &MetaType::Type) = (&self.value, &arg.mtype) { true } else { false };
+        let schema = if explicit { self } else { self.instantiate_types(globals) };
+        match schema.value {
+            FormulaSchema::Schema(_, ref mtype, _) => {
+                if !globals.unify_meta(mtype, &arg.mtype) {
+                    return Err(Error::new(MTypeMismatch {
+                        found: arg.mtype.repr(globals),
+                        expected: mtype.repr(globals),
+                    }, &arg.location));
+                }
+            },
+            FormulaSchema::Poly(..)                => (),
+            FormulaSchema::Formula(_)              => return Err(Error::new(NotSchemaSpec, &arg.location)),
+        }
+        Ok(FSchemaBuilder {
+            iarg_types: schema.iarg_types,
+            value: schema.value.specify(&arg.value, globals).unwrap(),
+            location: schema.location,
+        })
+    }
+}
+
+
+fn axiom_name(parts: &[&String]) -> String {
+    parts.iter().map(|name| {
+        let name = name.rsplit('.').next().unwrap();
+        let mut chars = name.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None        => String::new(),
+        }
+    }).collect()
+}
+
+
+pub struct DataBuilder {
+    id: usize,
+    constructors: Vec<(usize, Vec<InternalType>, FileLocation)>,
+    location: FileLocation,
+}
+
+impl DataBuilder {
+    pub fn prep(id: usize, globals: &mut Bindings, location: FileLocation) -> error::Result<()> {
+        globals.insert_object_noval(id, MetaType::Type, &location)?;
+        Ok(())
+    }
+
+    pub fn new(id: usize, constructors: Vec<(usize, error::Result<Vec<InternalType>>, FileLocation)>, globals: &Bindings, location: FileLocation) -> error::Result<DataBuilder> {
+        let itself = InternalType::Named(Global(id));
+        let mut checked = Vec::new();
+        for (ctor, arg_types, ctor_location) in constructors.into_iter() {
+            let arg_types = arg_types?;
+            if arg_types.iter().any(|arg_type| *arg_type != itself && DataBuilder::mentions(arg_type, id)) {
+                return Err(Error::new(NestedRecursion { name: globals.get_name(&id).unwrap().clone() }, &ctor_location));
+            }
+            checked.push((ctor, arg_types, ctor_location));
+        }
+        if checked.iter().all(|&(_, ref arg_types, _)| arg_types.contains(&itself)) {
+            return Err(Error::new(EmptyDataType { name: globals.get_name(&id).unwrap().clone() }, &location)
+                .with_help(String::from("add a constructor whose arguments do not mention the type being declared")));
+        }
+        Ok(DataBuilder {
+            id: id,
+            constructors: checked,
+            location: location,
+        })
+    }
+
+    fn mentions(itype: &InternalType, id: usize) -> bool {
+        match itype {
+            &InternalType::Named(name)                              => name == Global(id),
+            &InternalType::Func(box ref arg_type, box ref ret_type) => DataBuilder::mentions(arg_type, id) || DataBuilder::mentions(ret_type, id),
+        }
+    }
+
+    fn itself(&self) -> InternalType {
+        InternalType::Named(Global(self.id))
+    }
+
+    fn fresh(arg_types: &[InternalType], globals: &mut Bindings) -> Vec<usize> {
+        arg_types.iter().map(|_| globals.new_local()).collect()
+    }
+
+    fn apply(ctor: usize, vars: &[usize]) -> Term {
+        vars.iter().fold(Term::Symbol(Global(ctor), Vec::new()), |func, &var| {
+            Term::Application(Box::new(func), Box::new(Term::Symbol(Local(var), Vec::new())))
+        })
+    }
+
+    fn generalize(vars: &[usize], arg_types: &[InternalType], formula: Formula) -> Formula {
+        vars.iter().zip(arg_types.iter()).rev().fold(formula, |form, (&var, itype)| {
+            Formula::UniversalQ(var, itype.clone(), Box::new(form))
+        })
+    }
+
+    fn injectivity(&self, ctor: usize, arg_types: &[InternalType], globals: &mut Bindings) -> Formula {
+        let lhs = DataBuilder::fresh(arg_types, globals);
+        let rhs = DataBuilder::fresh(arg_types, globals);
+        let equal = Formula::Equality(self.itself(), DataBuilder::apply(ctor, &lhs), DataBuilder::apply(ctor, &rhs));
+        let parts = lhs.iter().zip(rhs.iter()).zip(arg_types.iter()).map(|((&x, &y), itype)| {
+            Formula::Equality(itype.clone(), Term::Symbol(Local(x), Vec::new()), Term::Symbol(Local(y), Vec::new()))
+        });
+        let conclusion = parts.fold(None, |acc, part| match acc {
+            Some(acc) => Some(Formula::Conjunction(Box::new(acc), Box::new(part))),
+            None      => Some(part),
+        }).unwrap();
+        let formula = Formula::Implication(Box::new(equal), Box::new(conclusion));
+        DataBuilder::generalize(&lhs, arg_types, DataBuilder::generalize(&rhs, arg_types, formula))
+    }
+
+    fn disjointness(&self, lhs: &(usize, Vec<InternalType>, FileLocation), rhs: &(usize, Vec<InternalType>, FileLocation), globals: &mut Bindings) -> Formula {
+        let lhs_vars = DataBuilder::fresh(&lhs.1, globals);
+        let rhs_vars = DataBuilder::fresh(&rhs.1, globals);
+        let equal = Formula::Equality(self.itself(), DataBuilder::apply(lhs.0, &lhs_vars), DataBuilder::apply(rhs.0, &rhs_vars));
+        DataBuilder::generalize(&lhs_vars, &lhs.1, DataBuilder::generalize(&rhs_vars, &rhs.1, Formula::Implication(Box::new(equal), Box::new(Formula::False))))
+    }
+
+    fn induction(&self, globals: &mut Bindings) -> FormulaSchema {
+        let pred = globals.new_local();
+        let holds = |term: Term| Formula::Application(Box::new(Formula::Relation(Local(pred), Vec::new())), term);
+        let var = globals.new_local();
+        let conclusion = Formula::UniversalQ(var, self.itself(), Box::new(holds(Term::Symbol(Local(var), Vec::new()))));
+        let formula = self.constructors.iter().rev().fold(conclusion, |form, &(ctor, ref arg_types, _)| {
+            let vars = DataBuilder::fresh(arg_types, globals);
+            let step = vars.iter().zip(arg_types.iter()).rev()
+                .filter(|&(_, itype)| *itype == self.itself())
+                .fold(holds(DataBuilder::apply(ctor, &vars)), |acc, (&var, _)| {
+                    Formula::Implication(Box::new(holds(Term::Symbol(Local(var), Vec::new()))), Box::new(acc))
+                });
+            Formula::Implication(Box::new(DataBuilder::generalize(&vars, arg_types, step)), Box::new(form))
+        });
+        FormulaSchema::Schema(pred, MetaType::Formula(vec![self.itself()]), Box::new(FormulaSchema::Formula(formula)))
+    }
+
+    pub fn save(self, globals: &mut Bindings) -> error::Result<usize> {
+        for &(ctor, ref arg_types, ref ctor_location) in self.constructors.iter() {
+            let itype = arg_types.iter().rev().fold(self.itself(), |ret_type, arg_type| {
+                InternalType::Func(Box::new(arg_type.clone()), Box::new(ret_type))
+            });
+            globals.insert_object_noval(ctor, MetaType::Term(itype), ctor_location)?;
+        }
+        let type_name = globals.get_name(&self.id).unwrap().clone();
+        let mut axioms = Vec::new();
+        for (index, &(ctor, ref arg_types, _)) in self.constructors.iter().enumerate() {
+            let ctor_name = globals.get_name(&ctor).unwrap().clone();
+            if !arg_types.is_empty() {
+                let formula = self.injectivity(ctor, arg_types, globals);
+                axioms.push((axiom_name(&[&ctor_name, &String::from("inj")]), FormulaSchema::Formula(formula)));
+            }
+            for other in self.constructors[index + 1..].iter() {
+                let other_name = globals.get_name(&other.0).unwrap().clone();
+                let formula = self.disjointness(&self.constructors[index], other, globals);
+                axioms.push((axiom_name(&[&ctor_name, &String::from("not"), &other_name]), FormulaSchema::Formula(formula)));
+            }
+        }
+        let induction = self.induction(globals);
+        axioms.push((axiom_name(&[&type_name, &String::from("induction")]), induction));
+        let mut saved = Vec::new();
+        for (name, schema) in axioms.into_iter() {
+            let axiom = globals.declare(&name);
+            saved.push(globals.insert_theorem(axiom, schema, &self.location)?);
+        }
+        globals.insert_data(self.id, DataType {
+            constructors: self.constructors.iter().map(|&(ctor, _, _)| ctor).collect(),
+            axioms: saved,
+        });
+        Ok(self.id)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use elaborate::Elaborator;
+    use error::{ErrorCollector, SourceInfo};
+    use syntax;
+
+    const THEORY: &'static str = "
+let Nat :: Type
+let zero :: Term Nat
+let P :: Formula
+let Q :: Formula
+";
+
+    fn check(text: &str) -> Vec<(&'static str, (usize, usize))> {
+        let text = format!("{}{}", THEORY, text);
+        let source = SourceInfo::from_text("<test>", &text);
+        let errors = ErrorCollector::new();
+        let mut locals = LocalBindings::new();
+        let mut globals = Bindings::new();
+        let stmts = syntax::parse_program(&source, &text);
+        Elaborator::new(&mut locals, &mut globals, &source, &errors).program(stmts);
+        errors.take().iter().map(|error| (error.err_type(), error.location().position())).collect()
+    }
+
+    fn codes(text: &str) -> Vec<&'static str> {
+        check(text).into_iter().map(|(code, _)| code).collect()
+    }
+
+    #[test]
+    fn conjunction_rules() {
+        assert_eq!(codes("theorem T : P and Q -> Q and P := assume h : P and Q { and_intro (and_elim_right h) (and_elim_left h) }"), Vec::<&str>::new());
+        assert_eq!(codes("theorem T : P and Q -> P := assume h : P and Q { and_elim_right h }"), vec!["ProofMismatch"]);
+        assert_eq!(codes("theorem T : P -> P := assume h : P { and_elim_left h }"), vec!["NotConjunctionElim"]);
+    }
+
+    #[test]
+    fn disjunction_rules() {
+        assert_eq!(codes("theorem T : P or Q -> Q or P := assume h : P or Q {
+            or_elim h (assume p : P { or_intro_right [Q] p }) (assume q : Q { or_intro_left [P] q }) }"), Vec::<&str>::new());
+        assert_eq!(codes("theorem T : P or Q -> P := assume h : P or Q { or_elim h (assume p : P { p }) (assume q : Q { q }) }"),
+            vec!["PremiseMismatch"]);
+    }
+
+    #[test]
+    fn negation_rules() {
+        assert_eq!(codes("theorem T : P -> not not P := assume p : P { not_intro (assume np : not P { not_elim np p }) }"), Vec::<&str>::new());
+        assert_eq!(codes("theorem T : not P -> Q -> false := assume np : not P { assume q : Q { not_elim np q } }"), vec!["PremiseMismatch"]);
+        assert_eq!(codes("theorem T : not P -> P -> false := assume np : not P { np }
+theorem U : (P -> false) -> not P := assume np : P -> false { np }"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn contrapositive_negates_both_sides() {
+        assert_eq!(codes("theorem T : (P -> Q) -> not Q -> not P := assume h : P -> Q { contrapositive. h }"), Vec::<&str>::new());
+        assert_eq!(codes("theorem T : (P -> Q) -> not Q -> P -> false := assume h : P -> Q {
+            assume nq : not Q { assume p : P { not_elim ((contrapositive. h) $ nq) p } } }"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn biconditional_rules() {
+        assert_eq!(codes("theorem T : (P <-> Q) -> (Q <-> P) := assume h : P <-> Q { iff_intro (iff_elim_right h) (iff_elim_left h) }"),
+            Vec::<&str>::new());
+        assert_eq!(codes("theorem T : (P -> Q) -> (P -> Q) -> (P <-> Q) := assume a : P -> Q { assume b : P -> Q { iff_intro a b } }"),
+            vec!["PremiseMismatch"]);
+    }
+
+    #[test]
+    fn assume_discharges_hypothesis() {
+        assert_eq!(codes("theorem T : P -> Q -> P := assume p : P { assume q : Q { p } }"), Vec::<&str>::new());
+        assert_eq!(codes("theorem T : P -> Q -> Q := assume p : P { assume q : Q { p } }"), vec!["ProofMismatch"]);
+        assert_eq!(codes("theorem T : (P -> P) and P := and_intro (assume p : P { p }) p"), vec!["NoBinding"]);
+        assert_eq!(codes("theorem T : P -> P := assume p : P { p }\ntheorem U : P := p"), vec!["NoBinding"]);
+    }
+
+    #[test]
+    fn premises_compared_after_inference() {
+        assert_eq!(codes("theorem NE : forall x. forall y. not (x = y) -> y = zero -> x = y -> false :=
+            given x. given y. assume a : not (x = y) { assume b : y = zero { assume c : x = y { not_elim a c } } }"), Vec::<&str>::new());
+        assert_eq!(codes("theorem II : forall x. forall y. (x = y -> y = x) -> y = zero -> (y = x -> x = y) -> (x = y <-> y = x) :=
+            given x. given y. assume a : x = y -> y = x { assume b : y = zero { assume c : y = x -> x = y { iff_intro a c } } }"), Vec::<&str>::new());
+        assert_eq!(codes("theorem OE : forall x. forall y. x = y or y = x -> y = zero -> x = y -> x = y :=
+            given x. given y. assume a : x = y or y = x { assume z : y = zero { assume c : x = y {
+                or_elim a (assume b : x = y { c }) (assume d : y = x { c }) } } }"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn transitivity_checks_middle_term() {
+        assert_eq!(codes("theorem T : forall x: Nat. forall y: Nat. x = y -> y = zero -> x = zero :=
+            given x. given y. assume a : x = y { assume b : y = zero { trans a b } }"), Vec::<&str>::new());
+        assert_eq!(codes("theorem T : forall x: Nat. forall y: Nat. x = y -> x = zero -> x = zero :=
+            given x. given y. assume a : x = y { assume b : x = zero { trans a b } }"), vec!["PremiseMismatch"]);
+    }
+
+    const SIGNATURE: &'static str = "let Bool :: Type
+let succ :: Term Nat -> Nat
+let neg :: Term Bool -> Bool
+let R :: Formula Nat
+let L :: Formula Nat Nat
+";
+
+    #[test]
+    fn schema_specification_checks_arguments() {
+        let schemas = "let yes :: Term Bool
+axiom Id = schema X :: Formula. X -> X
+axiom Z = schema t :: Term Nat. t = t
+";
+        assert_eq!(codes(&format!("{}{}theorem A : P -> P := Id [Formula P]\ntheorem B : zero = zero := Z [Term zero]", SIGNATURE, schemas)),
+            Vec::<&str>::new());
+        assert_eq!(check(&format!("{}{}theorem C : P -> P := Id [Term zero]", SIGNATURE, schemas)), vec![("MTypeMismatch", (13, 31))]);
+        assert_eq!(codes(&format!("{}{}theorem E : yes = yes := Z [Term yes]", SIGNATURE, schemas)), vec!["MTypeMismatch"]);
+        assert_eq!(check(&format!("{}{}theorem D : P -> P := Id [Formula P] [Formula Q]", SIGNATURE, schemas)), vec![("NotSchemaSpec", (13, 46))]);
+    }
+
+    #[test]
+    fn reflexivity_and_symmetry() {
+        assert_eq!(codes(&format!("{}theorem T : succ zero = succ zero := refl (succ zero)", SIGNATURE)), Vec::<&str>::new());
+        assert_eq!(codes(&format!("{}theorem T : zero = succ zero := refl zero", SIGNATURE)), vec!["ProofMismatch"]);
+        assert_eq!(codes(&format!("{}theorem T : forall x: Nat. x = zero -> zero = x := given x. assume h : x = zero {{ symm h }}", SIGNATURE)),
+            Vec::<&str>::new());
+        assert_eq!(codes(&format!("{}theorem T : forall x: Nat. x = zero -> x = zero := given x. assume h : x = zero {{ symm h }}", SIGNATURE)),
+            vec!["ProofMismatch"]);
+        assert_eq!(codes(&format!("{}theorem T : P -> P := assume h : P {{ symm h }}", SIGNATURE)), vec!["NotEqualityRule"]);
+    }
+
+    #[test]
+    fn transitivity_rejects_non_equalities() {
+        assert_eq!(codes(&format!("{}theorem T : forall x: Nat. x = zero -> R x -> x = zero := given x. assume a : x = zero {{ assume b : R x {{ trans a b }} }}",
+            SIGNATURE)), vec!["NotEqualityRule"]);
+    }
+
+    #[test]
+    fn congruence_checks_function_type() {
+        assert_eq!(codes(&format!("{}theorem T : forall x: Nat. x = zero -> succ x = succ zero := given x. assume h : x = zero {{ congr succ h }}",
+            SIGNATURE)), Vec::<&str>::new());
+        assert_eq!(codes(&format!("{}theorem T : forall x: Nat. x = zero -> succ x = succ zero := given x. assume h : x = zero {{ congr neg h }}",
+            SIGNATURE)), vec!["ITypeMismatch"]);
+        assert_eq!(codes(&format!("{}theorem T : forall x: Nat. x = zero -> zero = zero := given x. assume h : x = zero {{ congr zero h }}",
+            SIGNATURE)), vec!["ITypeMismatch"]);
+    }
+
+    #[test]
+    fn leibniz_substitutes_into_predicate() {
+        assert_eq!(codes(&format!("{}theorem T : forall x: Nat. x = zero -> L x x -> L x zero :=
+            given x. assume e : x = zero {{ assume h : L x x {{ leibniz [lambda y: Nat. L x y] e h }} }}", SIGNATURE)), Vec::<&str>::new());
+        assert_eq!(codes(&format!("{}theorem T : forall x: Nat. x = zero -> L x zero -> L x zero :=
+            given x. assume e : x = zero {{ assume h : L x zero {{ leibniz [lambda y: Nat. L x y] e h }} }}", SIGNATURE)), vec!["ProofMismatch"]);
+        assert_eq!(codes(&format!("{}theorem T : forall x: Bool. x = x -> R zero -> R zero :=
+            given x: Bool. assume e : x = x {{ assume h : R zero {{ leibniz [lambda y: Nat. R y] e h }} }}", SIGNATURE)), vec!["ITypeMismatch"]);
+    }
+
+    #[test]
+    fn rewrite_avoids_capture() {
+        assert_eq!(codes(&format!("{}theorem T : forall x: Nat. x = zero -> R x -> R zero := given x. assume e : x = zero {{ assume h : R x {{ rewrite e in h }} }}",
+            SIGNATURE)), Vec::<&str>::new());
+        let axiom = "axiom A = forall y: Nat. L zero y\n";
+        assert_eq!(codes(&format!("{}{}theorem T : forall y: Nat. zero = y -> forall z: Nat. L y z := given y. assume e : zero = y {{ rewrite e in A }}",
+            SIGNATURE, axiom)), Vec::<&str>::new());
+        assert_eq!(codes(&format!("{}{}theorem T : forall y: Nat. zero = y -> forall z: Nat. L z z := given y. assume e : zero = y {{ rewrite e in A }}",
+            SIGNATURE, axiom)), vec!["ProofMismatch"]);
+        assert_eq!(codes(&format!("{}theorem T : P -> P := assume h : P {{ rewrite h in h }}", SIGNATURE)), vec!["NotEqualityRule"]);
+    }
+
+    #[test]
+    fn existential_rules() {
+        assert_eq!(codes(&format!("{}theorem T : R zero -> exists x: Nat. R x := assume h : R zero {{ exists_intro [exists x: Nat. R x] zero h }}",
+            SIGNATURE)), Vec::<&str>::new());
+        assert_eq!(codes(&format!("{}theorem T : R zero -> exists x: Nat. R x := assume h : R zero {{ exists_intro [exists x: Nat. R x] (succ zero) h }}",
+            SIGNATURE)), vec!["ProofMismatch"]);
+        assert_eq!(codes(&format!("{}let yes :: Term Bool\ntheorem T : R zero -> exists x: Nat. R x := assume h : R zero {{ exists_intro [exists x: Nat. R x] yes h }}",
+            SIGNATURE)), vec!["ITypeMismatch"]);
+        assert_eq!(codes(&format!("{}theorem T : (exists x: Nat. R x) -> exists y: Nat. R y :=
+            assume h : exists x: Nat. R x {{ obtain w, hw from h. exists_intro [exists y: Nat. R y] w hw }}", SIGNATURE)), Vec::<&str>::new());
+        assert_eq!(codes(&format!("{}theorem T : (exists x: Nat. R x) -> R zero := assume h : exists x: Nat. R x {{ obtain w, hw from h. hw }}",
+            SIGNATURE)), vec!["WitnessEscapes"]);
+    }
+
+    #[test]
+    fn polymorphic_instances_are_compared() {
+        let theory = |text: &str| format!("let Bool :: Type
+let c :: forall A. Term A
+let R :: forall A. Formula A
+axiom AllNat = forall x: Nat. R x
+axiom NoNat = forall x: Nat. not R x
+axiom NoBool = forall y: Bool. not R y
+{}", text);
+        assert_eq!(codes(&theory("theorem Ok : false := not_elim (NoNat c) (AllNat c)")), Vec::<&str>::new());
+        assert_eq!(codes(&theory("theorem Boom : false := not_elim (NoBool c) (AllNat c)")), vec!["PremiseMismatch"]);
+        assert_eq!(codes(&theory("theorem T : forall x: Nat. x = c -> R x -> R c := given x. assume e : x = c { assume h : R x { rewrite e in h } }")),
+            Vec::<&str>::new());
+        assert_eq!(codes(&theory("theorem T : forall x: Nat. x = c -> R x -> false :=
+            given x: Nat. assume e : x = c { assume h : R x { not_elim (NoNat c) (rewrite e in h) } }")), Vec::<&str>::new());
+        assert_eq!(codes(&theory("theorem T : forall x: Nat. x = c -> R x -> false :=
+            given x: Nat. assume e : x = c { assume h : R x { not_elim (NoBool c) (rewrite e in h) } }")), vec!["PremiseMismatch"]);
+    }
+
+    #[test]
+    fn polymorphic_schema_specializations_are_compared() {
+        let theory = |text: &str| format!("let Bool :: Type
+let tt :: Term Bool
+let c :: forall A. Term A
+let R :: forall A. Formula A
+axiom All = forall A :: Type. forall x: A. R x
+axiom NoNat = forall x: Nat. not R x
+axiom NoBool = forall y: Bool. not R y
+{}", text);
+        assert_eq!(codes(&theory("theorem N : false := not_elim (NoNat zero) (All zero)
+theorem B : false := not_elim (NoBool tt) (All tt)
+theorem E : false := not_elim (NoBool c) (All [Type Bool] c)")), Vec::<&str>::new());
+        assert_eq!(codes(&theory("theorem Boom : false := not_elim (NoBool c) (All [Type Nat] c)")), vec!["PremiseMismatch"]);
+        assert_eq!(codes(&theory("theorem Boom : false := not_elim (NoBool c) (All zero)")), vec!["PremiseMismatch"]);
+    }
+
+    #[test]
+    fn failed_comparison_leaves_types_unbound() {
+        let mut globals = Bindings::new();
+        let nat = InternalType::Named(Global(globals.get_id("Nat")));
+        let var = globals.fresh_type();
+        let symbol = |id: usize| Term::Symbol(Global(id), Vec::new());
+        let lhs = Formula::Equality(var.clone(), symbol(1), symbol(1));
+        assert!(!globals.equivalent(&lhs, &Formula::Equality(nat.clone(), symbol(1), symbol(2))));
+        assert_eq!(globals.resolve_type(&var), var);
+        assert!(globals.equivalent(&lhs, &Formula::Equality(nat.clone(), symbol(1), symbol(1))));
+        assert_eq!(globals.resolve_type(&var), nat);
+
+        let location = FileLocation::new("<test>", 0, 0);
+        let proof = |value: Formula| FormulaBuilder { arg_types: Vec::new(), value: value, location: location.clone() };
+        let var = globals.fresh_type();
+        let first = proof(Formula::Equality(var.clone(), symbol(1), symbol(2)));
+        assert!(first.trans(proof(Formula::Equality(nat.clone(), symbol(1), symbol(2))), &globals, location.clone()).is_err());
+        assert_eq!(globals.resolve_type(&var), var);
+
+        let bool_type = InternalType::Named(Global(globals.get_id("Bool")));
+        let func = |arg: InternalType, ret: InternalType| InternalType::Func(Box::new(arg), Box::new(ret));
+        let var = globals.fresh_type();
+        let function = TermBuilder { itype: func(func(var.clone(), bool_type.clone()), nat.clone()), value: symbol(4), location: location.clone() };
+        let equality = proof(Formula::Equality(func(nat.clone(), nat.clone()), symbol(5), symbol(6)));
+        assert!(equality.congr(function, &globals, location.clone()).is_err());
+        assert_eq!(globals.resolve_type(&var), var);
+    }
+
+    #[test]
+    fn failed_data_declarations_poison_every_name() {
+        assert_eq!(codes("data Color = red | green | red
+axiom C = forall c: Color. c = c
+axiom G = green = green
+axiom R = red = red"), vec!["BindingExists", "DependsOnFailed", "DependsOnFailed", "DependsOnFailed"]);
+        assert_eq!(codes("data Stream = cons Stream
+axiom S = forall s: Stream. cons s = s"), vec!["EmptyDataType", "DependsOnFailed"]);
+        assert_eq!(codes("data Tree = leaf | node (Tree -> Nat)
+axiom L = leaf = leaf"), vec!["NestedRecursion", "DependsOnFailed"]);
+    }
+
+    #[test]
+    fn data_declaration_keeps_existing_bindings() {
+        assert_eq!(codes("let red :: Term Nat
+data Color = red | green
+axiom R = red = zero
+axiom G = green = green"), vec!["BindingExists", "DependsOnFailed"]);
+    }
+
+    #[test]
+    fn dropped_declarations_are_poisoned() {
+        assert_eq!(codes("axiom A = = P\ntheorem T : P := A"), vec!["UnexpectedToken", "DependsOnFailed"]);
+        assert_eq!(codes("let S :: \naxiom A = forall s: S. s = s"), vec!["UnexpectedToken", "DependsOnFailed"]);
+    }
+
+    #[test]
+    fn proof_block_steps() {
+        assert_eq!(codes("proof T : P and Q -> Q and P {
+            have h1 : P and Q -> Q := assume h : P and Q { and_elim_right h };
+            have h2 : P and Q -> P := assume h : P and Q { and_elim_left h };
+            show P and Q -> Q and P := assume h : P and Q { and_intro (h1 $ h) (h2 $ h) }
+        }"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn proof_block_reports_failing_step() {
+        let errors = check("proof T : P -> P {
+    have h1 : P -> Q := assume p : P { p };
+    have h2 : P -> P := assume p : P { p };
+    show P -> P := h2
+}");
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0], ("ProofMismatch", (6, 24)));
+        assert_eq!(errors[1], ("FailedSteps", (5, 10)));
+    }
+
+    #[test]
+    fn proof_block_show_must_match_goal() {
+        assert_eq!(codes("proof T : P -> P {
+            have h : Q -> Q := assume q : Q { q };
+            show Q -> Q := h
+        }"), vec!["ProofMismatch"]);
+    }
+
+    #[test]
+    fn proof_block_steps_are_scoped() {
+        assert_eq!(codes("proof T : P -> P {
+            have h : P -> P := assume p : P { p };
+            show P -> P := h
+        }
+        theorem U : P -> P := h"), vec!["NoBinding"]);
+    }
+}
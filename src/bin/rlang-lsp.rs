@@ -0,0 +1,38 @@
+extern crate rlang;
+
+use std::env;
+use std::process;
+
+use rlang::lsp;
+
+
+const USAGE: &'static str = "\
+usage: rlang-lsp [options]
+
+runs a language server on standard input and output, as `rlang lsp` does
+
+options:
+    --no-prelude              do not load the bundled prelude theories
+    -h, --help                print this message";
+
+
+fn main() {
+    let mut prelude = true;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--no-prelude"  => prelude = false,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            },
+            _               => {
+                eprintln!("error: unknown option `{}`\n\n{}", arg, USAGE);
+                process::exit(2);
+            },
+        }
+    }
+    if let Err(err) = lsp::Server::new(prelude).run() {
+        eprintln!("error: cannot write to the client: {}", err);
+        process::exit(2);
+    }
+}
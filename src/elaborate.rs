@@ -0,0 +1,479 @@
+use ast::*;
+use error;
+use error::{Error, ErrorCollector, SourceInfo};
+use error::ErrorKind::*;
+use module;
+use syntax::*;
+
+
+const MAX_NUMERAL: usize = 1000;
+
+pub struct Elaborator<'a> {
+    locals: &'a mut LocalBindings,
+    globals: &'a mut Bindings,
+    source: &'a SourceInfo,
+    errors: &'a ErrorCollector,
+    bound: Vec<error::FileLocation>,
+}
+
+impl<'a> Elaborator<'a> {
+    pub fn new(locals: &'a mut LocalBindings, globals: &'a mut Bindings, source: &'a SourceInfo, errors: &'a ErrorCollector) -> Elaborator<'a> {
+        Elaborator {
+            locals: locals,
+            globals: globals,
+            source: source,
+            errors: errors,
+            bound: Vec::new(),
+        }
+    }
+
+    pub fn bound_names(&self) -> &[error::FileLocation] {
+        &self.bound
+    }
+
+    pub fn program(&mut self, stmts: Vec<Statement>) -> Vec<Declaration> {
+        stmts.into_iter().filter_map(|stmt| self.statement(stmt)).collect()
+    }
+
+    pub fn statement(&mut self, stmt: Statement) -> Option<Declaration> {
+        match stmt {
+            Statement::Let(_, name, mtype)       => {
+                let var = self.globals.declare(&name.text);
+                match self.meta_type(mtype).and_then(|mtype| self.globals.insert_object_noval(var, mtype, &name.location)) {
+                    Ok(_)      => Some(Declaration::Object(var)),
+                    Err(error) => self.fail(var, &name.location, error),
+                }
+            },
+            Statement::Data(_, name, ctors)      => {
+                let var = self.globals.declare(&name.text);
+                let names: Vec<(usize, error::FileLocation)> = ctors.iter().map(|ctor| {
+                    (self.globals.declare(&ctor.name.text), ctor.name.location.clone())
+                }).collect();
+                self.globals.checkpoint();
+                let prep = DataBuilder::prep(var, self.globals, name.location.clone());
+                let mut checked = Vec::new();
+                for (ctor, &(id, _)) in ctors.into_iter().zip(names.iter()) {
+                    let arg_types: Vec<error::Result<InternalType>> = ctor.args.into_iter().map(|arg| self.internal_type(arg)).collect();
+                    checked.push((id, arg_types.into_iter().collect(), ctor.name.location));
+                }
+                match prep.and_then(|_| DataBuilder::new(var, checked, self.globals, name.location.clone())).and_then(|data| data.save(self.globals)) {
+                    Ok(_)      => {
+                        self.globals.commit();
+                        Some(Declaration::Data(var))
+                    },
+                    Err(error) => {
+                        self.globals.rollback();
+                        for (ctor, location) in names.into_iter() {
+                            self.globals.poison(ctor, &location);
+                        }
+                        self.fail(var, &name.location, error)
+                    },
+                }
+            },
+            Statement::Axiom(_, name, sch)       => {
+                let var = self.globals.declare(&name.text);
+                match self.schema(sch).and_then(|schema| schema.save(var, self.globals, &name.location)) {
+                    Ok(_)      => Some(Declaration::Axiom(var)),
+                    Err(error) => self.fail(var, &name.location, error),
+                }
+            },
+            Statement::Theorem(_, name, sch) |
+            Statement::Proof(_, name, sch)       => {
+                let var = self.globals.declare(&name.text);
+                match self.schema(sch).and_then(|schema| schema.save(var, self.globals, &name.location)) {
+                    Ok(_)      => Some(Declaration::Theorem(var)),
+                    Err(error) => self.fail(var, &name.location, error),
+                }
+            },
+            Statement::Import(loc, import)       => {
+                if let Err(error) = module::import(import, self.globals, self.source, self.errors, &loc) {
+                    self.errors.report(error);
+                }
+                None
+            },
+            Statement::Dropped(_, name)          => {
+                let var = self.globals.declare(&name.text);
+                self.globals.poison(var, &name.location);
+                None
+            },
+            Statement::Error(error)              => {
+                self.errors.report(error);
+                self.locals.clear();
+                self.globals.close_scopes();
+                None
+            },
+        }
+    }
+
+    fn bind(&mut self, name: &Name) -> usize {
+        self.bound.push(name.location.clone());
+        self.globals.resolve(&name.text)
+    }
+
+    fn reference(&mut self, name: &Name) -> usize {
+        let id = self.globals.resolve(&name.text);
+        if self.locals.get_local(&id).is_some() {
+            self.bound.push(name.location.clone());
+        }
+        id
+    }
+
+    fn fail(&mut self, id: usize, location: &error::FileLocation, error: Error) -> Option<Declaration> {
+        self.globals.poison(id, location);
+        self.errors.report(error);
+        None
+    }
+
+    fn schema(&mut self, schema: Schema) -> error::Result<FSchemaBuilder> {
+        match schema {
+            Schema::Schema(loc, var, mtype, box sch)       => {
+                let typedec = self.schema_var(var, mtype);
+                let sch = self.schema(sch);
+                let (var, mtype) = typedec?;
+                FSchemaBuilder::schema(var, mtype, sch, self.locals, loc)
+            },
+            Schema::Poly(loc, var, box sch)                => {
+                let var = self.type_var(var);
+                let sch = self.schema(sch);
+                FSchemaBuilder::poly(var?, sch, self.locals, loc)
+            },
+            Schema::Formula(form)                          => FSchemaBuilder::formula(self.formula(form)?),
+            Schema::Proved(form, proof)                    => {
+                let form = self.formula(form);
+                let proof = self.proof(proof);
+                FSchemaBuilder::formula(form?.prove(proof?, self.globals)?)
+            },
+            Schema::Block(loc, goal, steps, form, proof) => {
+                let goal = self.formula(goal);
+                self.globals.push_scope();
+                let checked: Vec<bool> = steps.into_iter().map(|step| self.step(step)).collect();
+                let form = self.formula(form);
+                let proof = self.proof(proof);
+                let show = form.and_then(|form| form.prove(proof?, self.globals));
+                self.globals.pop_scope();
+                let goal = goal?;
+                let show = show?;
+                if checked.iter().any(|&checked| !checked) {
+                    return Err(Error::new(FailedSteps, &loc));
+                }
+                FSchemaBuilder::formula(goal.prove(show, self.globals)?)
+            },
+        }
+    }
+
+    fn schema_var(&mut self, var: Name, mtype: MetaTypeExpr) -> error::Result<(usize, MetaType)> {
+        let id = self.bind(&var);
+        let mtype = self.meta_type(mtype)?;
+        FSchemaBuilder::schema_prep(id, mtype.clone(), self.locals, self.globals, var.location.start())?;
+        Ok((id, mtype))
+    }
+
+    fn type_var(&mut self, var: Name) -> error::Result<usize> {
+        let id = self.bind(&var);
+        FSchemaBuilder::schema_prep(id, MetaType::Type, self.locals, self.globals, var.location.start())?;
+        Ok(id)
+    }
+
+    fn step(&mut self, step: Step) -> bool {
+        let hyp = self.globals.resolve(&step.hyp.text);
+        let form = self.formula(step.formula);
+        let proof = self.proof(step.proof);
+        match FormulaBuilder::have(hyp, form, proof, self.globals, step.location) {
+            Ok(())     => true,
+            Err(error) => {
+                self.errors.report(error);
+                false
+            },
+        }
+    }
+
+    fn proof(&mut self, proof: Proof) -> error::Result<FormulaBuilder> {
+        match proof {
+            Proof::Given(loc, binder, box form)                     => {
+                let typedec = self.binder(binder);
+                let form = self.proof(form);
+                let (var, itype) = typedec?;
+                FormulaBuilder::universal_q(var, itype, form, self.locals, self.globals, loc)
+            },
+            Proof::Contrapositive(loc, box form)                    => self.proof(form)?.contrapositive(loc),
+            Proof::Obtain(loc, var, hyp, box existential, box form) => {
+                let var = self.bind(&var);
+                let hyp = self.globals.resolve(&hyp.text);
+                let existential = self.proof(existential);
+                let prep = existential.and_then(|existential| {
+                    FormulaBuilder::exists_elim_prep(var, hyp, existential, self.locals, self.globals, loc.clone())
+                });
+                let form = self.proof(form);
+                prep?;
+                FormulaBuilder::exists_elim(var, form, self.locals, self.globals, loc)
+            },
+            Proof::Assume(loc, hyp, form, box body)                 => {
+                let hyp = self.globals.resolve(&hyp.text);
+                let form = self.formula(form);
+                let prep = form.and_then(|form| FormulaBuilder::assume_prep(hyp, form, self.globals, loc.clone()));
+                let body = self.proof(body);
+                FormulaBuilder::assume(prep?, body, self.globals, loc)
+            },
+            Proof::ModusPonens(box lhs, box rhs)                    => {
+                let lhs = self.proof(lhs);
+                let rhs = self.proof(rhs);
+                lhs?.modus_ponens(rhs?, self.globals)
+            },
+            Proof::Instantiate(box quant, term)                     => {
+                let quant = self.proof(quant);
+                let term = self.term(term);
+                quant?.instantiate(term?, self.globals)
+            },
+            Proof::Schema(schema)                                   => self.schema_spec(schema)?.get_formula(self.globals),
+            Proof::AndIntro(loc, box lhs, box rhs)                  => {
+                let lhs = self.proof(lhs);
+                let rhs = self.proof(rhs);
+                FormulaBuilder::and_intro(lhs?, rhs?, loc)
+            },
+            Proof::AndElimLeft(loc, box form)                       => self.proof(form)?.and_elim_left(loc),
+            Proof::AndElimRight(loc, box form)                      => self.proof(form)?.and_elim_right(loc),
+            Proof::OrIntroLeft(loc, other, box form)                => {
+                let other = self.formula(other);
+                let form = self.proof(form);
+                form?.or_intro_left(other?, loc)
+            },
+            Proof::OrIntroRight(loc, other, box form)               => {
+                let other = self.formula(other);
+                let form = self.proof(form);
+                form?.or_intro_right(other?, loc)
+            },
+            Proof::OrElim(loc, box form, box lhs, box rhs)          => {
+                let form = self.proof(form);
+                let lhs = self.proof(lhs);
+                let rhs = self.proof(rhs);
+                form?.or_elim(lhs?, rhs?, self.globals, loc)
+            },
+            Proof::NotIntro(loc, box form)                          => self.proof(form)?.not_intro(loc),
+            Proof::NotElim(loc, box neg, box form)                  => {
+                let neg = self.proof(neg);
+                let form = self.proof(form);
+                neg?.not_elim(form?, self.globals, loc)
+            },
+            Proof::IffIntro(loc, box form, box conv)                => {
+                let form = self.proof(form);
+                let conv = self.proof(conv);
+                form?.iff_intro(conv?, self.globals, loc)
+            },
+            Proof::IffElimLeft(loc, box form)                       => self.proof(form)?.iff_elim_left(loc),
+            Proof::IffElimRight(loc, box form)                      => self.proof(form)?.iff_elim_right(loc),
+            Proof::FalseElim(loc, target, box form)                 => {
+                let target = self.formula(target);
+                let form = self.proof(form);
+                form?.false_elim(target?, loc)
+            },
+            Proof::Refl(loc, term)                                  => {
+                let term = self.term(term);
+                FormulaBuilder::refl(term?, self.globals, loc)
+            },
+            Proof::Symm(loc, box form)                              => self.proof(form)?.symm(loc),
+            Proof::Trans(loc, box form, box other)                  => {
+                let form = self.proof(form);
+                let other = self.proof(other);
+                form?.trans(other?, self.globals, loc)
+            },
+            Proof::Congr(loc, func, box form)                       => {
+                let func = self.term(func);
+                let form = self.proof(form);
+                form?.congr(func?, self.globals, loc)
+            },
+            Proof::Leibniz(loc, pred, box eq, box form)             => {
+                let pred = self.formula_abs(pred);
+                let eq = self.proof(eq);
+                let form = self.proof(form);
+                FormulaBuilder::leibniz(pred?, eq?, form?, self.globals, loc)
+            },
+            Proof::Rewrite(loc, box eq, box form)                   => {
+                let eq = self.proof(eq);
+                let form = self.proof(form);
+                form?.rewrite(eq?, self.globals, loc)
+            },
+            Proof::ExistsIntro(loc, form, term, box proof)          => {
+                let form = self.formula(form);
+                let term = self.term(term);
+                let proof = self.proof(proof);
+                form?.exists_intro(term?, proof?, self.globals, loc)
+            },
+        }
+    }
+
+    fn schema_spec(&mut self, schema: SchemaSpec) -> error::Result<FSchemaBuilder> {
+        match schema {
+            SchemaSpec::Specify(box schema, arg) => {
+                let schema = self.schema_spec(schema);
+                let arg = self.schema_arg(arg);
+                schema?.specify(arg?, self.globals)
+            },
+            SchemaSpec::Theorem(name)            => {
+                let id = self.globals.resolve(&name.text);
+                FSchemaBuilder::saved_theorem(id, self.globals, name.location)
+            },
+        }
+    }
+
+    fn schema_arg(&mut self, arg: SchemaArg) -> error::Result<MetaValueBuilder> {
+        match arg {
+            SchemaArg::Type(loc, itype) => MetaValueBuilder::type_(self.internal_type(itype)?, loc),
+            SchemaArg::Term(term)       => MetaValueBuilder::term(self.term(term)?),
+            SchemaArg::Formula(form)    => MetaValueBuilder::formula(self.formula_abs(form)?),
+        }
+    }
+
+    pub fn formula(&mut self, expr: Expr) -> error::Result<FormulaBuilder> {
+        self.expr(expr)?.formula(self.globals)
+    }
+
+    fn formula_abs(&mut self, expr: Expr) -> error::Result<FormulaBuilder> {
+        match expr {
+            Expr::Abstraction(loc, binder, box form) => {
+                let typedec = self.binder(binder);
+                let form = self.formula_abs(form);
+                let (var, itype) = typedec?;
+                FormulaBuilder::abstraction(var, itype, form, self.locals, self.globals, loc)
+            },
+            expr                                     => self.formula(expr),
+        }
+    }
+
+    pub fn expr(&mut self, expr: Expr) -> error::Result<ExprBuilder> {
+        match expr {
+            Expr::Symbol(name)                     => {
+                let id = self.reference(&name);
+                ExprBuilder::symbol(id, self.locals, self.globals, name.location)
+            },
+            Expr::Application(box head, box arg)   => {
+                let head = self.expr(head);
+                let arg = self.expr(arg);
+                ExprBuilder::application(head?, arg?, self.globals)
+            },
+            Expr::False(loc)                       => Ok(ExprBuilder::Formula(FormulaBuilder::false_(loc)?)),
+            Expr::Equality(box lhs, box rhs)       => {
+                let lhs = self.expr(lhs);
+                let rhs = self.expr(rhs);
+                Ok(ExprBuilder::Formula(FormulaBuilder::equality(lhs?.term(self.globals)?, rhs?.term(self.globals)?, self.globals)?))
+            },
+            Expr::Negation(loc, box form)          => {
+                let form = self.expr(form);
+                Ok(ExprBuilder::Formula(FormulaBuilder::negation(form?.formula(self.globals)?, loc)?))
+            },
+            Expr::Conjunction(box lhs, box rhs)    => {
+                let lhs = self.expr(lhs);
+                let rhs = self.expr(rhs);
+                Ok(ExprBuilder::Formula(FormulaBuilder::conjunction(lhs?.formula(self.globals)?, rhs?.formula(self.globals)?)?))
+            },
+            Expr::Disjunction(box lhs, box rhs)    => {
+                let lhs = self.expr(lhs);
+                let rhs = self.expr(rhs);
+                Ok(ExprBuilder::Formula(FormulaBuilder::disjunction(lhs?.formula(self.globals)?, rhs?.formula(self.globals)?)?))
+            },
+            Expr::Implication(box lhs, box rhs)    => {
+                let lhs = self.expr(lhs);
+                let rhs = self.expr(rhs);
+                Ok(ExprBuilder::Formula(FormulaBuilder::implication(lhs?.formula(self.globals)?, rhs?.formula(self.globals)?)?))
+            },
+            Expr::Biconditional(box lhs, box rhs)  => {
+                let lhs = self.expr(lhs);
+                let rhs = self.expr(rhs);
+                Ok(ExprBuilder::Formula(FormulaBuilder::biconditional(lhs?.formula(self.globals)?, rhs?.formula(self.globals)?)?))
+            },
+            Expr::UniversalQ(loc, binder, box form) => {
+                let typedec = self.binder(binder);
+                let form = self.formula(form);
+                let (var, itype) = typedec?;
+                Ok(ExprBuilder::Formula(FormulaBuilder::universal_q(var, itype, form, self.locals, self.globals, loc)?))
+            },
+            Expr::ExistentialQ(loc, binder, box form) => {
+                let typedec = self.binder(binder);
+                let form = self.formula(form);
+                let (var, itype) = typedec?;
+                Ok(ExprBuilder::Formula(FormulaBuilder::existential_q(var, itype, form, self.locals, self.globals, loc)?))
+            },
+            expr @ Expr::Abstraction(..)           => Ok(ExprBuilder::Formula(self.formula_abs(expr)?)),
+            Expr::Numeral(loc, text)               => Ok(ExprBuilder::Term(self.numeral(loc, text)?)),
+        }
+    }
+
+    fn numeral(&mut self, loc: error::FileLocation, text: String) -> error::Result<TermBuilder> {
+        let value = match text.parse::<usize>() {
+            Ok(value) if value <= MAX_NUMERAL => value,
+            _                                 => return Err(Error::new(NumeralTooLarge { found: text }, &loc)),
+        };
+        let zero = self.globals.resolve("zero");
+        let succ = self.globals.resolve("succ");
+        let mut term = TermBuilder::symbol(zero, self.locals, self.globals, loc.clone())?;
+        for _ in 0..value {
+            let func = TermBuilder::symbol(succ, self.locals, self.globals, loc.clone())?;
+            term = TermBuilder::application(func, term, self.globals)?;
+        }
+        Ok(term)
+    }
+
+    fn term(&mut self, expr: Expr) -> error::Result<TermBuilder> {
+        match expr {
+            Expr::Symbol(name)                   => {
+                let id = self.reference(&name);
+                TermBuilder::symbol(id, self.locals, self.globals, name.location)
+            },
+            Expr::Application(box func, box arg) => {
+                let func = self.term(func);
+                let arg = self.term(arg);
+                TermBuilder::application(func?, arg?, self.globals)
+            },
+            expr                                 => self.expr(expr)?.term(self.globals),
+        }
+    }
+
+    fn binder(&mut self, binder: Binder) -> error::Result<(usize, InternalType)> {
+        let var = self.bind(&binder.name);
+        let loc = binder.name.location.start();
+        match binder.itype {
+            Some(itype) => {
+                let itype = self.internal_type(itype)?;
+                FormulaBuilder::quantifier_prep(var, itype.clone(), self.locals, self.globals, loc)?;
+                Ok((var, itype))
+            },
+            None        => Ok((var, FormulaBuilder::inferred_prep(var, self.locals, self.globals, loc)?)),
+        }
+    }
+
+    fn meta_type(&mut self, mtype: MetaTypeExpr) -> error::Result<MetaType> {
+        match mtype {
+            MetaTypeExpr::Poly(var, box mtype) => {
+                let var = self.type_var(var);
+                let mtype = self.meta_type(mtype);
+                quantify_type(var?, mtype, self.locals)
+            },
+            MetaTypeExpr::Type                 => Ok(MetaType::Type),
+            MetaTypeExpr::Term(itype)          => Ok(MetaType::Term(self.internal_type(itype)?)),
+            MetaTypeExpr::Formula(itypes)      => {
+                let itypes: Vec<error::Result<InternalType>> = itypes.into_iter().map(|itype| self.internal_type(itype)).collect();
+                Ok(MetaType::Formula(itypes.into_iter().rev().collect::<error::Result<Vec<InternalType>>>()?))
+            },
+        }
+    }
+
+    fn internal_type(&mut self, itype: TypeExpr) -> error::Result<InternalType> {
+        match itype {
+            TypeExpr::Func(box arg, box res) => {
+                let arg = self.internal_type(arg);
+                let res = self.internal_type(res);
+                Ok(InternalType::Func(Box::new(arg?), Box::new(res?)))
+            },
+            TypeExpr::Named(name)            => {
+                let id = self.reference(&name);
+                let locals = &*self.locals;
+                if let Some(local_id) = locals.get_local(&id) {
+                    Ok(InternalType::Named(Local(local_id)))
+                } else if let Some(MetaType::Type) = self.globals.get_type(&id) {
+                    Ok(InternalType::Named(Global(id)))
+                } else {
+                    Err(self.globals.no_binding(&id, |other| locals.get_local(other).is_some(), &name.location))
+                }
+            },
+        }
+    }
+}
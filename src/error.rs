@@ -1,319 +1,950 @@
-#![allow(dead_code)]
-
-use std::fs::File;
-use std::io;
-use std::io::BufReader;
-use std::io::prelude::*;
-use std::result;
-
-
-// pub trait Error {
-//     const ERROR_NAME: &'static str;
-
-//     fn location_string(&self) -> String;
-
-//     fn message(&self) -> String;
-
-//     fn to_console<W: Write>(&self, dest: &mut W) {
-//         dest.write(format!("At {}:\n{} : {}\n", self.location_string(),
-//             <Self as Error>::ERROR_NAME, self.message()).as_bytes());
-//     }
-// }
-
-
-// pub struct FileReadFailure {
-//     filename: String,
-//     line: usize,
-//     ekind: Option<io::ErrorKind>,
-// }
-
-// impl FileReadFailure {
-//     pub fn new(filename: &str, line: usize, ekind: Option<io::ErrorKind>) -> FileReadFailure {
-//         FileReadFailure {
-//             filename: String::from(filename),
-//             line: line,
-//             ekind: ekind.clone(),
-//         }
-//     }
-// }
-
-// impl Error for FileReadFailure {
-//     const ERROR_NAME: &'static str = "FileReadFailure";
-
-//     fn location_string(&self) -> String {
-//         format!("{}:{}", self.filename.clone(), self.line)
-//     }
-
-//     fn message(&self) -> String {
-//         if let Some(ioerr) = self.ekind {
-//             format!("could not read source file: {:?}", ioerr)
-//         } else {
-//             String::from("could not open source file")
-//         }
-//     }
-// }
-
-
-#[derive(Debug)]
-pub enum ErrorKind {
-    FileOpenFailure {
-        filename: String,
-        rust_err: io::ErrorKind,
-    },
-    FileReadFailure {
-        filename: String,
-        line: usize,
-        rust_err: io::ErrorKind,
-    },
-    UnexpectedToken {
-        found: String,
-        expected: Vec<String>,
-    },
-    NoBinding {
-        name: String,
-    },
-    BindingExists {
-        name: String,
-    },
-    ITypeMismatch {
-        found: String,
-        expected: String,
-    },
-    MTypeMismatch {
-        found: String,
-        expected: String,
-    },
-    UnboundImplication,
-    UnboundTheorem,
-    NotImplicationContra,
-    NotImplicationMP,
-    ModusPonensMismatch,
-    NotUniversalQInst,
-}
-
-use self::ErrorKind::*;
-
-
-pub struct Error {
-    error: ErrorKind,
-    location: FileLocation,
-}
-
-impl Error {
-    pub fn new(error: ErrorKind, location: &FileLocation) -> Error {
-        Error {
-            error: error,
-            location: location.clone(),
-        }
-    }
-
-    fn location_string(&self) -> String {
-        self.location.full_string()
-    }
-
-    fn err_type(&self) -> &'static str {
-        match self.error {
-            FileOpenFailure{..}  => "FileOpenFailure"     ,
-            FileReadFailure{..}  => "FileReadFailure"     ,
-            UnexpectedToken{..}  => "UnexpectedToken"     ,
-            NoBinding{..}        => "NoBinding"           ,
-            BindingExists{..}    => "BindingExists"       ,
-            ITypeMismatch{..}    => "ITypeMismatch"       ,
-            MTypeMismatch{..}    => "MTypeMismatch"       ,
-            UnboundImplication   => "UnboundImplication"  ,
-            UnboundTheorem       => "UnboundTheorem"      ,
-            NotImplicationContra => "NotImplicationContra",
-            NotImplicationMP     => "NotImplicationMP"    ,
-            ModusPonensMismatch  => "ModusPonensMismatch" ,
-            NotUniversalQInst    => "NotUniversalQInst"   ,
-        }
-    }
-
-    fn message(&self) -> String {
-        match self.error {
-            FileOpenFailure {
-                ref filename,
-                rust_err,
-            } => format!("could not open source file \"{}\" because of error `{:?}`",
-                filename, rust_err),
-            FileReadFailure {
-                ref filename,
-                line,
-                rust_err,
-            } => format!("could not read source file \"{}\" at line {} because of error `{:?}`",
-                filename, line, rust_err),
-            UnexpectedToken {
-                ref found,
-                ref expected,
-            } => format!("found token \"{}\", expected one of {:?}",
-                found, expected),
-            NoBinding {
-                ref name,
-            } => format!("no binding found for `{}`", name),
-            BindingExists {
-                ref name,
-            } => format!("duplicate binding of `{}`", name),
-            ITypeMismatch {
-                ref found,
-                ref expected,
-            } => format!("found type `{}`, expected type `{}`", found, expected),
-            MTypeMismatch {
-                ref found,
-                ref expected,
-            } => format!("found metalogical type `{}`, expected metalogical type `{}`", found, expected),
-            UnboundImplication   => String::from("implication between non-nullary formulae"),
-            UnboundTheorem       => String::from("axiom/theorem accepts logical arguments"),
-            NotImplicationContra => String::from("cannot take contrapositive because formula is not an implication"),
-            NotImplicationMP     => String::from("cannot apply modus ponens to non-implication"),
-            ModusPonensMismatch  => String::from("cannot apply modus ponens because the antecedent does not match the hypothesis"),
-            NotUniversalQInst    => String::from("cannot instantiate because the formula is not a universal quantification"),
-        }
-    }
-
-    pub fn to_console<W: Write>(&self, dest: &mut W, source: &SourceInfo) {
-        dest.write(format!("At {}:\n{}{} : {}\n",
-            self.location_string(), excerpt(source, &self.location), self.err_type(),
-            self.message()).as_bytes()).unwrap();
-    }
-
-    pub fn to_console_noexcerpt<W: Write>(&self, dest: &mut W) {
-        dest.write(format!("At {}:\n{} : {}\n",
-            self.location_string(), self.err_type(), self.message()).as_bytes()).unwrap();
-    }
-}
-
-
-pub type Result<T> = result::Result<T, Error>;
-
-
-#[derive(Debug)]
-pub struct SourceInfo {
-    filename: String,
-    lines: Vec<String>,
-    len_so_far: Vec<usize>,
-}
-
-impl SourceInfo {
-    pub fn new(filename: &str, context: &FileLocation) -> Result<SourceInfo> {
-        match File::open(filename.clone()) {
-            Ok(source_file) => {
-                let mut buf = BufReader::new(source_file);
-                let mut lines: Vec<String> = Vec::new();
-                let mut len_so_far: Vec<usize> = Vec::new();
-                let mut line = String::new();
-                loop {
-                    match buf.read_line(&mut line) {
-                        Ok(amt) => {
-                            if amt == 0 { break; }
-                            let partial_sum = match len_so_far.last() {
-                                Some(ref num) => **num,
-                                None          => 0usize,
-                            };
-                            len_so_far.push(amt + partial_sum);
-                            lines.push(line.replace("\r", "\u{200B}"));
-                            line.clear();
-                        },
-                        Err(err) => {
-                            return Err(Error::new(FileReadFailure {
-                                filename: String::from(filename),
-                                line: lines.len(),
-                                rust_err: err.kind(),
-                            }, context));
-                        },
-                    }
-                }
-                Ok(SourceInfo {
-                    filename: String::from(filename),
-                    lines: lines,
-                    len_so_far: len_so_far,
-                })
-            },
-            Err(err) => {
-                Err(Error::new(FileOpenFailure {
-                    filename: String::from(filename),
-                    rust_err: err.kind(),
-                }, context))
-            }
-        }
-    }
-
-    pub fn to_file_location(&self, index: usize) -> FileLocation {
-        match self.len_so_far.binary_search(&index) {
-            Ok(lno)  => FileLocation::new(&self.filename, lno, index - self.len_so_far.get(lno).unwrap()),
-            Err(lno) => FileLocation::new(&self.filename, lno, index - self.len_so_far.get(lno - 1).unwrap()),
-        }
-    }
-
-    pub fn get_line(&self, lno: usize) -> String {
-        self.lines.get(lno).unwrap().replace("\r", "").replace("\n", "")
-    }
-}
-
-
-#[derive(Clone, Debug)]
-pub struct FileLocation {
-    filename: String,
-    line: usize,
-    col: usize,
-}
-
-impl FileLocation {
-    pub fn new(filename: &str, line: usize, col: usize) -> FileLocation {
-        FileLocation {
-            filename: String::from(filename),
-            line: line,
-            col: col
-        }
-    }
-
-    // pub fn get_source(&self) -> &str {
-    //     &self.source
-    // }
-
-    // pub fn get_line(&self) -> usize {
-    //     self.line
-    // }
-
-    // pub fn get_col(&self) -> usize {
-    //     self.col
-    // }
-
-    pub fn full_string(&self) -> String {
-        format!("{}:{}:{}", self.filename, self.line, self.col)
-    }
-}
-
-
-fn excerpt(source: &SourceInfo, location: &FileLocation) -> String {
-    format!("|\n|\t{}\n|\t{}\n",
-        source.get_line(location.line), format!("{}^", String::from(" ").repeat(location.col)))
-}
-
-
-// pub trait ErrorLocated {
-//     const ERROR_NAME: &'static str;
-
-//     fn location(&self) -> &FileLocation;
-
-//     fn excerpt(&self) -> &str;
-
-//     fn message(&self) -> String;
-// }
-
-// impl<E: ErrorLocated> Error for E {
-//     const ERROR_NAME: &'static str = <E as ErrorLocated>::ERROR_NAME;
-
-//     fn location_string(&self) -> String {
-//         self.location().full_string()
-//     }
-
-//     fn message(&self) -> String {
-//         <E as ErrorLocated>::message(&self)
-//     }
-
-//     fn to_console<W: Write>(&self, dest: &mut W) {
-//         dest.write(format!("At {}:\n|\n|\t{}\n|\t{}\n{} : {}\n", self.location_string(),
-//             self.excerpt(), format!("{}^", String::from(" ").repeat(self.location().col)),
-//             <Self as Error>::ERROR_NAME, self.message()).as_bytes());
-//     }
-// }
\ No newline at end of file
+#![allow(dead_code)]
+
+use std::cell::{Cell, RefCell};
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+use std::io::prelude::*;
+use std::cmp;
+use std::mem;
+use std::result;
+
+use lalrpop_util::ParseError;
+
+use lexer::{LexicalError, Tok};
+
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    FileOpenFailure {
+        filename: String,
+        rust_err: io::ErrorKind,
+    },
+    FileReadFailure {
+        filename: String,
+        line: usize,
+        rust_err: io::ErrorKind,
+    },
+    FileWriteFailure {
+        filename: String,
+        rust_err: io::ErrorKind,
+    },
+    UnexpectedToken {
+        found: String,
+        expected: Vec<String>,
+    },
+    InvalidCharacter {
+        found: String,
+    },
+    UnterminatedComment,
+    NumeralTooLarge {
+        found: String,
+    },
+    NoBinding {
+        name: String,
+    },
+    BindingExists {
+        name: String,
+    },
+    ITypeMismatch {
+        found: String,
+        expected: String,
+    },
+    MTypeMismatch {
+        found: String,
+        expected: String,
+    },
+    UnboundImplication,
+    UnboundConnective,
+    UnboundTheorem,
+    NotImplicationContra,
+    NotImplicationMP,
+    ModusPonensMismatch,
+    NotUniversalQInst,
+    NotConjunctionElim,
+    NotDisjunctionElim,
+    NotNegationElim,
+    NotBiconditionalElim,
+    NotImplicationRule,
+    NotEqualityRule,
+    PremiseMismatch,
+    NotExistentialQIntro,
+    NotExistentialQElim,
+    WitnessEscapes,
+    UnspecifiedSchema,
+    NotSchemaSpec,
+    ProofMismatch,
+    FailedSteps,
+    DependsOnFailed {
+        name: String,
+    },
+    ModuleNotFound {
+        name: String,
+    },
+    ImportCycle {
+        cycle: String,
+    },
+    AmbiguousType {
+        name: String,
+    },
+    NestedRecursion {
+        name: String,
+    },
+    EmptyDataType {
+        name: String,
+    },
+}
+
+use self::ErrorKind::*;
+
+impl ErrorKind {
+    pub fn to_json(&self) -> String {
+        match self {
+            &FileOpenFailure {
+                ref filename,
+                rust_err,
+            } | &FileWriteFailure {
+                ref filename,
+                rust_err,
+            } => format!("{{\"filename\":{},\"io_error\":{}}}",
+                json_string(filename), json_string(&format!("{:?}", rust_err))),
+            &FileReadFailure {
+                ref filename,
+                line,
+                rust_err,
+            } => format!("{{\"filename\":{},\"line\":{},\"io_error\":{}}}",
+                json_string(filename), line + 1, json_string(&format!("{:?}", rust_err))),
+            &UnexpectedToken {
+                ref found,
+                ref expected,
+            } => format!("{{\"found\":{},\"expected\":{}}}",
+                json_string(found), json_list(expected.iter().map(|token| json_string(token)))),
+            &InvalidCharacter {
+                ref found,
+            } | &NumeralTooLarge {
+                ref found,
+            } => format!("{{\"found\":{}}}", json_string(found)),
+            &NoBinding {
+                ref name,
+            } | &BindingExists {
+                ref name,
+            } | &DependsOnFailed {
+                ref name,
+            } | &ModuleNotFound {
+                ref name,
+            } | &AmbiguousType {
+                ref name,
+            } | &NestedRecursion {
+                ref name,
+            } | &EmptyDataType {
+                ref name,
+            } => format!("{{\"name\":{}}}", json_string(name)),
+            &ITypeMismatch {
+                ref found,
+                ref expected,
+            } | &MTypeMismatch {
+                ref found,
+                ref expected,
+            } => format!("{{\"found\":{},\"expected\":{}}}",
+                json_string(found), json_string(expected)),
+            &ImportCycle {
+                ref cycle,
+            } => format!("{{\"cycle\":{}}}", json_string(cycle)),
+            _ => String::from("{}"),
+        }
+    }
+}
+
+
+#[derive(Debug)]
+pub struct Error {
+    error: ErrorKind,
+    location: FileLocation,
+    labels: Vec<(FileLocation, String)>,
+    notes: Vec<String>,
+    help: Vec<String>,
+}
+
+impl Error {
+    pub fn new(error: ErrorKind, location: &FileLocation) -> Error {
+        Error {
+            error: error,
+            location: location.clone(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+        }
+    }
+
+    pub fn location(&self) -> &FileLocation {
+        &self.location
+    }
+
+    pub fn with_label(mut self, location: &FileLocation, label: String) -> Error {
+        self.labels.push((location.clone(), label));
+        self
+    }
+
+    pub fn with_note(mut self, note: String) -> Error {
+        self.notes.push(note);
+        self
+    }
+
+    pub fn with_help(mut self, help: String) -> Error {
+        self.help.push(help);
+        self
+    }
+
+    pub fn from_parse_error(error: ParseError<usize, Tok, LexicalError>, source: &SourceInfo) -> Error {
+        match error {
+            ParseError::InvalidToken { location }                    => {
+                Error::new(UnexpectedToken {
+                    found: String::from("<invalid token>"),
+                    expected: Vec::new(),
+                }, &source.to_file_location(location))
+            },
+            ParseError::UnrecognizedToken { token: Some((start, found, end)), expected } => {
+                Error::new(UnexpectedToken {
+                    found: found.to_string(),
+                    expected: expected.iter().map(|token| describe_token(token)).collect(),
+                }, &source.to_span(start, end))
+            },
+            ParseError::UnrecognizedToken { token: None, expected } => {
+                Error::new(UnexpectedToken {
+                    found: String::from("<end of file>"),
+                    expected: expected.iter().map(|token| describe_token(token)).collect(),
+                }, &source.to_file_location(source.len()))
+            },
+            ParseError::ExtraToken { token: (start, found, end) }   => {
+                Error::new(UnexpectedToken {
+                    found: found.to_string(),
+                    expected: vec![String::from("<end of file>")],
+                }, &source.to_span(start, end))
+            },
+            ParseError::User { error }                               => Error::from_lexical_error(error, source),
+        }
+    }
+
+    pub fn from_lexical_error(error: LexicalError, source: &SourceInfo) -> Error {
+        match error {
+            LexicalError::InvalidCharacter(location, found) => {
+                Error::new(InvalidCharacter {
+                    found: found.to_string(),
+                }, &source.to_span(location, location + found.len_utf8()))
+            },
+            LexicalError::UnterminatedComment(location)     => {
+                Error::new(UnterminatedComment, &source.to_file_location(location))
+            },
+        }
+    }
+
+    fn location_string(&self) -> String {
+        self.location.full_string()
+    }
+
+    pub fn err_type(&self) -> &'static str {
+        match self.error {
+            FileOpenFailure{..}  => "FileOpenFailure"     ,
+            FileReadFailure{..}  => "FileReadFailure"     ,
+            FileWriteFailure{..} => "FileWriteFailure"    ,
+            UnexpectedToken{..}  => "UnexpectedToken"     ,
+            InvalidCharacter{..} => "InvalidCharacter"    ,
+            UnterminatedComment  => "UnterminatedComment" ,
+            NumeralTooLarge{..}  => "NumeralTooLarge"     ,
+            NoBinding{..}        => "NoBinding"           ,
+            BindingExists{..}    => "BindingExists"       ,
+            ITypeMismatch{..}    => "ITypeMismatch"       ,
+            MTypeMismatch{..}    => "MTypeMismatch"       ,
+            UnboundImplication   => "UnboundImplication"  ,
+            UnboundConnective    => "UnboundConnective"   ,
+            UnboundTheorem       => "UnboundTheorem"      ,
+            NotImplicationContra => "NotImplicationContra",
+            NotImplicationMP     => "NotImplicationMP"    ,
+            ModusPonensMismatch  => "ModusPonensMismatch" ,
+            NotUniversalQInst    => "NotUniversalQInst"   ,
+            NotConjunctionElim   => "NotConjunctionElim"  ,
+            NotDisjunctionElim   => "NotDisjunctionElim"  ,
+            NotNegationElim      => "NotNegationElim"     ,
+            NotBiconditionalElim => "NotBiconditionalElim",
+            NotImplicationRule   => "NotImplicationRule"  ,
+            NotEqualityRule      => "NotEqualityRule"     ,
+            PremiseMismatch      => "PremiseMismatch"     ,
+            NotExistentialQIntro => "NotExistentialQIntro",
+            NotExistentialQElim  => "NotExistentialQElim" ,
+            WitnessEscapes       => "WitnessEscapes"      ,
+            UnspecifiedSchema    => "UnspecifiedSchema"   ,
+            NotSchemaSpec        => "NotSchemaSpec"       ,
+            ProofMismatch        => "ProofMismatch"       ,
+            FailedSteps          => "FailedSteps"         ,
+            DependsOnFailed{..}  => "DependsOnFailed"     ,
+            ModuleNotFound{..}   => "ModuleNotFound"      ,
+            ImportCycle{..}      => "ImportCycle"         ,
+            AmbiguousType{..}    => "AmbiguousType"       ,
+            NestedRecursion{..}  => "NestedRecursion"     ,
+            EmptyDataType{..}    => "EmptyDataType"       ,
+        }
+    }
+
+    fn message(&self) -> String {
+        match self.error {
+            FileOpenFailure {
+                ref filename,
+                rust_err,
+            } => format!("could not open source file \"{}\" because of error `{:?}`",
+                filename, rust_err),
+            FileReadFailure {
+                ref filename,
+                line,
+                rust_err,
+            } => format!("could not read source file \"{}\" at line {} because of error `{:?}`",
+                filename, line, rust_err),
+            FileWriteFailure {
+                ref filename,
+                rust_err,
+            } => format!("could not write source file \"{}\" because of error `{:?}`",
+                filename, rust_err),
+            UnexpectedToken {
+                ref found,
+                ref expected,
+            } => format!("found token \"{}\", expected one of {:?}",
+                found, expected),
+            InvalidCharacter {
+                ref found,
+            } => format!("unexpected character \"{}\"", found),
+            UnterminatedComment  => String::from("block comment is never closed"),
+            NumeralTooLarge {
+                ref found,
+            } => format!("numeral `{}` is too large to be written out in unary", found),
+            NoBinding {
+                ref name,
+            } => format!("no binding found for `{}`", name),
+            BindingExists {
+                ref name,
+            } => format!("duplicate binding of `{}`", name),
+            ITypeMismatch {
+                ref found,
+                ref expected,
+            } => format!("found type `{}`, expected type `{}`", found, expected),
+            MTypeMismatch {
+                ref found,
+                ref expected,
+            } => format!("found metalogical type `{}`, expected metalogical type `{}`", found, expected),
+            UnboundImplication   => String::from("implication between non-nullary formulae"),
+            UnboundConnective    => String::from("logical connective applied to non-nullary formulae"),
+            UnboundTheorem       => String::from("axiom/theorem accepts logical arguments"),
+            NotImplicationContra => String::from("cannot take contrapositive because formula is not an implication"),
+            NotImplicationMP     => String::from("cannot apply modus ponens to non-implication"),
+            ModusPonensMismatch  => String::from("cannot apply modus ponens because the antecedent does not match the hypothesis"),
+            NotUniversalQInst    => String::from("cannot instantiate because the formula is not a universal quantification"),
+            NotConjunctionElim   => String::from("cannot eliminate conjunction because the formula is not a conjunction"),
+            NotDisjunctionElim   => String::from("cannot eliminate disjunction because the formula is not a disjunction"),
+            NotNegationElim      => String::from("cannot eliminate negation because the formula is not a negation"),
+            NotBiconditionalElim => String::from("cannot eliminate biconditional because the formula is not a biconditional"),
+            NotImplicationRule   => String::from("cannot apply rule because the premise is not an implication"),
+            NotEqualityRule      => String::from("cannot apply rule because the premise is not an equality"),
+            PremiseMismatch      => String::from("cannot apply rule because the premises do not match each other"),
+            NotExistentialQIntro => String::from("cannot introduce witness because the formula is not an existential quantification"),
+            NotExistentialQElim  => String::from("cannot obtain witness because the formula is not an existential quantification"),
+            WitnessEscapes       => String::from("the obtained witness occurs free in the conclusion"),
+            UnspecifiedSchema    => String::from("cannot use schema as a formula before all of its arguments are specified"),
+            NotSchemaSpec        => String::from("cannot specify because the theorem is not a schema"),
+            ProofMismatch        => String::from("the proof does not establish the stated formula"),
+            FailedSteps          => String::from("the proof block contains steps that failed to check"),
+            DependsOnFailed {
+                ref name,
+            } => format!("depends on failed item `{}`", name),
+            ModuleNotFound {
+                ref name,
+            } => format!("could not find module `{}`", name),
+            ImportCycle {
+                ref cycle,
+            } => format!("import cycle detected: {}", cycle),
+            AmbiguousType {
+                ref name,
+            } => format!("cannot infer the type of `{}`", name),
+            NestedRecursion {
+                ref name,
+            } => format!("`{}` may only occur as a whole constructor argument", name),
+            EmptyDataType {
+                ref name,
+            } => format!("data type `{}` has no constructor without recursive arguments", name),
+        }
+    }
+
+    pub fn to_console<W: Write>(&self, dest: &mut W, source: &SourceInfo, color: bool) -> io::Result<()> {
+        let mut spans: Vec<(&FileLocation, Option<&str>)> = vec![(&self.location, None)];
+        let mut elsewhere: Vec<&(FileLocation, String)> = Vec::new();
+        for label in self.labels.iter() {
+            if label.0.filename == source.filename {
+                spans.push((&label.0, Some(&label.1)));
+            } else {
+                elsewhere.push(label);
+            }
+        }
+        spans.sort_by_key(|&(loc, _)| (loc.line, loc.col));
+        let width = spans.iter().map(|&(loc, _)| (loc.line + 1).to_string().len()).max().unwrap_or(1);
+        let gutter = paint(&format!("{} |", " ".repeat(width)), BLUE, color);
+
+        let mut out = format!("{}: {}\n{}{} {}\n{}\n",
+            paint(&format!("error[{}]", self.err_type()), RED, color), paint(&self.message(), BOLD, color),
+            " ".repeat(width), paint("-->", BLUE, color), self.location_string(), gutter);
+        let mut last_line: Option<usize> = None;
+        for &(loc, label) in spans.iter() {
+            if last_line != Some(loc.line) {
+                if let Some(prev) = last_line {
+                    if loc.line > prev + 1 {
+                        out.push_str(&format!("{}\n", paint("...", BLUE, color)));
+                    }
+                }
+                out.push_str(&format!("{} {}\n",
+                    paint(&format!("{:>w$} |", loc.line + 1, w = width), BLUE, color), source.get_line(loc.line)));
+                last_line = Some(loc.line);
+            }
+            let line = source.get_line(loc.line);
+            let start = char_offset(&line, loc.col);
+            let end = match loc.end {
+                Some((end_line, end_col)) if end_line == loc.line => cmp::max(start + 1, char_offset(&line, end_col)),
+                Some(_)                                           => cmp::max(start + 1, line.chars().count()),
+                None                                              => start + 1,
+            };
+            let (mark, style) = if label.is_some() { ("-", BLUE) } else { ("^", RED) };
+            let underline = mark.repeat(end - start);
+            out.push_str(&format!("{} {}{}\n", gutter, " ".repeat(start), match label {
+                Some(text) => paint(&format!("{} {}", underline, text), style, color),
+                None       => paint(&underline, style, color),
+            }));
+        }
+        for &&(ref loc, ref text) in elsewhere.iter() {
+            out.push_str(&format!("{} {}: {} ({})\n",
+                paint(&format!("{} =", " ".repeat(width)), BLUE, color), paint("note", BOLD, color), text, loc.full_string()));
+        }
+        for note in self.notes.iter() {
+            out.push_str(&format!("{} {}: {}\n", paint(&format!("{} =", " ".repeat(width)), BLUE, color), paint("note", BOLD, color), note));
+        }
+        for help in self.help.iter() {
+            out.push_str(&format!("{} {}: {}\n", paint(&format!("{} =", " ".repeat(width)), BLUE, color), paint("help", BOLD, color), help));
+        }
+        out.push('\n');
+        dest.write_all(out.as_bytes())
+    }
+
+    pub fn to_json<W: Write>(&self, dest: &mut W) -> io::Result<()> {
+        let related = self.labels.iter().map(|&(ref loc, ref text)| {
+            format!("{{\"message\":{},{}}}", json_string(text), loc.json_fields())
+        });
+        dest.write_all(format!("{{\"code\":{},\"message\":{},{},\"fields\":{},\"related\":{},\"notes\":{},\"help\":{}}}\n",
+            json_string(self.err_type()), json_string(&self.message()), self.location.json_fields(), self.error.to_json(),
+            json_list(related), json_list(self.notes.iter().map(|note| json_string(note))),
+            json_list(self.help.iter().map(|help| json_string(help)))).as_bytes())
+    }
+
+    pub fn to_diagnostic(&self, source: &SourceInfo, uri: &str) -> String {
+        let local = self.location.filename == source.filename;
+        let mut message = if local { self.message() } else { format!("{}: {}", self.location_string(), self.message()) };
+        for note in self.notes.iter() {
+            message.push_str(&format!("\nnote: {}", note));
+        }
+        for help in self.help.iter() {
+            message.push_str(&format!("\nhelp: {}", help));
+        }
+        let range = if local { self.location.to_range(source) } else { FileLocation::new(&source.filename, 0, 0).to_range(source) };
+        let related = self.labels.iter().filter(|&&(ref loc, _)| loc.filename == source.filename).map(|&(ref loc, ref text)| {
+            format!("{{\"location\":{{\"uri\":{},\"range\":{}}},\"message\":{}}}", json_string(uri), loc.to_range(source), json_string(text))
+        });
+        format!("{{\"range\":{},\"severity\":1,\"code\":{},\"source\":\"rlang\",\"message\":{},\"relatedInformation\":{}}}",
+            range, json_string(self.err_type()), json_string(&message), json_list(related))
+    }
+
+    pub fn to_console_noexcerpt<W: Write>(&self, dest: &mut W) -> io::Result<()> {
+        dest.write_all(format!("At {}:\n{} : {}\n",
+            self.location_string(), self.err_type(), self.message()).as_bytes())?;
+        for help in self.help.iter() {
+            dest.write_all(format!("help: {}\n", help).as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+
+pub type Result<T> = result::Result<T, Error>;
+
+
+pub fn read_source(filename: &str, context: &FileLocation) -> Result<(SourceInfo, String)> {
+    let source_info = SourceInfo::new(filename, context)?;
+    let mut file_text = String::new();
+    match File::open(filename).and_then(|mut f| f.read_to_string(&mut file_text)) {
+        Ok(_)    => Ok((source_info, file_text)),
+        Err(err) => Err(Error::new(FileReadFailure {
+            filename: String::from(filename),
+            line: 0,
+            rust_err: err.kind(),
+        }, context)),
+    }
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorFormat {
+    Human,
+    Short,
+    Json,
+}
+
+
+pub struct ErrorSink {
+    format: ErrorFormat,
+    color: bool,
+    count: Cell<usize>,
+}
+
+impl ErrorSink {
+    pub fn new(format: ErrorFormat, color: bool) -> ErrorSink {
+        ErrorSink {
+            format: format,
+            color: color,
+            count: Cell::new(0),
+        }
+    }
+
+    pub fn report<W: Write>(&self, dest: &mut W, error: &Error, source: &SourceInfo) -> io::Result<()> {
+        self.count.set(self.count.get() + 1);
+        match self.format {
+            ErrorFormat::Human => error.to_console(dest, source, self.color),
+            ErrorFormat::Short => error.to_console_noexcerpt(dest),
+            ErrorFormat::Json  => error.to_json(dest),
+        }
+    }
+
+    pub fn report_noexcerpt<W: Write>(&self, dest: &mut W, error: &Error) -> io::Result<()> {
+        self.count.set(self.count.get() + 1);
+        match self.format {
+            ErrorFormat::Json => error.to_json(dest),
+            _                 => error.to_console_noexcerpt(dest),
+        }
+    }
+
+    pub fn flush<W: Write>(&self, dest: &mut W, errors: &ErrorCollector, source: &SourceInfo) -> io::Result<()> {
+        let sources = errors.sources.borrow();
+        for error in errors.take().iter() {
+            if error.location.filename == source.filename {
+                self.report(dest, error, source)?;
+            } else if let Some(other) = sources.iter().find(|other| error.location.filename == other.filename) {
+                self.report(dest, error, other)?;
+            } else {
+                self.report_noexcerpt(dest, error)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn format(&self) -> ErrorFormat {
+        self.format
+    }
+
+    pub fn count(&self) -> usize {
+        self.count.get()
+    }
+}
+
+
+pub struct ErrorCollector {
+    errors: RefCell<Vec<Error>>,
+    sources: RefCell<Vec<SourceInfo>>,
+}
+
+impl ErrorCollector {
+    pub fn new() -> ErrorCollector {
+        ErrorCollector {
+            errors: RefCell::new(Vec::new()),
+            sources: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn add_source(&self, source: SourceInfo) {
+        self.sources.borrow_mut().push(source);
+    }
+
+    pub fn report(&self, error: Error) {
+        self.errors.borrow_mut().push(error);
+    }
+
+    pub fn len(&self) -> usize {
+        self.errors.borrow().len()
+    }
+
+    pub fn take(&self) -> Vec<Error> {
+        mem::replace(&mut *self.errors.borrow_mut(), Vec::new())
+    }
+}
+
+
+#[derive(Debug)]
+pub struct SourceInfo {
+    filename: String,
+    lines: Vec<String>,
+    len_so_far: Vec<usize>,
+}
+
+impl SourceInfo {
+    pub fn new(filename: &str, context: &FileLocation) -> Result<SourceInfo> {
+        match File::open(filename) {
+            Ok(source_file) => {
+                let mut buf = BufReader::new(source_file);
+                let mut lines: Vec<String> = Vec::new();
+                let mut len_so_far: Vec<usize> = Vec::new();
+                let mut line = String::new();
+                loop {
+                    match buf.read_line(&mut line) {
+                        Ok(amt) => {
+                            if amt == 0 { break; }
+                            let partial_sum = match len_so_far.last() {
+                                Some(ref num) => **num,
+                                None          => 0usize,
+                            };
+                            len_so_far.push(amt + partial_sum);
+                            lines.push(line.replace("\r", "\u{200B}"));
+                            line.clear();
+                        },
+                        Err(err) => {
+                            return Err(Error::new(FileReadFailure {
+                                filename: String::from(filename),
+                                line: lines.len(),
+                                rust_err: err.kind(),
+                            }, context));
+                        },
+                    }
+                }
+                Ok(SourceInfo {
+                    filename: String::from(filename),
+                    lines: lines,
+                    len_so_far: len_so_far,
+                })
+            },
+            Err(err) => {
+                Err(Error::new(FileOpenFailure {
+                    filename: String::from(filename),
+                    rust_err: err.kind(),
+                }, context))
+            }
+        }
+    }
+
+    pub fn from_text(filename: &str, text: &str) -> SourceInfo {
+        let mut lines: Vec<String> = Vec::new();
+        let mut len_so_far: Vec<usize> = Vec::new();
+        for line in text.split_inclusive('\n') {
+            let partial_sum = match len_so_far.last() {
+                Some(ref num) => **num,
+                None          => 0usize,
+            };
+            len_so_far.push(line.len() + partial_sum);
+            lines.push(line.replace("\r", "\u{200B}"));
+        }
+        SourceInfo {
+            filename: String::from(filename),
+            lines: lines,
+            len_so_far: len_so_far,
+        }
+    }
+
+    pub fn to_file_location(&self, index: usize) -> FileLocation {
+        match self.len_so_far.binary_search(&index) {
+            Ok(lno) if self.lines[lno].ends_with('\n')  => FileLocation::new(&self.filename, lno + 1, 0),
+            Ok(0) | Err(0)                              => FileLocation::new(&self.filename, 0, index),
+            Ok(lno) | Err(lno)                          => FileLocation::new(&self.filename, lno, index - self.len_so_far.get(lno - 1).unwrap()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        *self.len_so_far.last().unwrap_or(&0)
+    }
+
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    pub fn to_span(&self, start: usize, end: usize) -> FileLocation {
+        self.to_file_location(start).to(&self.to_file_location(end))
+    }
+
+    pub fn get_line(&self, lno: usize) -> String {
+        match self.lines.get(lno) {
+            Some(line) => line.replace("\r", "").replace("\n", ""),
+            None       => String::new(),
+        }
+    }
+}
+
+
+#[derive(Clone, Debug)]
+pub struct FileLocation {
+    filename: String,
+    line: usize,
+    col: usize,
+    end: Option<(usize, usize)>,
+}
+
+impl FileLocation {
+    pub fn new(filename: &str, line: usize, col: usize) -> FileLocation {
+        FileLocation {
+            filename: String::from(filename),
+            line: line,
+            col: col,
+            end: None,
+        }
+    }
+
+    pub fn start(&self) -> FileLocation {
+        FileLocation {
+            filename: self.filename.clone(),
+            line: self.line,
+            col: self.col,
+            end: None,
+        }
+    }
+
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    pub fn position(&self) -> (usize, usize) {
+        (self.line, self.col)
+    }
+
+    pub fn to(&self, other: &FileLocation) -> FileLocation {
+        let end = cmp::max(other.end.unwrap_or((other.line, other.col)), self.end.unwrap_or((self.line, self.col)));
+        FileLocation {
+            filename: self.filename.clone(),
+            line: self.line,
+            col: self.col,
+            end: Some(end),
+        }
+    }
+
+    // pub fn get_source(&self) -> &str {
+    //     &self.source
+    // }
+
+    // pub fn get_line(&self) -> usize {
+    //     self.line
+    // }
+
+    // pub fn get_col(&self) -> usize {
+    //     self.col
+    // }
+
+    pub fn full_string(&self) -> String {
+        format!("{}:{}:{}", self.filename, self.line + 1, self.col + 1)
+    }
+
+    pub fn to_json(&self) -> String {
+        format!("{{{}}}", self.json_fields())
+    }
+
+    pub fn to_range(&self, source: &SourceInfo) -> String {
+        let (end_line, end_col) = self.end.unwrap_or((self.line, self.col + 1));
+        format!("{{\"start\":{},\"end\":{}}}", lsp_position(source, self.line, self.col), lsp_position(source, end_line, end_col))
+    }
+
+    fn json_fields(&self) -> String {
+        let (end_line, end_col) = self.end.unwrap_or((self.line, self.col + 1));
+        format!("\"file\":{},\"line\":{},\"column\":{},\"end_line\":{},\"end_column\":{}",
+            json_string(&self.filename), self.line + 1, self.col + 1, end_line + 1, end_col + 1)
+    }
+}
+
+
+fn describe_token(token: &str) -> String {
+    String::from(token.trim_matches('"'))
+}
+
+
+pub fn edit_distance(lhs: &str, rhs: &str) -> usize {
+    let lhs: Vec<char> = lhs.chars().collect();
+    let rhs: Vec<char> = rhs.chars().collect();
+    let mut prev: Vec<usize> = Vec::new();
+    let mut row: Vec<usize> = (0..rhs.len() + 1).collect();
+    for i in 0..lhs.len() {
+        let mut next = vec![i + 1; rhs.len() + 1];
+        for j in 0..rhs.len() {
+            let cost = if lhs[i] == rhs[j] { 0 } else { 1 };
+            next[j + 1] = cmp::min(cmp::min(row[j + 1], next[j]) + 1, row[j] + cost);
+            if i > 0 && j > 0 && lhs[i] == rhs[j - 1] && lhs[i - 1] == rhs[j] {
+                next[j + 1] = cmp::min(next[j + 1], prev[j - 1] + 1);
+            }
+        }
+        prev = row;
+        row = next;
+    }
+    row[rhs.len()]
+}
+
+
+pub fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"'                    => out.push_str("\\\""),
+            '\\'                   => out.push_str("\\\\"),
+            '\n'                   => out.push_str("\\n"),
+            '\r'                   => out.push_str("\\r"),
+            '\t'                   => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c                      => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+pub fn json_list<I: Iterator<Item = String>>(items: I) -> String {
+    format!("[{}]", items.collect::<Vec<String>>().join(","))
+}
+
+
+fn char_offset(line: &str, byte: usize) -> usize {
+    line.char_indices().take_while(|&(i, _)| i < byte).count()
+}
+
+fn lsp_position(source: &SourceInfo, line: usize, col: usize) -> String {
+    let character: usize = source.get_line(line).char_indices().take_while(|&(i, _)| i < col).map(|(_, c)| c.len_utf16()).sum();
+    format!("{{\"line\":{},\"character\":{}}}", line, character)
+}
+
+
+const RED: &'static str = "1;31";
+const BLUE: &'static str = "1;34";
+const BOLD: &'static str = "1";
+
+fn paint(text: &str, style: &str, color: bool) -> String {
+    if color {
+        format!("\x1b[{}m{}\x1b[0m", style, text)
+    } else {
+        String::from(text)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ast::*;
+    use elaborate::Elaborator;
+    use json::Json;
+    use syntax;
+
+    fn check(text: &str) -> (SourceInfo, Vec<Error>) {
+        let source = SourceInfo::from_text("<test>", text);
+        let errors = ErrorCollector::new();
+        let mut locals = LocalBindings::new();
+        let mut globals = Bindings::new();
+        let stmts = syntax::parse_program(&source, text);
+        Elaborator::new(&mut locals, &mut globals, &source, &errors).program(stmts);
+        let errors = errors.take();
+        (source, errors)
+    }
+
+    fn render(error: &Error, source: &SourceInfo) -> String {
+        let mut out = Vec::new();
+        error.to_console(&mut out, source, false).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn renders_labels_notes_and_help() {
+        let (source, errors) = check("let Nat :: Type\nlet Bool :: Type\nlet zero :: Term Nat\nlet t :: Term Bool\naxiom A = zero\n\n    = t");
+        assert_eq!(errors.len(), 1);
+        let error = errors.into_iter().next().unwrap()
+            .with_label(&FileLocation::new("other.rl", 1, 2), String::from("elsewhere"))
+            .with_note(String::from("a note"))
+            .with_help(String::from("some help"));
+        assert_eq!(render(&error, &source), "\
+error[ITypeMismatch]: found type `Bool`, expected type `Nat`
+ --> <test>:7:7
+  |
+5 | axiom A = zero
+  |           ---- this has type `Nat`
+...
+7 |     = t
+  |       ^
+  = note: elsewhere (other.rl:2:3)
+  = note: a note
+  = help: some help
+
+");
+    }
+
+    #[test]
+    fn suggests_similar_names() {
+        let (source, errors) = check("let Nat :: Type\nlet zero :: Term Nat\naxiom A = zreo = zero");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(render(&errors[0], &source), "\
+error[NoBinding]: no binding found for `zreo`
+ --> <test>:3:11
+  |
+3 | axiom A = zreo = zero
+  |           ^^^^
+  = help: did you mean `zero`?
+
+");
+    }
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("zero", "zero"), 0);
+        assert_eq!(edit_distance("zero", ""), 4);
+        assert_eq!(edit_distance("zero", "hero"), 1);
+        assert_eq!(edit_distance("zero", "zeros"), 1);
+        assert_eq!(edit_distance("zero", "zro"), 1);
+        assert_eq!(edit_distance("zero", "zreo"), 1);
+        assert_eq!(edit_distance("ab", "ba"), 1);
+        assert_eq!(edit_distance("abcd", "badc"), 2);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("αβ", "βα"), 1);
+    }
+
+    fn to_json(error: &Error) -> Json {
+        let mut out = Vec::new();
+        error.to_json(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.ends_with("}\n"));
+        Json::parse(&text).unwrap()
+    }
+
+    #[test]
+    fn kinds_serialize_their_fields() {
+        let kinds = vec![
+            (FileReadFailure { filename: String::from("a \"b\".rl"), line: 2, rust_err: io::ErrorKind::NotFound },
+                "{\"filename\":\"a \\\"b\\\".rl\",\"line\":3,\"io_error\":\"NotFound\"}"),
+            (UnexpectedToken { found: String::from("\\"), expected: vec![String::from("\")\""), String::from("<identifier>")] },
+                "{\"found\":\"\\\\\",\"expected\":[\"\\\")\\\"\",\"<identifier>\"]}"),
+            (NoBinding { name: String::from("zéro") }, "{\"name\":\"zéro\"}"),
+            (ITypeMismatch { found: String::from("Bool"), expected: String::from("Nat -> Nat") },
+                "{\"found\":\"Bool\",\"expected\":\"Nat -> Nat\"}"),
+            (ImportCycle { cycle: String::from("m.a -> m.b -> m.a") }, "{\"cycle\":\"m.a -> m.b -> m.a\"}"),
+            (ProofMismatch, "{}"),
+        ];
+        for (kind, expected) in kinds.into_iter() {
+            assert_eq!(Json::parse(&kind.to_json()), Json::parse(expected));
+        }
+    }
+
+    #[test]
+    fn errors_serialize_locations_and_related_spans() {
+        let (_, errors) = check("let Nat :: Type\nlet Bool :: Type\nlet zero :: Term Nat\nlet t :: Term Bool\naxiom A = zero = t");
+        let error = errors.into_iter().next().unwrap().with_help(String::from("some help"));
+        let json = to_json(&error);
+        assert_eq!(json.get("code").and_then(Json::as_str), Some("ITypeMismatch"));
+        assert_eq!(json.get("message").and_then(Json::as_str), Some("found type `Bool`, expected type `Nat`"));
+        assert_eq!(json.get("file").and_then(Json::as_str), Some("<test>"));
+        let position: Vec<Option<usize>> = ["line", "column", "end_line", "end_column"].iter()
+            .map(|field| json.get(field).and_then(Json::as_usize)).collect();
+        assert_eq!(position, vec![Some(5), Some(18), Some(5), Some(19)]);
+        assert_eq!(json.get("fields"), Json::parse("{\"found\":\"Bool\",\"expected\":\"Nat\"}").ok().as_ref());
+        assert_eq!(json.get("related"), Json::parse("[{\"message\":\"this has type `Nat`\",\"file\":\"<test>\",\
+            \"line\":5,\"column\":11,\"end_line\":5,\"end_column\":15}]").ok().as_ref());
+        assert_eq!(json.get("notes"), Some(&Json::Array(Vec::new())));
+        assert_eq!(json.get("help"), Some(&Json::Array(vec![Json::String(String::from("some help"))])));
+    }
+}
@@ -0,0 +1,496 @@
+use error::{Error, SourceInfo};
+use lexer::{Lexer, Tok};
+use module::Import;
+use pretty::*;
+use syntax;
+use syntax::{Binder, Constructor, Expr, MetaTypeExpr, Schema, Statement, TypeExpr};
+
+
+const INDENT: usize = 4;
+
+
+enum Doc {
+    Text(String),
+    Line,
+    Concat(Vec<Doc>),
+    Nest(Box<Doc>),
+    Group(Box<Doc>),
+}
+
+fn text<S: Into<String>>(text: S) -> Doc {
+    Doc::Text(text.into())
+}
+
+fn concat(docs: Vec<Doc>) -> Doc {
+    Doc::Concat(docs)
+}
+
+fn nest(doc: Doc) -> Doc {
+    Doc::Nest(Box::new(doc))
+}
+
+fn group(doc: Doc) -> Doc {
+    Doc::Group(Box::new(doc))
+}
+
+fn fill(docs: Vec<Doc>) -> Doc {
+    let mut result = Vec::new();
+    for (i, doc) in docs.into_iter().enumerate() {
+        if i > 0 {
+            result.push(group(concat(vec![Doc::Line, doc])));
+        } else {
+            result.push(doc);
+        }
+    }
+    concat(result)
+}
+
+fn fits(mut remaining: isize, doc: &Doc, rest: &[(usize, bool, &Doc)]) -> bool {
+    let mut stack: Vec<(bool, &Doc)> = vec![(true, doc)];
+    let mut rest = rest.iter().rev();
+    while remaining >= 0 {
+        let (flat, doc) = match stack.pop() {
+            Some(item) => item,
+            None       => {
+                match rest.next() {
+                    Some(&(_, flat, doc)) => (flat, doc),
+                    None                  => return true,
+                }
+            },
+        };
+        match doc {
+            &Doc::Text(ref text)      => remaining -= text.chars().count() as isize,
+            &Doc::Line if flat        => remaining -= 1,
+            &Doc::Line                => return true,
+            &Doc::Concat(ref docs)    => stack.extend(docs.iter().rev().map(|doc| (flat, doc))),
+            &Doc::Nest(box ref doc)   |
+            &Doc::Group(box ref doc)  => stack.push((flat, doc)),
+        }
+    }
+    false
+}
+
+fn render(doc: &Doc, width: usize) -> String {
+    let mut out = String::new();
+    let mut column = 0;
+    let mut stack: Vec<(usize, bool, &Doc)> = vec![(0, false, doc)];
+    while let Some((indent, flat, doc)) = stack.pop() {
+        match doc {
+            &Doc::Text(ref text)     => {
+                out.push_str(text);
+                column += text.chars().count();
+            },
+            &Doc::Line if flat       => {
+                out.push(' ');
+                column += 1;
+            },
+            &Doc::Line               => {
+                out.push('\n');
+                out.extend((0..indent).map(|_| ' '));
+                column = indent;
+            },
+            &Doc::Concat(ref docs)   => stack.extend(docs.iter().rev().map(|doc| (indent, flat, doc))),
+            &Doc::Nest(box ref doc)  => stack.push((indent + INDENT, flat, doc)),
+            &Doc::Group(box ref doc) => {
+                let flat = flat || fits(width as isize - column as isize, doc, &stack);
+                stack.push((indent, flat, doc));
+            },
+        }
+    }
+    out
+}
+
+
+fn internal_type(itype: &TypeExpr, atomic: bool) -> String {
+    match itype {
+        &TypeExpr::Named(ref name)                      => name.text.clone(),
+        &TypeExpr::Func(box ref arg_type, box ref ret_type) => {
+            let text = format!("{} -> {}", internal_type(arg_type, true), internal_type(ret_type, false));
+            if atomic { format!("({})", text) } else { text }
+        },
+    }
+}
+
+fn meta_type(mtype: &MetaTypeExpr) -> String {
+    match mtype {
+        &MetaTypeExpr::Poly(ref var, box ref mtype) => format!("forall {}. {}", var.text, meta_type(mtype)),
+        &MetaTypeExpr::Type                         => String::from("Type"),
+        &MetaTypeExpr::Term(ref itype)              => format!("Term {}", internal_type(itype, false)),
+        &MetaTypeExpr::Formula(ref itypes)          => {
+            itypes.iter().fold(String::from("Formula"), |acc, itype| format!("{} {}", acc, internal_type(itype, true)))
+        },
+    }
+}
+
+fn binder(keyword: &str, binder: &Binder) -> Doc {
+    match binder.itype {
+        Some(ref itype) => text(format!("{} {}: {}.", keyword, binder.name.text, internal_type(itype, false))),
+        None            => text(format!("{} {}.", keyword, binder.name.text)),
+    }
+}
+
+fn binders(heads: Vec<Doc>, body: Doc) -> Doc {
+    if heads.is_empty() {
+        return body;
+    }
+    group(concat(vec![fill(heads), nest(concat(vec![Doc::Line, body]))]))
+}
+
+fn chain(parts: Vec<Doc>, op: &str) -> Doc {
+    let mut docs = Vec::new();
+    for (i, part) in parts.into_iter().enumerate() {
+        if i > 0 {
+            docs.push(text(format!(" {}", op)));
+            docs.push(Doc::Line);
+        }
+        docs.push(part);
+    }
+    group(concat(docs))
+}
+
+fn quantifiers<'a>(form: &'a Expr, heads: &mut Vec<Doc>) -> &'a Expr {
+    match form {
+        &Expr::UniversalQ(_, ref var, box ref form)   => {
+            heads.push(binder("forall", var));
+            quantifiers(form, heads)
+        },
+        &Expr::ExistentialQ(_, ref var, box ref form) => {
+            heads.push(binder("exists", var));
+            quantifiers(form, heads)
+        },
+        _                                             => form,
+    }
+}
+
+fn level(expr: &Expr) -> usize {
+    match expr {
+        &Expr::Symbol(_) | &Expr::Numeral(..) | &Expr::False(_) => ATOM,
+        &Expr::Application(..)                                    => APPLICATION,
+        &Expr::Equality(..)                                       => EQUALITY,
+        &Expr::Negation(..)                                       => NEGATION,
+        &Expr::Conjunction(..)                                    => CONJUNCTION,
+        &Expr::Disjunction(..)                                    => DISJUNCTION,
+        &Expr::Implication(..)                                    => IMPLICATION,
+        &Expr::Biconditional(..)                                  => BICONDITIONAL,
+        &Expr::UniversalQ(..) | &Expr::ExistentialQ(..)          => QUANTIFIER,
+        &Expr::Abstraction(..)                                    => ABSTRACTION,
+    }
+}
+
+fn expr(form: &Expr, context: usize) -> Doc {
+    if level(form) < context {
+        return group(concat(vec![text("("), nest(expr(form, ABSTRACTION)), text(")")]));
+    }
+    match form {
+        &Expr::Symbol(ref name)                   => text(name.text.clone()),
+        &Expr::Numeral(_, ref num)                => text(num.clone()),
+        &Expr::False(_)                           => text("false"),
+        &Expr::Application(..)                    => {
+            let mut args = Vec::new();
+            let mut head = form;
+            while let &Expr::Application(box ref func, box ref arg) = head {
+                args.push(arg);
+                head = func;
+            }
+            let args = args.into_iter().rev().map(|arg| concat(vec![Doc::Line, expr(arg, ATOM)])).collect();
+            group(concat(vec![expr(head, APPLICATION), nest(concat(args))]))
+        },
+        &Expr::Equality(box ref lhs, box ref rhs) => {
+            group(concat(vec![expr(lhs, APPLICATION), text(" ="), nest(concat(vec![Doc::Line, expr(rhs, APPLICATION)]))]))
+        },
+        &Expr::Negation(_, box ref form)          => concat(vec![text("not "), expr(form, NEGATION)]),
+        &Expr::Conjunction(..)                    => {
+            let mut parts = Vec::new();
+            let mut lhs = form;
+            while let &Expr::Conjunction(box ref left, box ref right) = lhs {
+                parts.push(expr(right, NEGATION));
+                lhs = left;
+            }
+            parts.push(expr(lhs, CONJUNCTION));
+            parts.reverse();
+            chain(parts, "and")
+        },
+        &Expr::Disjunction(..)                    => {
+            let mut parts = Vec::new();
+            let mut lhs = form;
+            while let &Expr::Disjunction(box ref left, box ref right) = lhs {
+                parts.push(expr(right, CONJUNCTION));
+                lhs = left;
+            }
+            parts.push(expr(lhs, DISJUNCTION));
+            parts.reverse();
+            chain(parts, "or")
+        },
+        &Expr::Implication(..)                    => {
+            let consequent = if context == ANTECEDENT { ANTECEDENT } else { CONSEQUENT };
+            let mut parts = Vec::new();
+            let mut rhs = form;
+            while let &Expr::Implication(box ref left, box ref right) = rhs {
+                parts.push(expr(left, DISJUNCTION));
+                rhs = right;
+            }
+            parts.push(expr(rhs, consequent));
+            chain(parts, "->")
+        },
+        &Expr::Biconditional(box ref lhs, box ref rhs) => {
+            chain(vec![expr(lhs, ANTECEDENT), expr(rhs, BICONSEQUENT)], "<->")
+        },
+        &Expr::UniversalQ(..) | &Expr::ExistentialQ(..) => {
+            let mut heads = Vec::new();
+            let body = quantifiers(form, &mut heads);
+            binders(heads, expr(body, FORMULA))
+        },
+        &Expr::Abstraction(..)                    => {
+            let mut heads = Vec::new();
+            let mut body = form;
+            while let &Expr::Abstraction(_, ref var, box ref form) = body {
+                heads.push(binder("lambda", var));
+                body = form;
+            }
+            binders(heads, expr(body, ABSTRACTION))
+        },
+    }
+}
+
+fn schema(sch: &Schema) -> Doc {
+    let mut heads = Vec::new();
+    let mut body = sch;
+    loop {
+        body = match body {
+            &Schema::Schema(_, ref var, ref mtype, box ref sch) => {
+                heads.push(text(format!("schema {} :: {}.", var.text, meta_type(mtype))));
+                sch
+            },
+            &Schema::Poly(_, ref var, box ref sch)              => {
+                heads.push(text(format!("forall {} :: Type.", var.text)));
+                sch
+            },
+            _                                                   => break,
+        };
+    }
+    let form = match body {
+        &Schema::Formula(ref form)               |
+        &Schema::Proved(ref form, _)             |
+        &Schema::Block(_, ref form, _, _, _)     => form,
+        &Schema::Schema(..) | &Schema::Poly(..) => unreachable!(),
+    };
+    let body = quantifiers(form, &mut heads);
+    binders(heads, expr(body, FORMULA))
+}
+
+fn constructor(ctor: &Constructor) -> Doc {
+    text(ctor.args.iter().fold(ctor.name.text.clone(), |acc, itype| format!("{} {}", acc, internal_type(itype, true))))
+}
+
+fn import(import: &Import) -> Doc {
+    let mut result = format!("{}import {}", if import.open { "open " } else { "" }, import.path);
+    if let Some(ref alias) = import.alias {
+        result = format!("{} as {}", result, alias);
+    }
+    if let Some(ref names) = import.names {
+        result = format!("{} ({})", result, names.join(", "));
+    }
+    text(result)
+}
+
+fn statement(stmt: &Statement) -> Doc {
+    match stmt {
+        &Statement::Let(_, ref var, ref mtype)     => {
+            group(concat(vec![text(format!("let {} ::", var.text)), nest(concat(vec![Doc::Line, text(meta_type(mtype))]))]))
+        },
+        &Statement::Data(_, ref var, ref ctors)    => {
+            let rest = ctors[1..].iter().map(|ctor| concat(vec![Doc::Line, text("| "), constructor(ctor)])).collect();
+            group(concat(vec![text(format!("data {} = ", var.text)), constructor(&ctors[0]), nest(concat(rest))]))
+        },
+        &Statement::Axiom(_, ref var, ref sch)     => {
+            group(concat(vec![text(format!("axiom {} =", var.text)), nest(concat(vec![Doc::Line, schema(sch)]))]))
+        },
+        &Statement::Theorem(_, ref var, ref sch)   => {
+            group(concat(vec![text(format!("theorem {} :", var.text)), nest(concat(vec![Doc::Line, schema(sch), text(" :=")]))]))
+        },
+        &Statement::Proof(_, ref var, ref sch)     => {
+            group(concat(vec![text(format!("proof {} :", var.text)), nest(concat(vec![Doc::Line, schema(sch), text(" {")]))]))
+        },
+        &Statement::Import(_, ref imp)             => import(imp),
+        &Statement::Dropped(..) |
+        &Statement::Error(_)                       => unreachable!(),
+    }
+}
+
+
+fn is_head(tok: &Tok, prev: Option<&Tok>) -> bool {
+    match *tok {
+        Tok::Let | Tok::Data | Tok::Axiom | Tok::Theorem | Tok::Proof | Tok::Open => true,
+        Tok::Import                                                             => prev != Some(&Tok::Open),
+        _                                                                       => false,
+    }
+}
+
+fn last_line_width(text: &str) -> usize {
+    text.rsplit('\n').next().unwrap().chars().count()
+}
+
+fn layout<F: Fn(usize, usize) -> bool>(stmt: &Statement, text: &str, tokens: &[(usize, Tok, usize)], has_comment: &F, width: usize) -> String {
+    let (start, end) = (tokens[0].0, tokens[tokens.len() - 1].2);
+    let cut = match stmt {
+        &Statement::Theorem(..) => tokens.iter().find(|&&(_, ref tok, _)| *tok == Tok::ColonEq).map(|&(_, _, end)| end),
+        &Statement::Proof(..)   => tokens.iter().find(|&&(_, ref tok, _)| *tok == Tok::LBrace).map(|&(_, _, end)| end),
+        _                       => None,
+    }.unwrap_or(end);
+    if has_comment(start, cut) {
+        return String::from(&text[start..end]);
+    }
+    let head = render(&statement(stmt), width);
+    let body = &text[cut..end];
+    if body.contains('\n') || body.is_empty() {
+        format!("{}{}", head, body)
+    } else if last_line_width(&head) + body.chars().count() <= width {
+        format!("{} {}", head, body.trim())
+    } else {
+        format!("{}\n{}{}", head, " ".repeat(INDENT), body.trim())
+    }
+}
+
+pub fn format_source(source: &SourceInfo, text: &str, width: usize) -> Result<String, Vec<Error>> {
+    let (errors, stmts): (Vec<Statement>, Vec<Statement>) = syntax::parse_program(source, text).into_iter().partition(|stmt| {
+        if let &Statement::Error(_) = stmt { true } else { false }
+    });
+    if !errors.is_empty() {
+        return Err(errors.into_iter().map(|stmt| if let Statement::Error(error) = stmt { error } else { unreachable!() }).collect());
+    }
+    let mut lexer = Lexer::new(text);
+    let tokens: Vec<(usize, Tok, usize)> = lexer.by_ref().filter_map(|tok| tok.ok()).filter(|&(_, ref tok, _)| *tok != Tok::Eof).collect();
+    let comments: Vec<(usize, usize)> = lexer.comments().iter().map(|comment| (comment.start, comment.end)).collect();
+    let has_comment = |start: usize, end: usize| comments.iter().any(|&(pos, _)| start <= pos && pos < end);
+
+    let mut heads: Vec<usize> = Vec::new();
+    for (i, &(_, ref tok, _)) in tokens.iter().enumerate() {
+        if is_head(tok, if i > 0 { Some(&tokens[i - 1].1) } else { None }) {
+            heads.push(i);
+        }
+    }
+    heads.push(tokens.len());
+
+    let mut items: Vec<(usize, usize, String)> = stmts.iter().zip(heads.windows(2)).map(|(stmt, range)| {
+        let tokens = &tokens[range[0]..range[1]];
+        (tokens[0].0, tokens[tokens.len() - 1].2, layout(stmt, text, tokens, &has_comment, width))
+    }).collect();
+    items.extend(comments.iter().map(|&(start, end)| (start, end, String::from(text[start..end].trim_end()))));
+    items.sort_by_key(|&(start, _, _)| start);
+
+    let mut out = String::new();
+    let mut prev: Option<usize> = None;
+    for (start, end, item) in items {
+        if let Some(prev_end) = prev {
+            if prev_end > start {
+                continue;
+            }
+            match text[prev_end..start].matches('\n').count() {
+                0 => out.push(' '),
+                1 => out.push('\n'),
+                _ => out.push_str("\n\n"),
+            }
+        }
+        out.push_str(&item);
+        prev = Some(end);
+    }
+    Ok(out.lines().map(|line| format!("{}\n", line.trim_end())).collect())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ast::{Declaration, LocalBindings};
+    use elaborate::Elaborator;
+    use error::ErrorCollector;
+    use state::{Bindings, RLangRepr};
+
+    const SAMPLE: &'static str = "\
+-- numbers
+let  Nat::Type
+let zero :: Term  Nat
+
+
+let le :: Formula Nat Nat  -- order
+axiom A=∀x:Nat. ¬(x=zero) ∧ ((le x zero))
+theorem T : ∀x. le x x → le x x :=
+  given x.   assume h : le x x { h }
+axiom B = forall x: Nat. #| inside |# le x x
+axiom Long = forall x: Nat. forall y: Nat. le x y and le y x and le x zero and le zero y -> x = y or le x zero
+";
+
+    const PRELUDE: &'static [&'static str] = &[
+        include_str!("../prelude/logic.rl"),
+        include_str!("../prelude/eq.rl"),
+        include_str!("../prelude/nat.rl"),
+        include_str!("../prelude/order.rl"),
+        include_str!("../prelude/set.rl"),
+    ];
+
+    fn format(text: &str, width: usize) -> String {
+        format_source(&SourceInfo::from_text("<test>", text), text, width).unwrap()
+    }
+
+    fn print(text: &str) -> Vec<String> {
+        let source = SourceInfo::from_text("<test>", text);
+        let errors = ErrorCollector::new();
+        let mut locals = LocalBindings::new();
+        let mut globals = Bindings::new();
+        let stmts = syntax::parse_program(&source, text);
+        let decls: Vec<Declaration> = Elaborator::new(&mut locals, &mut globals, &source, &errors).program(stmts);
+        assert_eq!(errors.len(), 0, "errors in {:?}", text);
+        decls.iter().map(|decl| decl.repr(&globals)).collect()
+    }
+
+    #[test]
+    fn layout_at_default_width() {
+        assert_eq!(format(SAMPLE, 80), "\
+-- numbers
+let Nat :: Type
+let zero :: Term Nat
+
+let le :: Formula Nat Nat -- order
+axiom A = forall x: Nat. not x = zero and le x zero
+theorem T : forall x. le x x -> le x x :=
+  given x.   assume h : le x x { h }
+axiom B = forall x: Nat. #| inside |# le x x
+axiom Long =
+    forall x: Nat. forall y: Nat.
+        le x y and le y x and le x zero and le zero y -> x = y or le x zero
+");
+    }
+
+    #[test]
+    fn lines_fit_in_width() {
+        let formatted = format(SAMPLE, 40);
+        for line in formatted.lines().filter(|line| !line.contains("#|") && !line.starts_with("  given")) {
+            assert!(line.chars().count() <= 40, "line too long: {:?}", line);
+        }
+        assert!(formatted.contains("axiom Long =\n    forall x: Nat. forall y: Nat.\n        le x y and\n"));
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        for &text in PRELUDE.iter().chain([SAMPLE].iter()) {
+            for &width in [20, 40, 80, 120].iter() {
+                let once = format(text, width);
+                assert_eq!(format(&once, width), once);
+            }
+        }
+    }
+
+    #[test]
+    fn formatting_preserves_meaning() {
+        let expected = print(SAMPLE);
+        for &width in [20, 40, 80].iter() {
+            assert_eq!(print(&format(SAMPLE, width)), expected);
+        }
+    }
+
+    #[test]
+    fn parse_errors_are_reported() {
+        let text = "let Nat :: Type\naxiom A = forall x: Nat.\n";
+        assert!(format_source(&SourceInfo::from_text("<test>", text), text, 80).is_err());
+    }
+}
@@ -0,0 +1,255 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use error::{json_list, json_string};
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = Parser { chars: text.chars().peekable() };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        match parser.chars.next() {
+            Some(c) => Err(format!("unexpected `{}` after the value", c)),
+            None    => Ok(value),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            &Json::Object(ref fields) => fields.iter().find(|&&(ref name, _)| name == key).map(|&(_, ref value)| value),
+            _                         => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            &Json::String(ref text) => Some(text),
+            _                       => None,
+        }
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            &Json::Number(num) if num >= 0.0 && num.fract() == 0.0 => Some(num as usize),
+            _                                                      => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            &Json::Array(ref items) => Some(items),
+            _                       => None,
+        }
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Json::Null               => write!(f, "null"),
+            &Json::Bool(value)        => write!(f, "{}", value),
+            &Json::Number(num)        => write!(f, "{}", num),
+            &Json::String(ref text)   => write!(f, "{}", json_string(text)),
+            &Json::Array(ref items)   => write!(f, "{}", json_list(items.iter().map(|item| item.to_string()))),
+            &Json::Object(ref fields) => {
+                let fields = fields.iter().map(|&(ref name, ref value)| format!("{}:{}", json_string(name), value));
+                write!(f, "{{{}}}", fields.collect::<Vec<String>>().join(","))
+            },
+        }
+    }
+}
+
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.peek().is_some_and(|c| c.is_whitespace()) {
+            self.chars.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.chars.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c)                  => Err(format!("expected `{}`, found `{}`", expected, c)),
+            None                     => Err(format!("expected `{}`, found the end of input", expected)),
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.chars.peek().cloned() {
+            Some('n')                                  => self.literal("null", Json::Null),
+            Some('t')                                  => self.literal("true", Json::Bool(true)),
+            Some('f')                                  => self.literal("false", Json::Bool(false)),
+            Some('"')                                  => self.string().map(Json::String),
+            Some('[')                                  => self.array(),
+            Some('{')                                  => self.object(),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some(c)                                    => Err(format!("unexpected `{}`", c)),
+            None                                       => Err(String::from("unexpected end of input")),
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        for expected in word.chars() {
+            if self.chars.next() != Some(expected) {
+                return Err(format!("invalid literal, expected `{}`", word));
+            }
+        }
+        Ok(value)
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let mut text = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !(c.is_ascii_digit() || c == '-' || c == '+' || c == '.' || c == 'e' || c == 'E') {
+                break;
+            }
+            text.push(c);
+            self.chars.next();
+        }
+        text.parse().map(Json::Number).map_err(|_| format!("invalid number `{}`", text))
+    }
+
+    fn hex(&mut self) -> Result<u32, String> {
+        let digits: String = self.chars.by_ref().take(4).collect();
+        u32::from_str_radix(&digits, 16).map_err(|_| format!("invalid escape `\\u{}`", digits))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut text = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"')  => return Ok(text),
+                Some('\\') => {
+                    let c = match self.chars.next() {
+                        Some('"')  => '"',
+                        Some('\\') => '\\',
+                        Some('/')  => '/',
+                        Some('b')  => '\u{8}',
+                        Some('f')  => '\u{c}',
+                        Some('n')  => '\n',
+                        Some('r')  => '\r',
+                        Some('t')  => '\t',
+                        Some('u')  => {
+                            let mut code = self.hex()?;
+                            if (0xD800..0xDC00).contains(&code) {
+                                self.expect('\\')?;
+                                self.expect('u')?;
+                                code = 0x10000 + ((code - 0xD800) << 10) + (self.hex()? & 0x3FF);
+                            }
+                            char::from_u32(code).unwrap_or('\u{FFFD}')
+                        },
+                        Some(c)    => return Err(format!("invalid escape `\\{}`", c)),
+                        None       => return Err(String::from("unterminated string")),
+                    };
+                    text.push(c);
+                },
+                Some(c)    => text.push(c),
+                None       => return Err(String::from("unterminated string")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&']') {
+            self.chars.next();
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => (),
+                Some(']') => return Ok(Json::Array(items)),
+                _         => return Err(String::from("expected `,` or `]` in array")),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.expect('{')?;
+        let mut fields = Vec::new();
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&'}') {
+            self.chars.next();
+            return Ok(Json::Object(fields));
+        }
+        loop {
+            let name = self.string()?;
+            self.expect(':')?;
+            fields.push((name, self.value()?));
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => (),
+                Some('}') => return Ok(Json::Object(fields)),
+                _         => return Err(String::from("expected `,` or `}` in object")),
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_values() {
+        let value = Json::parse(" {\"a\": [1, -2.5e1, true, false, null], \"b\": {}, \"c\": [] } ").unwrap();
+        assert_eq!(value, Json::Object(vec![
+            (String::from("a"), Json::Array(vec![Json::Number(1.0), Json::Number(-25.0), Json::Bool(true), Json::Bool(false), Json::Null])),
+            (String::from("b"), Json::Object(Vec::new())),
+            (String::from("c"), Json::Array(Vec::new())),
+        ]));
+        assert_eq!(value.get("a").and_then(Json::as_array).map(|items| items.len()), Some(5));
+        assert_eq!(value.get("a").and_then(|a| a.as_array()).and_then(|items| items[0].as_usize()), Some(1));
+        assert_eq!(value.get("missing"), None);
+        assert_eq!(Json::parse(&value.to_string()), Ok(value));
+    }
+
+    #[test]
+    fn parses_escapes() {
+        assert_eq!(Json::parse("\"a\\\"b\\\\c\\/d\\n\\t\"").unwrap().as_str(), Some("a\"b\\c/d\n\t"));
+        assert_eq!(Json::parse("\"caf\\u00e9\"").unwrap().as_str(), Some("café"));
+        assert_eq!(Json::parse("\"\\ud83d\\ude00!\"").unwrap().as_str(), Some("\u{1F600}!"));
+        assert_eq!(Json::parse("\"\u{1F600}\"").unwrap().as_str(), Some("\u{1F600}"));
+    }
+
+    #[test]
+    fn reports_errors() {
+        assert!(Json::parse("").is_err());
+        assert!(Json::parse("[1, 2").is_err());
+        assert!(Json::parse("[1 2]").is_err());
+        assert!(Json::parse("{\"a\" 1}").is_err());
+        assert!(Json::parse("{a: 1}").is_err());
+        assert!(Json::parse("tru").is_err());
+        assert!(Json::parse("\"abc").is_err());
+        assert!(Json::parse("\"\\x\"").is_err());
+        assert!(Json::parse("\"\\u12\"").is_err());
+        assert!(Json::parse("\"\\ud83d\"").is_err());
+        assert!(Json::parse("1-").is_err());
+        assert_eq!(Json::parse("1 2"), Err(String::from("unexpected `2` after the value")));
+    }
+}
@@ -0,0 +1,506 @@
+#![allow(dead_code)]
+
+use std::fmt;
+use std::mem;
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tok<'input> {
+    Ident(&'input str),
+    QualIdent(&'input str),
+    Number(&'input str),
+    Eof,
+    Let,
+    Data,
+    Axiom,
+    Theorem,
+    Proof,
+    Import,
+    Open,
+    As,
+    Schema,
+    Forall,
+    Exists,
+    Lambda,
+    Type,
+    Term,
+    Formula,
+    Have,
+    Show,
+    Given,
+    Contrapositive,
+    Obtain,
+    From,
+    Assume,
+    In,
+    False,
+    And,
+    Or,
+    Not,
+    AndIntro,
+    AndElimLeft,
+    AndElimRight,
+    OrIntroLeft,
+    OrIntroRight,
+    OrElim,
+    NotIntro,
+    NotElim,
+    IffIntro,
+    IffElimLeft,
+    IffElimRight,
+    FalseElim,
+    Refl,
+    Symm,
+    Trans,
+    Congr,
+    Leibniz,
+    Rewrite,
+    ExistsIntro,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    LBrace,
+    RBrace,
+    Comma,
+    Dot,
+    Colon,
+    ColonColon,
+    ColonEq,
+    Semicolon,
+    Bar,
+    Dollar,
+    Equals,
+    Arrow,
+    Iff,
+}
+
+const TOKENS: &'static [(&'static str, Tok<'static>)] = &[
+    ("let",            Tok::Let),
+    ("data",           Tok::Data),
+    ("axiom",          Tok::Axiom),
+    ("theorem",        Tok::Theorem),
+    ("proof",          Tok::Proof),
+    ("import",         Tok::Import),
+    ("open",           Tok::Open),
+    ("as",             Tok::As),
+    ("schema",         Tok::Schema),
+    ("forall",         Tok::Forall),
+    ("exists",         Tok::Exists),
+    ("lambda",         Tok::Lambda),
+    ("Type",           Tok::Type),
+    ("Term",           Tok::Term),
+    ("Formula",        Tok::Formula),
+    ("have",           Tok::Have),
+    ("show",           Tok::Show),
+    ("given",          Tok::Given),
+    ("contrapositive", Tok::Contrapositive),
+    ("obtain",         Tok::Obtain),
+    ("from",           Tok::From),
+    ("assume",         Tok::Assume),
+    ("in",             Tok::In),
+    ("false",          Tok::False),
+    ("and",            Tok::And),
+    ("or",             Tok::Or),
+    ("not",            Tok::Not),
+    ("and_intro",      Tok::AndIntro),
+    ("and_elim_left",  Tok::AndElimLeft),
+    ("and_elim_right", Tok::AndElimRight),
+    ("or_intro_left",  Tok::OrIntroLeft),
+    ("or_intro_right", Tok::OrIntroRight),
+    ("or_elim",        Tok::OrElim),
+    ("not_intro",      Tok::NotIntro),
+    ("not_elim",       Tok::NotElim),
+    ("iff_intro",      Tok::IffIntro),
+    ("iff_elim_left",  Tok::IffElimLeft),
+    ("iff_elim_right", Tok::IffElimRight),
+    ("false_elim",     Tok::FalseElim),
+    ("refl",           Tok::Refl),
+    ("symm",           Tok::Symm),
+    ("trans",          Tok::Trans),
+    ("congr",          Tok::Congr),
+    ("leibniz",        Tok::Leibniz),
+    ("rewrite",        Tok::Rewrite),
+    ("exists_intro",   Tok::ExistsIntro),
+    ("(",              Tok::LParen),
+    (")",              Tok::RParen),
+    ("[",              Tok::LBracket),
+    ("]",              Tok::RBracket),
+    ("{",              Tok::LBrace),
+    ("}",              Tok::RBrace),
+    (",",              Tok::Comma),
+    (".",              Tok::Dot),
+    (":",              Tok::Colon),
+    ("::",             Tok::ColonColon),
+    (":=",             Tok::ColonEq),
+    (";",              Tok::Semicolon),
+    ("|",              Tok::Bar),
+    ("$",              Tok::Dollar),
+    ("=",              Tok::Equals),
+    ("->",             Tok::Arrow),
+    ("<->",            Tok::Iff),
+];
+
+const ALIASES: &'static [(char, Tok<'static>)] = &[
+    ('∀', Tok::Forall),
+    ('∃', Tok::Exists),
+    ('→', Tok::Arrow),
+    ('↔', Tok::Iff),
+    ('¬', Tok::Not),
+    ('∧', Tok::And),
+    ('∨', Tok::Or),
+    ('⊥', Tok::False),
+];
+
+fn lookup(text: &str) -> Option<Tok<'static>> {
+    TOKENS.iter().find(|&&(name, _)| name == text).map(|&(_, tok)| tok)
+}
+
+fn alias(c: char) -> Option<Tok<'static>> {
+    ALIASES.iter().find(|&&(name, _)| name == c).map(|&(_, tok)| tok)
+}
+
+impl<'input> fmt::Display for Tok<'input> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Tok::Ident(text) | Tok::QualIdent(text) | Tok::Number(text) => write!(f, "{}", text),
+            Tok::Eof                                                    => write!(f, "<end of file>"),
+            tok => write!(f, "{}", TOKENS.iter().find(|&&(_, other)| other == tok).unwrap().0),
+        }
+    }
+}
+
+
+#[derive(Clone, Debug)]
+pub enum LexicalError {
+    InvalidCharacter(usize, char),
+    UnterminatedComment(usize),
+}
+
+
+#[derive(Clone, Debug)]
+pub struct Comment<'input> {
+    pub start: usize,
+    pub end: usize,
+    pub text: &'input str,
+}
+
+
+pub struct Lexer<'input> {
+    text: &'input str,
+    pos: usize,
+    finished: bool,
+    // inside the header of a binder, up to the dot that ends it
+    binder: bool,
+    depth: usize,
+    comments: Vec<Comment<'input>>,
+    errors: Vec<LexicalError>,
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_ident_continue(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '\''
+}
+
+fn is_symbol(c: char) -> bool {
+    match c {
+        '!' | '#' | '%' | '&' | '*' | '+' | '-' | '/' | '<' | '=' | '>' | '?' | '@' | '^' | '~' | '\\' => true,
+        c => !c.is_ascii() && !c.is_alphanumeric() && !c.is_whitespace() && alias(c).is_none(),
+    }
+}
+
+impl<'input> Lexer<'input> {
+    pub fn new(text: &'input str) -> Lexer<'input> {
+        Lexer {
+            text: text,
+            pos: 0,
+            finished: false,
+            binder: false,
+            depth: 0,
+            comments: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn comments(&self) -> &[Comment<'input>] {
+        &self.comments
+    }
+
+    pub fn take_errors(&mut self) -> Vec<LexicalError> {
+        mem::replace(&mut self.errors, Vec::new())
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn peek_second(&self) -> Option<char> {
+        self.text[self.pos..].chars().nth(1)
+    }
+
+    fn skip_while<F: Fn(char) -> bool>(&mut self, pred: F) {
+        while let Some(c) = self.peek() {
+            if !pred(c) {
+                break;
+            }
+            self.pos += c.len_utf8();
+        }
+    }
+
+    fn at_comment(&self) -> bool {
+        let rest = &self.text[self.pos..];
+        rest.starts_with("--") || rest.starts_with("#|")
+    }
+
+    // `;` at the start of a line begins a comment, as in the original syntax; elsewhere it separates proof steps
+    fn at_line_start(&self, start: usize) -> bool {
+        self.text[..start].rsplit('\n').next().unwrap().trim().is_empty()
+    }
+
+    fn comment(&mut self, start: usize) {
+        self.comments.push(Comment {
+            start: start,
+            end: self.pos,
+            text: &self.text[start..self.pos],
+        });
+    }
+
+    fn block_comment(&mut self, start: usize) {
+        let mut depth = 0;
+        while let Some(c) = self.peek() {
+            let rest = &self.text[self.pos..];
+            if rest.starts_with("#|") {
+                depth += 1;
+                self.pos += 2;
+            } else if rest.starts_with("|#") {
+                depth -= 1;
+                self.pos += 2;
+                if depth == 0 {
+                    self.comment(start);
+                    return;
+                }
+            } else {
+                self.pos += c.len_utf8();
+            }
+        }
+        self.comment(start);
+        self.errors.push(LexicalError::UnterminatedComment(start));
+    }
+
+    // in a binder header, a dotted name is qualified only if the dot ending the header follows it,
+    // so `forall x: arith.Nat. P x` names a qualified type while `forall x: Nat.P x` binds x in `P x`
+    fn name(&mut self, start: usize) -> Tok<'input> {
+        self.skip_while(is_ident_continue);
+        let end = self.pos;
+        let mut qualified = false;
+        while self.peek() == Some('.') && self.peek_second().map_or(false, is_ident_start) {
+            self.pos += 1;
+            self.skip_while(is_ident_continue);
+            qualified = true;
+        }
+        if qualified && self.binder && self.depth == 0 && !self.at_header_end() {
+            self.pos = end;
+            qualified = false;
+        }
+        let text = &self.text[start..self.pos];
+        if qualified {
+            Tok::QualIdent(text)
+        } else {
+            lookup(text).unwrap_or(Tok::Ident(text))
+        }
+    }
+
+    fn at_header_end(&self) -> bool {
+        self.peek() == Some('.') && self.peek_second().map_or(true, |c| c.is_whitespace() || "([{".contains(c))
+    }
+
+    fn token(&mut self, start: usize, c: char) -> Option<Tok<'input>> {
+        if is_ident_start(c) {
+            return Some(self.name(start));
+        }
+        if c.is_ascii_digit() {
+            self.skip_while(|c| c.is_ascii_digit());
+            return Some(Tok::Number(&self.text[start..self.pos]));
+        }
+        if let Some(tok) = alias(c) {
+            self.pos += c.len_utf8();
+            return Some(tok);
+        }
+        if is_symbol(c) {
+            while self.peek().is_some_and(is_symbol) && !self.at_comment() {
+                self.pos += self.peek().unwrap().len_utf8();
+            }
+            let text = &self.text[start..self.pos];
+            return Some(lookup(text).unwrap_or(Tok::Ident(text)));
+        }
+        self.pos += c.len_utf8();
+        if c == ':' && (self.peek() == Some(':') || self.peek() == Some('=')) {
+            self.pos += 1;
+        }
+        lookup(&self.text[start..self.pos])
+    }
+
+    fn track(&mut self, tok: Tok<'input>) {
+        match tok {
+            Tok::Forall | Tok::Exists | Tok::Lambda | Tok::Schema | Tok::Given if !self.binder => {
+                self.binder = true;
+                self.depth = 0;
+            },
+            Tok::LParen | Tok::LBracket | Tok::LBrace if self.binder                          => self.depth += 1,
+            Tok::RParen | Tok::RBracket | Tok::RBrace if self.binder && self.depth > 0        => self.depth -= 1,
+            Tok::Dot if self.binder && self.depth == 0                                         => self.binder = false,
+            _                                                                                  => (),
+        }
+    }
+}
+
+impl<'input> Iterator for Lexer<'input> {
+    type Item = Result<(usize, Tok<'input>, usize), LexicalError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.skip_while(char::is_whitespace);
+            let start = self.pos;
+            let c = match self.peek() {
+                Some(c)                 => c,
+                None if self.finished   => return None,
+                None                    => {
+                    self.finished = true;
+                    return Some(Ok((start, Tok::Eof, start)));
+                },
+            };
+            if self.text[start..].starts_with("--") || (c == ';' && self.at_line_start(start)) {
+                self.skip_while(|c| c != '\n' && c != '\r');
+                self.comment(start);
+                continue;
+            }
+            if self.text[start..].starts_with("#|") {
+                self.block_comment(start);
+                continue;
+            }
+            match self.token(start, c) {
+                Some(tok) => {
+                    self.track(tok);
+                    return Some(Ok((start, tok, self.pos)));
+                },
+                None      => self.errors.push(LexicalError::InvalidCharacter(start, c)),
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(text: &str) -> Vec<Tok<'_>> {
+        let mut lexer = Lexer::new(text);
+        let tokens = lexer.by_ref().map(|item| item.unwrap().1).collect();
+        assert!(lexer.take_errors().is_empty(), "unexpected lexical errors in {:?}", text);
+        tokens
+    }
+
+    fn comments(text: &str) -> Vec<&str> {
+        let mut lexer = Lexer::new(text);
+        lexer.by_ref().count();
+        lexer.comments().iter().map(|comment| comment.text).collect()
+    }
+
+    #[test]
+    fn keywords_and_names() {
+        assert_eq!(tokens("let x' :: Term Nat"), vec![Tok::Let, Tok::Ident("x'"), Tok::ColonColon, Tok::Term, Tok::Ident("Nat"), Tok::Eof]);
+        assert_eq!(tokens("arith.zero and_intro letter"), vec![Tok::QualIdent("arith.zero"), Tok::AndIntro, Tok::Ident("letter"), Tok::Eof]);
+        assert_eq!(tokens("forall x. x"), vec![Tok::Forall, Tok::Ident("x"), Tok::Dot, Tok::Ident("x"), Tok::Eof]);
+    }
+
+    #[test]
+    fn binder_dot_ends_the_header() {
+        assert_eq!(tokens("forall x: Nat.P x"), vec![
+            Tok::Forall, Tok::Ident("x"), Tok::Colon, Tok::Ident("Nat"), Tok::Dot, Tok::Ident("P"), Tok::Ident("x"), Tok::Eof,
+        ]);
+        assert_eq!(tokens("forall x.P x"), vec![Tok::Forall, Tok::Ident("x"), Tok::Dot, Tok::Ident("P"), Tok::Ident("x"), Tok::Eof]);
+        assert_eq!(tokens("exists x: (arith.Nat).arith.P x"), vec![
+            Tok::Exists, Tok::Ident("x"), Tok::Colon, Tok::LParen, Tok::QualIdent("arith.Nat"), Tok::RParen, Tok::Dot,
+            Tok::QualIdent("arith.P"), Tok::Ident("x"), Tok::Eof,
+        ]);
+        assert_eq!(tokens("forall x: Arith.Nat. P x"), vec![
+            Tok::Forall, Tok::Ident("x"), Tok::Colon, Tok::QualIdent("Arith.Nat"), Tok::Dot, Tok::Ident("P"), Tok::Ident("x"), Tok::Eof,
+        ]);
+        assert_eq!(tokens("schema Phi :: Formula Arith.Nat.(Phi Arith.zero)"), vec![
+            Tok::Schema, Tok::Ident("Phi"), Tok::ColonColon, Tok::Formula, Tok::QualIdent("Arith.Nat"), Tok::Dot,
+            Tok::LParen, Tok::Ident("Phi"), Tok::QualIdent("Arith.zero"), Tok::RParen, Tok::Eof,
+        ]);
+    }
+
+    #[test]
+    fn operators_and_numbers() {
+        assert_eq!(tokens("a <= 12 -> b <-> c := d"), vec![
+            Tok::Ident("a"), Tok::Ident("<="), Tok::Number("12"), Tok::Arrow, Tok::Ident("b"),
+            Tok::Iff, Tok::Ident("c"), Tok::ColonEq, Tok::Ident("d"), Tok::Eof,
+        ]);
+    }
+
+    #[test]
+    fn unicode_aliases() {
+        assert_eq!(tokens("∀x. ¬⊥ → ∃y. P ∧ Q ∨ R ↔ S"), tokens("forall x. not false -> exists y. P and Q or R <-> S"));
+    }
+
+    #[test]
+    fn line_comments() {
+        assert_eq!(tokens("let x -- a comment\nlet y"), vec![Tok::Let, Tok::Ident("x"), Tok::Let, Tok::Ident("y"), Tok::Eof]);
+        assert_eq!(comments("a --one\n-- two"), vec!["--one", "-- two"]);
+        assert_eq!(tokens("x <=--comment"), vec![Tok::Ident("x"), Tok::Ident("<="), Tok::Eof]);
+    }
+
+    #[test]
+    fn semicolon_is_always_a_token() {
+        assert_eq!(tokens("h; ;x ; y"), vec![Tok::Ident("h"), Tok::Semicolon, Tok::Semicolon, Tok::Ident("x"), Tok::Semicolon, Tok::Ident("y"), Tok::Eof]);
+    }
+
+    #[test]
+    fn spaced_semicolon_ends_a_step() {
+        assert_eq!(tokens("{ have h : P := A ; show P := h }"), vec![
+            Tok::LBrace, Tok::Have, Tok::Ident("h"), Tok::Colon, Tok::Ident("P"), Tok::ColonEq, Tok::Ident("A"), Tok::Semicolon,
+            Tok::Show, Tok::Ident("P"), Tok::ColonEq, Tok::Ident("h"), Tok::RBrace, Tok::Eof,
+        ]);
+        assert!(comments("have h : P := A ; show P := h").is_empty());
+    }
+
+    #[test]
+    fn semicolon_at_line_start_is_a_comment() {
+        assert_eq!(comments("; a comment\nlet x :: Type\n  ; indented\nhave h : P := A;\nshow"), vec!["; a comment", "; indented"]);
+        assert_eq!(tokens("; a comment\r\nlet x :: Type ; still code"), vec![
+            Tok::Let, Tok::Ident("x"), Tok::ColonColon, Tok::Type, Tok::Semicolon, Tok::Ident("still"), Tok::Ident("code"), Tok::Eof,
+        ]);
+    }
+
+    #[test]
+    fn nested_block_comments() {
+        assert_eq!(tokens("a #| b #| c |# d |# e"), vec![Tok::Ident("a"), Tok::Ident("e"), Tok::Eof]);
+        assert_eq!(comments("a #| b #| c |# d |# e"), vec!["#| b #| c |# d |#"]);
+    }
+
+    #[test]
+    fn tokens_print_back() {
+        let text = "proof T : ∀x: Nat. x = 0 → ¬(P x ∧ Q) { have h : arith.P x := and_elim_left h; show ⊥ := h $ h }";
+        let toks = tokens(text);
+        let printed: Vec<String> = toks.iter().filter(|&&tok| tok != Tok::Eof).map(|tok| tok.to_string()).collect();
+        assert_eq!(tokens(&printed.join(" ")), toks);
+    }
+
+    #[test]
+    fn lexical_errors() {
+        let mut lexer = Lexer::new("a #| b #| c |#");
+        lexer.by_ref().count();
+        let errors = lexer.take_errors();
+        assert!(match errors.as_slice() { &[LexicalError::UnterminatedComment(2)] => true, _ => false }, "{:?}", errors);
+        let mut lexer = Lexer::new("a ` b");
+        assert_eq!(lexer.by_ref().map(|item| item.unwrap().1).collect::<Vec<Tok>>(), vec![Tok::Ident("a"), Tok::Ident("b"), Tok::Eof]);
+        let errors = lexer.take_errors();
+        assert!(match errors.as_slice() { &[LexicalError::InvalidCharacter(2, '`')] => true, _ => false }, "{:?}", errors);
+    }
+}
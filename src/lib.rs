@@ -0,0 +1,18 @@
+#![feature(box_patterns)]
+
+extern crate lalrpop_util;
+
+mod grammar;
+pub mod lexer;
+pub mod error;
+pub mod ast;
+pub mod types;
+pub mod state;
+pub mod repl;
+pub mod module;
+pub mod syntax;
+pub mod elaborate;
+pub mod pretty;
+pub mod format;
+pub mod json;
+pub mod lsp;
@@ -0,0 +1,543 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use ast::*;
+use elaborate::Elaborator;
+use error;
+use error::{json_list, json_string, ErrorCollector, FileLocation, SourceInfo};
+use json::Json;
+use lexer::{Lexer, Tok};
+use module;
+use syntax;
+
+
+const CAPABILITIES: &'static str = "{\"capabilities\":{\"textDocumentSync\":1,\"hoverProvider\":true,\
+    \"definitionProvider\":true,\"completionProvider\":{\"triggerCharacters\":[\".\"]}},\
+    \"serverInfo\":{\"name\":\"rlang\"}}";
+
+const PARSE_ERROR: i32 = -32700;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+
+const KIND_FUNCTION: u32 = 3;
+const KIND_CLASS: u32 = 7;
+const KIND_CONSTANT: u32 = 21;
+
+
+struct Document {
+    uri: String,
+    text: String,
+    source: SourceInfo,
+    bound: Vec<(usize, usize)>,
+}
+
+impl Document {
+    /// Elaborates `text` into `globals`, which the caller has checkpointed.
+    fn check(uri: &str, text: String, globals: &mut Bindings) -> (Document, Vec<String>) {
+        let path = uri_to_path(uri);
+        let source = SourceInfo::from_text(&path, &text);
+        let mut locals = LocalBindings::new();
+        globals.add_search_dir(Path::new(&path).parent().map_or(PathBuf::new(), |dir| dir.to_path_buf()));
+        let errors = ErrorCollector::new();
+        let stmts = syntax::parse_program(&source, &text);
+        let bound = {
+            let mut elaborator = Elaborator::new(&mut locals, globals, &source, &errors);
+            elaborator.program(stmts);
+            elaborator.bound_names().iter().map(FileLocation::position).collect()
+        };
+        globals.pop_search_dir();
+        let diagnostics = errors.take().iter().map(|error| error.to_diagnostic(&source, uri)).collect();
+        (Document {
+            uri: String::from(uri),
+            text: text,
+            source: source,
+            bound: bound,
+        }, diagnostics)
+    }
+
+    fn describe(&self, globals: &Bindings, id: &usize, name: &str) -> Option<String> {
+        match (globals.get_type(id), globals.get_theorem(id)) {
+            (Some(mtype), _)     => Some(format!("{} :: {}", name, mtype.repr(globals))),
+            (None, Some(schema)) => Some(format!("{} : {}", name, schema.repr(globals))),
+            (None, None)         => None,
+        }
+    }
+
+    fn global_at(&self, globals: &Bindings, offset: usize) -> Option<(usize, usize, &str, usize)> {
+        let (start, name, end) = name_at(&self.text, offset)?;
+        if self.bound.contains(&self.source.to_file_location(start).position()) {
+            return None;
+        }
+        Some((globals.lookup(name)?, start, name, end))
+    }
+
+    fn hover(&self, globals: &Bindings, offset: usize) -> String {
+        let (id, start, name, end) = match self.global_at(globals, offset) {
+            Some(global) => global,
+            None         => return String::from("null"),
+        };
+        match self.describe(globals, &id, name) {
+            Some(text) => format!("{{\"contents\":{{\"kind\":\"markdown\",\"value\":{}}},\"range\":{}}}",
+                json_string(&format!("```\n{}\n```", text)), self.source.to_span(start, end).to_range(&self.source)),
+            None       => String::from("null"),
+        }
+    }
+
+    fn definition(&self, globals: &Bindings, offset: usize) -> String {
+        let id = match self.global_at(globals, offset) {
+            Some((id, _, _, _)) => id,
+            None                => return String::from("null"),
+        };
+        let site = match globals.get_site(&id) {
+            Some(site) => site,
+            None       => return String::from("null"),
+        };
+        if site.filename() == self.source.filename() {
+            return format!("{{\"uri\":{},\"range\":{}}}", json_string(&self.uri), site.to_range(&self.source));
+        }
+        match error::read_source(site.filename(), site) {
+            Ok((source, _)) => format!("{{\"uri\":{},\"range\":{}}}",
+                json_string(&path_to_uri(site.filename())), site.to_range(&source)),
+            Err(_)          => String::from("null"),
+        }
+    }
+
+    fn completion(&self, globals: &Bindings, offset: usize) -> String {
+        let word = word_before(&self.text, offset);
+        let qualifier = word.rfind('.').map(|dot| &word[..dot]);
+        let mut items: Vec<(String, String)> = Vec::new();
+        for id in globals.declared() {
+            let name = globals.get_name(&id).unwrap().clone();
+            let base = name.rsplit('.').next().unwrap();
+            let label = match qualifier {
+                Some(qualifier) => format!("{}.{}", qualifier, base),
+                None            => String::from(base),
+            };
+            let label = if globals.lookup(&label) == Some(id) {
+                label
+            } else if qualifier.is_none() {
+                name.clone()
+            } else {
+                continue;
+            };
+            let kind = match globals.get_type(&id) {
+                Some(MetaType::Type) => KIND_CLASS,
+                Some(_)              => KIND_FUNCTION,
+                None                 => KIND_CONSTANT,
+            };
+            let insert = match qualifier {
+                Some(qualifier) => &label[qualifier.len() + 1..],
+                None            => &label[..],
+            };
+            let item = format!("{{\"label\":{},\"kind\":{},\"detail\":{},\"insertText\":{},\"filterText\":{}}}",
+                json_string(&label), kind, json_string(&self.describe(globals, &id, &label).unwrap()),
+                json_string(insert), json_string(insert));
+            items.push((label.clone(), item));
+        }
+        items.sort();
+        items.dedup_by(|lhs, rhs| lhs.0 == rhs.0);
+        json_list(items.into_iter().map(|(_, item)| item))
+    }
+}
+
+
+pub struct Server {
+    shutdown: bool,
+    globals: Bindings,
+    active: Option<String>,
+    documents: HashMap<String, Document>,
+}
+
+impl Server {
+    pub fn new(prelude: bool) -> Server {
+        let mut globals = Bindings::new();
+        if prelude {
+            module::import_prelude(&mut globals, &ErrorCollector::new(), &FileLocation::new("<prelude>", 0, 0));
+        }
+        Server {
+            shutdown: false,
+            globals: globals,
+            active: None,
+            documents: HashMap::new(),
+        }
+    }
+
+    pub fn run(&mut self) -> io::Result<()> {
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+        self.serve(&mut stdin.lock(), &mut stdout.lock())
+    }
+
+    fn serve<R: BufRead, W: Write>(&mut self, input: &mut R, output: &mut W) -> io::Result<()> {
+        while let Some(body) = read_message(input) {
+            match Json::parse(&body) {
+                Ok(message) => self.handle(&message, output)?,
+                Err(msg)    => send(output, &reply_error(&Json::Null, PARSE_ERROR, &msg))?,
+            }
+        }
+        Ok(())
+    }
+
+    fn handle<W: Write>(&mut self, message: &Json, output: &mut W) -> io::Result<()> {
+        let method = message.get("method").and_then(Json::as_str).unwrap_or("");
+        let params = message.get("params").unwrap_or(&Json::Null);
+        match message.get("id") {
+            Some(id) => {
+                let reply = match self.request(method, params) {
+                    Ok(result)       => format!("{{\"jsonrpc\":\"2.0\",\"id\":{},\"result\":{}}}", id, result),
+                    Err((code, msg)) => reply_error(id, code, &msg),
+                };
+                send(output, &reply)
+            },
+            None     => self.notification(method, params, output),
+        }
+    }
+
+    fn request(&mut self, method: &str, params: &Json) -> Result<String, (i32, String)> {
+        match method {
+            "initialize"              => Ok(String::from(CAPABILITIES)),
+            "shutdown"                => {
+                self.shutdown = true;
+                Ok(String::from("null"))
+            },
+            "textDocument/hover"      => self.at_position(params).map(|(doc, globals, offset)| doc.hover(globals, offset)),
+            "textDocument/definition" => self.at_position(params).map(|(doc, globals, offset)| doc.definition(globals, offset)),
+            "textDocument/completion" => self.at_position(params).map(|(doc, globals, offset)| doc.completion(globals, offset)),
+            _                         => Err((METHOD_NOT_FOUND, format!("unknown method `{}`", method))),
+        }
+    }
+
+    fn notification<W: Write>(&mut self, method: &str, params: &Json, output: &mut W) -> io::Result<()> {
+        let document = params.get("textDocument");
+        let uri = document.and_then(|doc| doc.get("uri")).and_then(Json::as_str);
+        match (method, uri) {
+            ("textDocument/didOpen", Some(uri))   => {
+                if let Some(text) = document.and_then(|doc| doc.get("text")).and_then(Json::as_str) {
+                    self.update(uri, String::from(text), output)?;
+                }
+            },
+            ("textDocument/didChange", Some(uri)) => {
+                let changes = params.get("contentChanges").and_then(Json::as_array).unwrap_or(&[]);
+                if let Some(text) = changes.last().and_then(|change| change.get("text")).and_then(Json::as_str) {
+                    self.update(uri, String::from(text), output)?;
+                }
+            },
+            ("textDocument/didClose", Some(uri))  => {
+                if self.active.as_ref().map(String::as_str) == Some(uri) {
+                    self.active = None;
+                    self.globals.rollback();
+                }
+                self.documents.remove(uri);
+                publish(output, uri, Vec::new())?;
+            },
+            ("exit", _)                           => process::exit(if self.shutdown { 0 } else { 1 }),
+            _                                     => (),
+        }
+        Ok(())
+    }
+
+    fn update<W: Write>(&mut self, uri: &str, text: String, output: &mut W) -> io::Result<()> {
+        let diagnostics = self.activate(uri, text);
+        publish(output, uri, diagnostics)
+    }
+
+    /// Elaborates a document on top of the prelude, replacing the one elaborated before it.
+    fn activate(&mut self, uri: &str, text: String) -> Vec<String> {
+        if self.active.take().is_some() {
+            self.globals.rollback();
+        }
+        self.globals.checkpoint();
+        self.active = Some(String::from(uri));
+        let (document, diagnostics) = Document::check(uri, text, &mut self.globals);
+        self.documents.insert(String::from(uri), document);
+        diagnostics
+    }
+
+    fn at_position(&mut self, params: &Json) -> Result<(&Document, &Bindings, usize), (i32, String)> {
+        let uri = params.get("textDocument").and_then(|doc| doc.get("uri")).and_then(Json::as_str);
+        let position = params.get("position");
+        let line = position.and_then(|pos| pos.get("line")).and_then(Json::as_usize);
+        let character = position.and_then(|pos| pos.get("character")).and_then(Json::as_usize);
+        match (uri, line, character) {
+            (Some(uri), Some(line), Some(character)) => {
+                if !self.documents.contains_key(uri) {
+                    return Err((INVALID_PARAMS, format!("document `{}` is not open", uri)));
+                }
+                if self.active.as_ref().map(String::as_str) != Some(uri) {
+                    let text = self.documents.remove(uri).unwrap().text;
+                    self.activate(uri, text);
+                }
+                let doc = &self.documents[uri];
+                Ok((doc, &self.globals, offset_of(&doc.text, line, character)))
+            },
+            _                                        => Err((INVALID_PARAMS, String::from("expected a document and a position"))),
+        }
+    }
+}
+
+
+fn read_message<R: BufRead>(input: &mut R) -> Option<String> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header).ok()? == 0 {
+            return None;
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse().ok();
+            }
+        }
+    }
+    let mut body = vec![0; length?];
+    input.read_exact(&mut body).ok()?;
+    String::from_utf8(body).ok()
+}
+
+fn send<W: Write>(output: &mut W, message: &str) -> io::Result<()> {
+    write!(output, "Content-Length: {}\r\n\r\n{}", message.len(), message)?;
+    output.flush()
+}
+
+fn reply_error(id: &Json, code: i32, msg: &str) -> String {
+    format!("{{\"jsonrpc\":\"2.0\",\"id\":{},\"error\":{{\"code\":{},\"message\":{}}}}}", id, code, json_string(msg))
+}
+
+fn publish<W: Write>(output: &mut W, uri: &str, diagnostics: Vec<String>) -> io::Result<()> {
+    send(output, &format!("{{\"jsonrpc\":\"2.0\",\"method\":\"textDocument/publishDiagnostics\",\"params\":{{\"uri\":{},\"diagnostics\":{}}}}}",
+        json_string(uri), json_list(diagnostics.into_iter())))
+}
+
+
+fn name_at(text: &str, offset: usize) -> Option<(usize, &str, usize)> {
+    Lexer::new(text).filter_map(|token| token.ok())
+        .take_while(|&(start, _, _)| start <= offset)
+        .filter(|&(_, _, end)| offset <= end)
+        .filter_map(|(start, tok, end)| match tok {
+            Tok::Ident(name) | Tok::QualIdent(name) => Some((start, name, end)),
+            _                                       => None,
+        })
+        .next()
+}
+
+fn word_before(text: &str, offset: usize) -> &str {
+    let start = text[..offset].char_indices().rev()
+        .take_while(|&(_, c)| c.is_alphanumeric() || c == '_' || c == '\'' || c == '.')
+        .last()
+        .map_or(offset, |(i, _)| i);
+    &text[start..offset]
+}
+
+fn offset_of(text: &str, line: usize, character: usize) -> usize {
+    let start: usize = text.split_inclusive('\n').take(line).map(str::len).sum();
+    let mut units = 0;
+    for (i, c) in text[start..].char_indices() {
+        if units >= character || c == '\n' || c == '\r' {
+            return start + i;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+
+fn uri_to_path(uri: &str) -> String {
+    if !uri.starts_with("file://") {
+        return String::from(uri);
+    }
+    let bytes = &uri.as_bytes()["file://".len()..];
+    let mut path = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match (bytes[i], bytes.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(&String::from_utf8_lossy(hex), 16).ok())) {
+            (b'%', Some(byte)) => {
+                path.push(byte);
+                i += 3;
+            },
+            (byte, _)          => {
+                path.push(byte);
+                i += 1;
+            },
+        }
+    }
+    String::from_utf8_lossy(&path).into_owned()
+}
+
+fn path_to_uri(path: &str) -> String {
+    let path = fs::canonicalize(path).unwrap_or(PathBuf::from(path));
+    let mut uri = String::from("file://");
+    for &byte in path.to_string_lossy().as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => uri.push(byte as char),
+            byte                                                                       => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn frame(body: &str) -> String {
+        format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+    }
+
+    fn exchange(messages: &[&str]) -> Vec<Json> {
+        let input: String = messages.iter().map(|body| frame(body)).collect();
+        let mut output = Vec::new();
+        Server::new(false).serve(&mut Cursor::new(input), &mut output).unwrap();
+        let mut output = Cursor::new(output);
+        let mut replies = Vec::new();
+        while let Some(body) = read_message(&mut output) {
+            replies.push(Json::parse(&body).unwrap());
+        }
+        replies
+    }
+
+    fn position(uri: &str, line: usize, character: usize) -> String {
+        format!("{{\"textDocument\":{{\"uri\":{}}},\"position\":{{\"line\":{},\"character\":{}}}}}", json_string(uri), line, character)
+    }
+
+    #[test]
+    fn reads_framed_messages() {
+        let mut input = Cursor::new("Content-Length: 2\r\n\r\n{}content-length:  4\r\nContent-Type: x\r\n\r\nnull\n\r\n[]");
+        assert_eq!(read_message(&mut input), Some(String::from("{}")));
+        assert_eq!(read_message(&mut input), Some(String::from("null")));
+        assert_eq!(read_message(&mut input), None);
+        assert_eq!(read_message(&mut Cursor::new("Content-Length: 10\r\n\r\nshort")), None);
+        assert_eq!(read_message(&mut Cursor::new("")), None);
+    }
+
+    #[test]
+    fn offsets_count_utf16_units() {
+        let text = "αβ x\n\u{1F600} y\r\nz";
+        assert_eq!(offset_of(text, 0, 0), 0);
+        assert_eq!(offset_of(text, 0, 2), 4);
+        assert_eq!(offset_of(text, 0, 99), 6);
+        assert_eq!(offset_of(text, 1, 2), 11);
+        assert_eq!(offset_of(text, 1, 3), 12);
+        assert_eq!(offset_of(text, 1, 99), 13);
+        assert_eq!(offset_of(text, 2, 0), 15);
+        assert_eq!(offset_of(text, 5, 0), text.len());
+    }
+
+    #[test]
+    fn converts_uris() {
+        assert_eq!(uri_to_path("file:///tmp/a%20b%C3%A9.rl"), "/tmp/a bé.rl");
+        assert_eq!(uri_to_path("untitled:1"), "untitled:1");
+        assert_eq!(uri_to_path("file:///tmp/100%"), "/tmp/100%");
+        assert_eq!(path_to_uri("/nonexistent/a bé.rl"), "file:///nonexistent/a%20b%C3%A9.rl");
+        assert_eq!(uri_to_path(&path_to_uri("/nonexistent/a bé.rl")), "/nonexistent/a bé.rl");
+    }
+
+    #[test]
+    fn finds_the_name_under_the_cursor() {
+        let text = "axiom A = arith.zero = x";
+        assert_eq!(name_at(text, 6), Some((6, "A", 7)));
+        assert_eq!(name_at(text, 7), Some((6, "A", 7)));
+        assert_eq!(name_at(text, 8), None);
+        assert_eq!(name_at(text, 13), Some((10, "arith.zero", 20)));
+        assert_eq!(name_at(text, 0), None);
+        assert_eq!(name_at(text, 24), Some((23, "x", 24)));
+    }
+
+    #[test]
+    fn answers_hover_and_definition() {
+        let uri = "file:///nonexistent/test.rl";
+        let text = "let Nat :: Type\nlet zero :: Term Nat\naxiom A = zero = zero\n";
+        let replies = exchange(&[
+            "{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"initialize\",\"params\":{}}",
+            &format!("{{\"jsonrpc\":\"2.0\",\"method\":\"textDocument/didOpen\",\"params\":{{\"textDocument\":{{\"uri\":{},\"text\":{}}}}}}}",
+                json_string(uri), json_string(text)),
+            &format!("{{\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"textDocument/hover\",\"params\":{}}}", position(uri, 2, 11)),
+            &format!("{{\"jsonrpc\":\"2.0\",\"id\":3,\"method\":\"textDocument/definition\",\"params\":{}}}", position(uri, 2, 18)),
+            "{\"jsonrpc\":\"2.0\",\"id\":4,\"method\":\"nonsense\"}",
+        ]);
+        assert_eq!(replies.len(), 5);
+        assert_eq!(replies[0].get("id"), Some(&Json::Number(1.0)));
+        assert_eq!(replies[0].get("result"), Json::parse(CAPABILITIES).ok().as_ref());
+        assert_eq!(replies[1].get("method").and_then(Json::as_str), Some("textDocument/publishDiagnostics"));
+        assert_eq!(replies[1].get("params").and_then(|params| params.get("diagnostics")), Some(&Json::Array(Vec::new())));
+        let hover = replies[2].get("result").unwrap();
+        assert_eq!(hover.get("contents").and_then(|contents| contents.get("value")).and_then(Json::as_str),
+            Some("```\nzero :: Term Nat\n```"));
+        assert_eq!(hover.get("range"), Json::parse("{\"start\":{\"line\":2,\"character\":10},\"end\":{\"line\":2,\"character\":14}}").ok().as_ref());
+        let definition = replies[3].get("result").unwrap();
+        assert_eq!(definition.get("uri").and_then(Json::as_str), Some(uri));
+        assert_eq!(definition.get("range").and_then(|range| range.get("start")),
+            Json::parse("{\"line\":1,\"character\":4}").ok().as_ref());
+        assert_eq!(replies[4].get("error").and_then(|error| error.get("code")), Some(&Json::Number(METHOD_NOT_FOUND as f64)));
+    }
+
+    #[test]
+    fn bound_variables_are_not_globals() {
+        let text = "let Nat :: Type\nlet x :: Term Nat\naxiom A = forall x: Nat. x = x\naxiom B = x = y\n";
+        let mut globals = Bindings::new();
+        let (document, _) = Document::check("file:///nonexistent/test.rl", String::from(text), &mut globals);
+        let offset = |line: usize, character: usize| offset_of(text, line, character);
+        assert_eq!(document.hover(&globals, offset(2, 17)), "null");
+        assert_eq!(document.hover(&globals, offset(2, 25)), "null");
+        assert_eq!(document.definition(&globals, offset(2, 29)), "null");
+        assert!(document.hover(&globals, offset(3, 10)).contains("x :: Term Nat"));
+        assert!(document.definition(&globals, offset(3, 10)).contains("\"line\":1"));
+        assert_eq!(document.hover(&globals, offset(3, 14)), "null");
+        assert_eq!(globals.lookup("y"), None);
+        assert_eq!(globals.lookup("forall"), None);
+        assert!(globals.search_path().is_empty());
+    }
+
+    #[test]
+    fn documents_do_not_see_each_other() {
+        let open = |uri: &str, text: &str| format!("{{\"jsonrpc\":\"2.0\",\"method\":\"textDocument/didOpen\",\
+            \"params\":{{\"textDocument\":{{\"uri\":{},\"text\":{}}}}}}}", json_string(uri), json_string(text));
+        let hover = |id: usize, uri: &str| format!("{{\"jsonrpc\":\"2.0\",\"id\":{},\"method\":\"textDocument/hover\",\"params\":{}}}",
+            id, position(uri, 1, 10));
+        let (a, b) = ("file:///nonexistent/a.rl", "file:///nonexistent/b.rl");
+        let replies = exchange(&[
+            &open(a, "let Nat :: Type\naxiom A = Nat"),
+            &open(b, "let Nat :: Formula\naxiom B = Nat"),
+            &hover(1, a),
+            &hover(2, b),
+            &format!("{{\"jsonrpc\":\"2.0\",\"method\":\"textDocument/didClose\",\"params\":{{\"textDocument\":{{\"uri\":{}}}}}}}",
+                json_string(b)),
+            &hover(3, a),
+        ]);
+        let value = |reply: &Json| reply.get("result").and_then(|result| result.get("contents"))
+            .and_then(|contents| contents.get("value")).and_then(Json::as_str).map(String::from);
+        assert_eq!(replies.len(), 6);
+        assert_eq!(value(&replies[2]), Some(String::from("```\nNat :: Type\n```")));
+        assert_eq!(value(&replies[3]), Some(String::from("```\nNat :: Formula\n```")));
+        assert_eq!(value(&replies[5]), Some(String::from("```\nNat :: Type\n```")));
+    }
+
+    struct Closed;
+
+    impl Write for Closed {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_failures_stop_the_server() {
+        let input = frame("{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"initialize\"}");
+        let result = Server::new(false).serve(&mut Cursor::new(input), &mut Closed);
+        assert_eq!(result.map_err(|err| err.kind()), Err(io::ErrorKind::BrokenPipe));
+    }
+}
@@ -1,49 +1,323 @@
-#![feature(box_patterns)]
+extern crate rlang;
 
 // use std::collections::HashMap;
 use std::env;
-use std::fs::File;
-use std::io;
-use std::io::Read;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process;
 
-mod grammar;
-mod error;
-mod ast;
-mod types;
-mod state;
+use rlang::{error, format, lsp, module, repl, syntax};
+use rlang::ast::*;
+use rlang::elaborate::Elaborator;
+use rlang::error::{Error, ErrorCollector, ErrorFormat, ErrorSink, FileLocation};
+use rlang::error::ErrorKind::FileWriteFailure;
+use rlang::state::Bindings;
 
-use ast::*;
-use error::FileLocation;
-use error::SourceInfo;
-use state::Bindings;
 
+const USAGE: &'static str = "\
+usage: rlang <command> [options] <file>...
 
-fn main() {
+commands:
+    check    check the given files, exiting with a non-zero status on any error
+    print    check the given files and print the resulting environment
+    fmt      reformat the given files in place
+    repl     start an interactive session
+    lsp      run a language server on standard input and output
+
+options:
+    --format <human|short|json>
+                              how errors are printed (default: human); json
+                              prints one object per line and moves the
+                              summary to stderr
+    --color <auto|always|never>
+                              whether errors are coloured (default: auto)
+    --no-prelude              do not load the bundled prelude theories
+    -q, --quiet               only print errors
+    -v, --verbose             also report every checked declaration
+    --check                   with fmt, only report files that are not
+                              formatted instead of rewriting them
+    --width <columns>         with fmt, the line width to fit (default: 80)
+    -h, --help                print this message";
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Command {
+    Check,
+    Print,
+    Fmt,
+    Repl,
+    Lsp,
+}
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+
+struct Options {
+    command: Command,
+    format: ErrorFormat,
+    color: bool,
+    prelude: bool,
+    verbosity: Verbosity,
+    check: bool,
+    width: usize,
+    files: Vec<String>,
+}
+
+impl Options {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+        let command = match args.next() {
+            Some(ref cmd) if cmd == "check" => Command::Check,
+            Some(ref cmd) if cmd == "print" => Command::Print,
+            Some(ref cmd) if cmd == "fmt"   => Command::Fmt,
+            Some(ref cmd) if cmd == "repl"  => Command::Repl,
+            Some(ref cmd) if cmd == "lsp"   => Command::Lsp,
+            Some(cmd)                       => return Err(format!("unknown command `{}`", cmd)),
+            None                            => return Err(String::from("no command given")),
+        };
+        let mut options = Options {
+            command: command,
+            format: ErrorFormat::Human,
+            color: color_auto(),
+            prelude: true,
+            verbosity: Verbosity::Normal,
+            check: false,
+            width: 80,
+            files: Vec::new(),
+        };
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--format"          => {
+                    options.format = match args.next() {
+                        Some(ref fmt) if fmt == "human" => ErrorFormat::Human,
+                        Some(ref fmt) if fmt == "short" => ErrorFormat::Short,
+                        Some(ref fmt) if fmt == "json"  => ErrorFormat::Json,
+                        Some(fmt)                       => return Err(format!("unknown format `{}`", fmt)),
+                        None                            => return Err(String::from("missing value for `--format`")),
+                    };
+                },
+                "--color"           => {
+                    options.color = match args.next() {
+                        Some(ref when) if when == "auto"   => color_auto(),
+                        Some(ref when) if when == "always" => true,
+                        Some(ref when) if when == "never"  => false,
+                        Some(when)                         => return Err(format!("unknown color choice `{}`", when)),
+                        None                               => return Err(String::from("missing value for `--color`")),
+                    };
+                },
+                "--no-prelude"      => options.prelude = false,
+                "-q" | "--quiet"    => options.verbosity = Verbosity::Quiet,
+                "-v" | "--verbose"  => options.verbosity = Verbosity::Verbose,
+                "--check"           => options.check = true,
+                "--width"           => {
+                    options.width = match args.next().map(|width| width.parse()) {
+                        Some(Ok(width)) if width > 0 => width,
+                        Some(_)                      => return Err(String::from("invalid value for `--width`")),
+                        None                         => return Err(String::from("missing value for `--width`")),
+                    };
+                },
+                _ if arg.starts_with("-") => return Err(format!("unknown option `{}`", arg)),
+                _                   => options.files.push(arg),
+            }
+        }
+        if options.files.is_empty() && options.command != Command::Repl && options.command != Command::Lsp {
+            return Err(String::from("no input files"));
+        }
+        Ok(options)
+    }
+}
+
+
+fn color_auto() -> bool {
+    io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none()
+}
+
+
+#[derive(Default)]
+struct Stats {
+    objects: usize,
+    axioms: usize,
+    theorems: usize,
+    errors: usize,
+    unformatted: usize,
+}
+
+
+fn run_file(filename: &str, options: &Options) -> io::Result<Stats> {
     let base_context = FileLocation::new("<prelude>", 0, 0);
-    let stdout = io::stdout();
-    for filename in env::args().skip(1) {
-        match SourceInfo::new(&filename, &base_context) {
-            Ok(source_info) => {
-                let mut f = File::open(filename.clone()).unwrap();
-                let mut file_text: String = "".to_string();
-
-                if let Err(_) = f.read_to_string(&mut file_text) {
-                    panic!("failed to read file {}", filename);
+    let err_dest = ErrorSink::new(options.format, options.color);
+    let mut stats = Stats::default();
+    match error::read_source(filename, &base_context) {
+        Ok((source_info, file_text)) => {
+            let mut locals = LocalBindings::new();
+            let mut globals = Bindings::new();
+            globals.add_search_dir(Path::new(filename).parent().map_or(PathBuf::new(), |dir| dir.to_path_buf()));
+            let errors = ErrorCollector::new();
+            if options.prelude {
+                module::import_prelude(&mut globals, &errors, &base_context);
+            }
+            let stmts = syntax::parse_program(&source_info, &file_text);
+            let decls = Elaborator::new(&mut locals, &mut globals, &source_info, &errors).program(stmts);
+            err_dest.flush(&mut io::stdout(), &errors, &source_info)?;
+            for decl in decls.iter() {
+                match decl {
+                    &Declaration::Object(_)  => stats.objects += 1,
+                    &Declaration::Axiom(_)   => stats.axioms += 1,
+                    &Declaration::Theorem(_) => stats.theorems += 1,
+                    &Declaration::Data(id)   => stats.objects += 1 + globals.get_data(&id).unwrap().constructors.len(),
                 }
-
-                let mut locals = LocalBindings::new();
-                let mut globals = Bindings::new();
-                // println!("parsing");
-                match grammar::parse_Program(&mut locals, &mut globals, &source_info, &stdout, &file_text) {
-                    Ok(_)  => println!("ran"),
-                    Err(err) => println!("{:?}", err),
+                if options.verbosity >= Verbosity::Verbose {
+                    status(options, format!("checked {}", describe(decl, &globals)));
                 }
-                // println!("parsed");
-            },
-            Err(rlang_err) => {
-                let mut handle = stdout.lock();
-                rlang_err.to_console_noexcerpt(&mut handle);
             }
+            if options.command == Command::Print {
+                for decl in decls.iter() {
+                    println!("{}", decl.repr(&globals));
+                }
+            }
+        },
+        Err(rlang_err)               => err_dest.report_noexcerpt(&mut io::stdout(), &rlang_err)?,
+    }
+    stats.errors += err_dest.count();
+    if options.verbosity >= Verbosity::Normal {
+        status(options, format!("{}: {} objects, {} axioms, {} theorems, {} errors",
+            filename, stats.objects, stats.axioms, stats.theorems, stats.errors));
+    }
+    Ok(stats)
+}
+
+
+fn format_file(filename: &str, options: &Options) -> io::Result<Stats> {
+    let base_context = FileLocation::new("<prelude>", 0, 0);
+    let err_dest = ErrorSink::new(options.format, options.color);
+    let mut stats = Stats::default();
+    match error::read_source(filename, &base_context) {
+        Ok((source_info, file_text)) => {
+            match format::format_source(&source_info, &file_text, options.width) {
+                Ok(ref formatted) if *formatted == file_text => (),
+                Ok(_) if options.check                       => {
+                    stats.unformatted += 1;
+                    status(options, format!("{}: not formatted", filename));
+                },
+                Ok(formatted)                                => {
+                    match fs::write(filename, formatted) {
+                        Ok(())   => {
+                            if options.verbosity >= Verbosity::Normal {
+                                status(options, format!("{}: formatted", filename));
+                            }
+                        },
+                        Err(err) => err_dest.report_noexcerpt(&mut io::stdout(), &Error::new(FileWriteFailure {
+                            filename: String::from(filename),
+                            rust_err: err.kind(),
+                        }, &base_context))?,
+                    }
+                },
+                Err(errors)                                  => {
+                    for error in errors.iter() {
+                        err_dest.report(&mut io::stdout(), error, &source_info)?;
+                    }
+                },
+            }
+        },
+        Err(rlang_err)               => err_dest.report_noexcerpt(&mut io::stdout(), &rlang_err)?,
+    }
+    stats.errors += err_dest.count();
+    Ok(stats)
+}
+
+
+fn output(stats: io::Result<Stats>) -> Stats {
+    match stats {
+        Ok(stats) => stats,
+        Err(err)  => {
+            eprintln!("error: cannot write the report: {}", err);
+            process::exit(2);
+        },
+    }
+}
+
+
+fn status(options: &Options, text: String) {
+    if options.format == ErrorFormat::Json {
+        eprintln!("{}", text);
+    } else {
+        println!("{}", text);
+    }
+}
+
+
+fn describe(decl: &Declaration, globals: &Bindings) -> String {
+    match decl {
+        &Declaration::Object(id)  => format!("object `{}`", globals.get_name(&id).unwrap()),
+        &Declaration::Axiom(id)   => format!("axiom `{}`", globals.get_name(&id).unwrap()),
+        &Declaration::Theorem(id) => format!("theorem `{}`", globals.get_name(&id).unwrap()),
+        &Declaration::Data(id)    => format!("data type `{}`", globals.get_name(&id).unwrap()),
+    }
+}
+
+
+fn main() {
+    if env::args().skip(1).any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(msg)    => {
+            eprintln!("error: {}\n\n{}", msg, USAGE);
+            process::exit(2);
+        },
+    };
+    if options.command == Command::Repl {
+        if let Err(err) = repl::Repl::new(options.format, options.color, options.prelude).and_then(|mut repl| repl.run()) {
+            eprintln!("error: cannot write the report: {}", err);
+            process::exit(2);
+        }
+        return;
+    }
+    if options.command == Command::Lsp {
+        if let Err(err) = lsp::Server::new(options.prelude).run() {
+            eprintln!("error: cannot write to the client: {}", err);
+            process::exit(2);
+        }
+        return;
+    }
+    if options.command == Command::Fmt {
+        let stats: Vec<Stats> = options.files.iter().map(|filename| output(format_file(filename, &options))).collect();
+        let errors: usize = stats.iter().map(|stats| stats.errors).sum();
+        let unformatted = stats.iter().filter(|stats| stats.unformatted > 0).count();
+        if options.verbosity >= Verbosity::Normal && unformatted > 0 {
+            status(&options, format!("result: FAILED. {} of {} files not formatted", unformatted, options.files.len()));
         }
+        if errors > 0 || unformatted > 0 {
+            process::exit(1);
+        }
+        return;
+    }
+    let mut errors = 0;
+    let mut failed = 0;
+    for filename in options.files.iter() {
+        let stats = output(run_file(filename, &options));
+        errors += stats.errors;
+        if stats.errors > 0 {
+            failed += 1;
+        }
+    }
+    if options.verbosity >= Verbosity::Normal {
+        if failed == 0 {
+            status(&options, format!("result: ok. {} files passed", options.files.len()));
+        } else {
+            status(&options, format!("result: FAILED. {} of {} files failed with {} errors",
+                failed, options.files.len(), errors));
+        }
+    }
+    if errors > 0 {
+        process::exit(1);
     }
-}
\ No newline at end of file
+}
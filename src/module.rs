@@ -0,0 +1,255 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use ast::*;
+use elaborate::Elaborator;
+use error;
+use error::{Error, ErrorCollector, FileLocation, SourceInfo};
+use error::ErrorKind::*;
+use syntax;
+
+
+const PRELUDE: &'static [(&'static str, &'static str)] = &[
+    ("prelude.logic", include_str!("../prelude/logic.rl")),
+    ("prelude.eq",    include_str!("../prelude/eq.rl")),
+    ("prelude.nat",   include_str!("../prelude/nat.rl")),
+    ("prelude.order", include_str!("../prelude/order.rl")),
+    ("prelude.set",   include_str!("../prelude/set.rl")),
+];
+
+
+#[derive(Debug)]
+pub struct Import {
+    pub path: String,
+    pub alias: Option<String>,
+    pub names: Option<Vec<String>>,
+    pub open: bool,
+}
+
+impl Import {
+    fn alias(&self) -> &str {
+        match self.alias {
+            Some(ref alias) => alias,
+            None            => self.path.rsplit('.').next().unwrap(),
+        }
+    }
+}
+
+
+pub fn import(import: Import, globals: &mut Bindings, source: &SourceInfo, errors: &ErrorCollector, location: &FileLocation) -> error::Result<()> {
+    if !globals.is_loaded(&import.path) {
+        if let Some(cycle) = globals.import_cycle(&import.path) {
+            return Err(Error::new(ImportCycle { cycle: cycle }, location));
+        }
+        let (module_source, text) = load_module(&import.path, globals, source, location)?;
+        let filename = String::from(module_source.filename());
+        let mut locals = LocalBindings::new();
+        globals.enter_module(&import.path);
+        let stmts = syntax::parse_program(&module_source, &text);
+        Elaborator::new(&mut locals, globals, &module_source, errors).program(stmts);
+        globals.leave_module(&import.path, &filename);
+        errors.add_source(module_source);
+    }
+    if let Some(ref names) = import.names {
+        for name in names.iter() {
+            let id = globals.get_id(&format!("{}.{}", import.path, name));
+            if globals.get_type(&id).is_none() && globals.get_theorem(&id).is_none() {
+                return Err(globals.no_binding(&id, |_| false, location));
+            }
+        }
+    }
+    globals.add_alias(import.alias(), &import.path);
+    if import.open || import.names.is_some() {
+        globals.add_open(&import.path, import.names.clone());
+    }
+    Ok(())
+}
+
+
+pub fn import_prelude(globals: &mut Bindings, errors: &ErrorCollector, location: &FileLocation) {
+    let source = SourceInfo::from_text("<prelude>", "");
+    for &(path, _) in PRELUDE.iter() {
+        let prelude = Import {
+            path: String::from(path),
+            alias: None,
+            names: None,
+            open: true,
+        };
+        if let Err(error) = import(prelude, globals, &source, errors, location) {
+            errors.report(error);
+        }
+    }
+}
+
+
+fn load_module(path: &str, globals: &Bindings, source: &SourceInfo, location: &FileLocation) -> error::Result<(SourceInfo, String)> {
+    match PRELUDE.iter().find(|&&(name, _)| name == path) {
+        Some(&(_, text)) => {
+            let filename = format!("<prelude>/{}.rl", path.rsplit('.').next().unwrap());
+            Ok((SourceInfo::from_text(&filename, text), String::from(text)))
+        },
+        None             => error::read_source(&find_module(path, globals, source, location)?, location),
+    }
+}
+
+
+fn find_module(path: &str, globals: &Bindings, source: &SourceInfo, location: &FileLocation) -> error::Result<String> {
+    let relative: PathBuf = format!("{}.rl", path.replace('.', "/")).into();
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    dirs.push(Path::new(source.filename()).parent().map_or(PathBuf::new(), |dir| dir.to_path_buf()));
+    dirs.extend(globals.search_path().iter().cloned());
+    if let Some(rlang_path) = env::var_os("RLANG_PATH") {
+        dirs.extend(env::split_paths(&rlang_path));
+    }
+    let mut candidates: Vec<PathBuf> = Vec::new();
+    for dir in dirs.iter() {
+        let candidate = dir.join(&relative);
+        if !candidates.contains(&candidate) {
+            candidates.push(candidate);
+        }
+    }
+    match candidates.iter().find(|candidate| candidate.is_file()) {
+        Some(found) => Ok(found.to_string_lossy().into_owned()),
+        None        => {
+            let searched: Vec<String> = candidates.iter().map(|candidate| candidate.to_string_lossy().into_owned()).collect();
+            Err(Error::new(ModuleNotFound { name: String::from(path) }, location)
+                .with_note(format!("searched {}", searched.join(", "))))
+        },
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::process;
+
+    use super::*;
+
+    const ARITH: &'static str = "
+data Nat = zero | succ Nat
+let add :: Term Nat -> Nat -> Nat
+";
+
+    /// A temporary search directory, deleted when dropped.
+    struct SearchDir(PathBuf);
+
+    impl Drop for SearchDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn search_dir(name: &str, files: &[(&str, &str)]) -> SearchDir {
+        let dir = env::temp_dir().join(format!("rlang-{}-{}", name, process::id()));
+        for &(file, text) in files.iter() {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+        SearchDir(dir)
+    }
+
+    fn arith_import(alias: Option<&str>, open: bool) -> Import {
+        Import {
+            path: String::from("m.arith"),
+            alias: alias.map(String::from),
+            names: None,
+            open: open,
+        }
+    }
+
+    fn check(dir: &str, files: &[(&str, &str)], text: &str) -> Vec<(&'static str, String)> {
+        let dir = search_dir(dir, files);
+        let mut globals = Bindings::new();
+        globals.add_search_dir(dir.0.clone());
+        let source = SourceInfo::from_text("<test>", text);
+        let errors = ErrorCollector::new();
+        let mut locals = LocalBindings::new();
+        let stmts = syntax::parse_program(&source, text);
+        Elaborator::new(&mut locals, &mut globals, &source, &errors).program(stmts);
+        errors.take().iter().map(|error| {
+            let mut output = Vec::new();
+            error.to_console_noexcerpt(&mut output).unwrap();
+            (error.err_type(), String::from_utf8(output).unwrap())
+        }).collect()
+    }
+
+    fn codes(dir: &str, files: &[(&str, &str)], text: &str) -> Vec<&'static str> {
+        check(dir, files, text).into_iter().map(|(code, _)| code).collect()
+    }
+
+    fn resolved(globals: &mut Bindings, name: &str) -> String {
+        let id = globals.resolve(name);
+        globals.get_name(&id).unwrap().clone()
+    }
+
+    #[test]
+    fn prelude_checks_without_errors() {
+        let mut globals = Bindings::new();
+        let errors = ErrorCollector::new();
+        import_prelude(&mut globals, &errors, &FileLocation::new("<test>", 0, 0));
+        let codes: Vec<&str> = errors.take().iter().map(|error| error.err_type()).collect();
+        assert_eq!(codes, Vec::<&str>::new());
+        for &(path, _) in PRELUDE.iter() {
+            assert!(globals.is_loaded(path), "{} was not loaded", path);
+        }
+    }
+
+    #[test]
+    fn rollback_drops_aliases_and_opens() {
+        let dir = search_dir("rollback", &[("m/arith.rl", ARITH)]);
+        let mut globals = Bindings::new();
+        globals.add_search_dir(dir.0.clone());
+        let errors = ErrorCollector::new();
+        let source = SourceInfo::from_text("<test>", "");
+        let location = FileLocation::new("<test>", 0, 0);
+        globals.checkpoint();
+        import(arith_import(Some("A"), true), &mut globals, &source, &errors, &location).unwrap();
+        assert_eq!(resolved(&mut globals, "A.zero"), "m.arith.zero");
+        assert_eq!(resolved(&mut globals, "zero"), "m.arith.zero");
+        globals.rollback();
+        assert!(!globals.is_loaded("m.arith"));
+        assert_eq!(resolved(&mut globals, "A.zero"), "A.zero");
+        assert_eq!(resolved(&mut globals, "zero"), "zero");
+        globals.checkpoint();
+        import(arith_import(None, false), &mut globals, &source, &errors, &location).unwrap();
+        globals.commit();
+        assert_eq!(resolved(&mut globals, "arith.zero"), "m.arith.zero");
+        assert!(errors.take().is_empty());
+    }
+
+    #[test]
+    fn qualified_and_aliased_names() {
+        let files = &[("m/arith.rl", ARITH)];
+        assert_eq!(codes("qualified", files, "import m.arith
+axiom A = forall x: arith.Nat. arith.add x arith.zero = x"), Vec::<&str>::new());
+        assert_eq!(codes("alias", files, "import m.arith as Arith
+let one :: Term Arith.Nat
+axiom A = Arith.succ Arith.zero = one
+axiom B = forall x: Arith.Nat. Arith.add x Arith.zero = x
+axiom C = schema Phi :: Formula Arith.Nat. Phi Arith.zero -> exists x: Arith.Nat. Phi x"), Vec::<&str>::new());
+        assert_eq!(codes("unqualified", files, "import m.arith
+axiom A = succ zero = zero"), vec!["NoBinding"]);
+    }
+
+    #[test]
+    fn selective_imports() {
+        let files = &[("m/arith.rl", ARITH)];
+        assert_eq!(codes("selected", files, "import m.arith (Nat, zero)
+let one :: Term Nat
+axiom A = zero = one"), Vec::<&str>::new());
+        assert_eq!(codes("unselected", files, "import m.arith (Nat, zero)
+axiom A = succ zero = zero"), vec!["NoBinding"]);
+        assert_eq!(codes("unknown", files, "import m.arith (Nat, nope)"), vec!["NoBinding"]);
+    }
+
+    #[test]
+    fn missing_modules_and_cycles() {
+        assert_eq!(codes("missing", &[], "import m.missing"), vec!["ModuleNotFound"]);
+        let errors = check("cycle", &[("m/a.rl", "import m.b"), ("m/b.rl", "import m.a")], "import m.a");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, "ImportCycle");
+        assert!(errors[0].1.contains("m.a -> m.b -> m.a"), "{}", errors[0].1);
+    }
+}
@@ -0,0 +1,475 @@
+use std::collections::{HashMap, HashSet};
+
+use state::Bindings;
+use types::{Formula, FormulaSchema, Ident, InternalType, MetaType, Term};
+
+
+// Printing contexts, from loosest to tightest. A construct is printed without
+// parentheses when its own level is at least the level of its context.
+pub const ABSTRACTION: usize = 0;
+pub const FORMULA: usize = 1;
+pub const CONSEQUENT: usize = 2;
+pub const BICONSEQUENT: usize = 3;
+pub const ANTECEDENT: usize = 4;
+pub const DISJUNCTION: usize = 5;
+pub const CONJUNCTION: usize = 6;
+pub const NEGATION: usize = 7;
+pub const EQUALITY: usize = 8;
+pub const APPLICATION: usize = 9;
+pub const ATOM: usize = 10;
+
+pub const IMPLICATION: usize = ANTECEDENT;
+pub const BICONDITIONAL: usize = FORMULA;
+pub const QUANTIFIER: usize = CONSEQUENT;
+
+const TERM_VAR: &'static str = "x";
+const FORMULA_VAR: &'static str = "P";
+const TYPE_VAR: &'static str = "T";
+
+
+fn parenthesize(text: String, level: usize, context: usize) -> String {
+    if level >= context { text } else { format!("({})", text) }
+}
+
+fn meta_var(mtype: &MetaType) -> &'static str {
+    match mtype {
+        &MetaType::Type    => TYPE_VAR,
+        &MetaType::Term(_) => TERM_VAR,
+        _                  => FORMULA_VAR,
+    }
+}
+
+
+#[derive(Default)]
+struct Symbols {
+    globals: HashSet<usize>,
+    free: Vec<(usize, &'static str)>,
+    bound: Vec<usize>,
+}
+
+impl Symbols {
+    fn ident(&mut self, id: Ident, default: &'static str) {
+        match id {
+            Ident::Global(id)      => { self.globals.insert(id); },
+            Ident::Local(local_id) => {
+                if !self.bound.contains(&local_id) && !self.free.iter().any(|&(var, _)| var == local_id) {
+                    self.free.push((local_id, default));
+                }
+            },
+        }
+    }
+
+    fn internal_type(&mut self, itype: &InternalType) {
+        match itype {
+            &InternalType::Named(id)                                => self.ident(id, TYPE_VAR),
+            &InternalType::Func(box ref arg_type, box ref ret_type) => {
+                self.internal_type(arg_type);
+                self.internal_type(ret_type);
+            },
+        }
+    }
+
+    fn term(&mut self, term: &Term) {
+        match term {
+            &Term::Symbol(id, _)                          => self.ident(id, TERM_VAR),
+            &Term::Application(box ref func, box ref arg) => {
+                self.term(func);
+                self.term(arg);
+            },
+        }
+    }
+
+    fn formula(&mut self, form: &Formula) {
+        match form {
+            &Formula::False                                    => (),
+            &Formula::Relation(id, _)                          => self.ident(id, FORMULA_VAR),
+            &Formula::Application(box ref pred, ref arg)       => {
+                self.formula(pred);
+                self.term(arg);
+            },
+            &Formula::Equality(ref itype, ref lhs, ref rhs)    => {
+                self.internal_type(itype);
+                self.term(lhs);
+                self.term(rhs);
+            },
+            &Formula::Negation(box ref form)                   => self.formula(form),
+            &Formula::Implication(box ref lhs, box ref rhs)    |
+            &Formula::Conjunction(box ref lhs, box ref rhs)    |
+            &Formula::Disjunction(box ref lhs, box ref rhs)    |
+            &Formula::Biconditional(box ref lhs, box ref rhs)  => {
+                self.formula(lhs);
+                self.formula(rhs);
+            },
+            &Formula::UniversalQ(var, ref itype, box ref form)   |
+            &Formula::ExistentialQ(var, ref itype, box ref form) |
+            &Formula::Abstraction(var, ref itype, box ref form)  => {
+                self.internal_type(itype);
+                self.bound.push(var);
+                self.formula(form);
+                self.bound.pop();
+            },
+        }
+    }
+
+    fn meta_type(&mut self, mtype: &MetaType) {
+        match mtype {
+            &MetaType::Type                                    => (),
+            &MetaType::Term(ref itype)                         => self.internal_type(itype),
+            &MetaType::Formula(ref arg_types)                  => {
+                for itype in arg_types {
+                    self.internal_type(itype);
+                }
+            },
+            &MetaType::Schema(ref arg_types, box ref ret_type) => {
+                for mtype in arg_types {
+                    self.meta_type(mtype);
+                }
+                self.meta_type(ret_type);
+            },
+            &MetaType::Poly(ref vars, box ref mtype)           => {
+                let depth = self.bound.len();
+                self.bound.extend(vars.iter().cloned());
+                self.meta_type(mtype);
+                self.bound.truncate(depth);
+            },
+        }
+    }
+
+    fn schema(&mut self, schema: &FormulaSchema) {
+        match schema {
+            &FormulaSchema::Formula(ref form)                      => self.formula(form),
+            &FormulaSchema::Schema(var, ref mtype, box ref schema) => {
+                self.meta_type(mtype);
+                self.bound.push(var);
+                self.schema(schema);
+                self.bound.pop();
+            },
+            &FormulaSchema::Poly(var, box ref schema)              => {
+                self.bound.push(var);
+                self.schema(schema);
+                self.bound.pop();
+            },
+        }
+    }
+}
+
+
+pub struct Printer<'a> {
+    globals: &'a Bindings,
+    names: HashMap<usize, String>,
+}
+
+impl<'a> Printer<'a> {
+    pub fn new(globals: &'a Bindings) -> Printer<'a> {
+        Printer {
+            globals: globals,
+            names: HashMap::new(),
+        }
+    }
+
+    pub fn internal_type(&mut self, itype: &InternalType) -> String {
+        let mut symbols = Symbols::default();
+        symbols.internal_type(itype);
+        self.declare(symbols);
+        self.print_type(itype, false)
+    }
+
+    pub fn type_argument(&mut self, itype: &InternalType) -> String {
+        let mut symbols = Symbols::default();
+        symbols.internal_type(itype);
+        self.declare(symbols);
+        self.print_type(itype, true)
+    }
+
+    pub fn term(&mut self, term: &Term) -> String {
+        let mut symbols = Symbols::default();
+        symbols.term(term);
+        self.declare(symbols);
+        self.print_term(term, APPLICATION)
+    }
+
+    pub fn formula(&mut self, form: &Formula) -> String {
+        let mut symbols = Symbols::default();
+        symbols.formula(form);
+        self.declare(symbols);
+        self.print_formula(form, ABSTRACTION)
+    }
+
+    pub fn meta_type(&mut self, mtype: &MetaType) -> String {
+        let mut symbols = Symbols::default();
+        symbols.meta_type(mtype);
+        self.declare(symbols);
+        self.print_meta_type(mtype)
+    }
+
+    pub fn schema(&mut self, schema: &FormulaSchema) -> String {
+        let mut symbols = Symbols::default();
+        symbols.schema(schema);
+        self.declare(symbols);
+        self.print_schema(schema)
+    }
+
+    fn global_name(&self, id: &usize) -> String {
+        self.globals.shortest_name(id)
+    }
+
+    fn declare(&mut self, symbols: Symbols) {
+        let used: HashSet<String> = symbols.globals.iter().map(|id| self.global_name(id)).collect();
+        for (var, default) in symbols.free {
+            if !self.names.contains_key(&var) {
+                self.bind(var, default, &used);
+            }
+        }
+    }
+
+    fn bind(&mut self, var: usize, default: &str, used: &HashSet<String>) -> (String, Option<String>) {
+        let base = String::from(self.globals.local_name(&var).unwrap_or(default));
+        let name = {
+            let taken = |name: &String| used.contains(name) || self.names.values().any(|other| other == name);
+            if taken(&base) {
+                (1..).map(|n| format!("{}{}", base, n)).find(|name| !taken(name)).unwrap()
+            } else {
+                base
+            }
+        };
+        (name.clone(), self.names.insert(var, name))
+    }
+
+    fn unbind(&mut self, var: usize, old: Option<String>) {
+        match old {
+            Some(name) => { self.names.insert(var, name); },
+            None       => { self.names.remove(&var); },
+        }
+    }
+
+    fn ident(&self, id: Ident, default: &str) -> String {
+        match id {
+            Ident::Global(id)      => self.global_name(&id),
+            Ident::Local(local_id) => {
+                match self.names.get(&local_id) {
+                    Some(name) => name.clone(),
+                    None       => String::from(self.globals.local_name(&local_id).unwrap_or(default)),
+                }
+            },
+        }
+    }
+
+    fn print_type(&self, itype: &InternalType, atomic: bool) -> String {
+        match itype {
+            &InternalType::Named(id)                                => self.ident(id, TYPE_VAR),
+            &InternalType::Func(box ref arg_type, box ref ret_type) => {
+                let text = format!("{} -> {}", self.print_type(arg_type, true), self.print_type(ret_type, false));
+                if atomic { format!("({})", text) } else { text }
+            },
+        }
+    }
+
+    fn print_term(&self, term: &Term, context: usize) -> String {
+        match term {
+            &Term::Symbol(id, _)                          => self.ident(id, TERM_VAR),
+            &Term::Application(box ref func, box ref arg) => {
+                let text = format!("{} {}", self.print_term(func, APPLICATION), self.print_term(arg, ATOM));
+                parenthesize(text, APPLICATION, context)
+            },
+        }
+    }
+
+    fn print_binder(&mut self, keyword: &str, var: usize, itype: &InternalType, form: &Formula, body: usize) -> String {
+        let mut symbols = Symbols::default();
+        symbols.formula(form);
+        let used = symbols.globals.iter().map(|id| self.global_name(id)).collect();
+        let itype = self.print_type(itype, false);
+        let (name, old) = self.bind(var, TERM_VAR, &used);
+        let text = format!("{} {}: {}. {}", keyword, name, itype, self.print_formula(form, body));
+        self.unbind(var, old);
+        text
+    }
+
+    fn print_formula(&mut self, form: &Formula, context: usize) -> String {
+        match form {
+            &Formula::False                                       => String::from("false"),
+            &Formula::Relation(id, _)                             => self.ident(id, FORMULA_VAR),
+            &Formula::Application(box ref pred, ref arg)          => {
+                let text = format!("{} {}", self.print_formula(pred, APPLICATION), self.print_term(arg, ATOM));
+                parenthesize(text, APPLICATION, context)
+            },
+            &Formula::Equality(_, ref lhs, ref rhs)               => {
+                let text = format!("{} = {}", self.print_term(lhs, APPLICATION), self.print_term(rhs, APPLICATION));
+                parenthesize(text, EQUALITY, context)
+            },
+            &Formula::Negation(box ref form)                      => {
+                let text = format!("not {}", self.print_formula(form, NEGATION));
+                parenthesize(text, NEGATION, context)
+            },
+            &Formula::Conjunction(box ref lhs, box ref rhs)       => {
+                let text = format!("{} and {}", self.print_formula(lhs, CONJUNCTION), self.print_formula(rhs, NEGATION));
+                parenthesize(text, CONJUNCTION, context)
+            },
+            &Formula::Disjunction(box ref lhs, box ref rhs)       => {
+                let text = format!("{} or {}", self.print_formula(lhs, DISJUNCTION), self.print_formula(rhs, CONJUNCTION));
+                parenthesize(text, DISJUNCTION, context)
+            },
+            &Formula::Implication(box ref lhs, box ref rhs)       => {
+                let consequent = if context == ANTECEDENT { ANTECEDENT } else { CONSEQUENT };
+                let text = format!("{} -> {}", self.print_formula(lhs, DISJUNCTION), self.print_formula(rhs, consequent));
+                parenthesize(text, IMPLICATION, context)
+            },
+            &Formula::Biconditional(box ref lhs, box ref rhs)     => {
+                let text = format!("{} <-> {}", self.print_formula(lhs, ANTECEDENT), self.print_formula(rhs, BICONSEQUENT));
+                parenthesize(text, BICONDITIONAL, context)
+            },
+            &Formula::UniversalQ(var, ref itype, box ref form)    => {
+                let text = self.print_binder("forall", var, itype, form, FORMULA);
+                parenthesize(text, QUANTIFIER, context)
+            },
+            &Formula::ExistentialQ(var, ref itype, box ref form)  => {
+                let text = self.print_binder("exists", var, itype, form, FORMULA);
+                parenthesize(text, QUANTIFIER, context)
+            },
+            &Formula::Abstraction(var, ref itype, box ref form)   => {
+                let text = self.print_binder("lambda", var, itype, form, ABSTRACTION);
+                parenthesize(text, ABSTRACTION, context)
+            },
+        }
+    }
+
+    fn print_meta_type(&mut self, mtype: &MetaType) -> String {
+        match mtype {
+            &MetaType::Type                                    => String::from("Type"),
+            &MetaType::Term(ref itype)                         => format!("Term {}", self.print_type(itype, false)),
+            &MetaType::Formula(ref arg_types)                  => {
+                arg_types.iter().rev().fold(String::from("Formula"), |acc, itype| {
+                    format!("{} {}", acc, self.print_type(itype, true))
+                })
+            },
+            &MetaType::Schema(ref arg_types, box ref ret_type) => {
+                let args: Vec<String> = arg_types.iter().rev().map(|mtype| format!("({})", self.print_meta_type(mtype))).collect();
+                format!("Schema {} to {}", args.join(" "), self.print_meta_type(ret_type))
+            },
+            &MetaType::Poly(ref vars, box ref mtype)           => {
+                let mut symbols = Symbols::default();
+                symbols.meta_type(mtype);
+                let used = symbols.globals.iter().map(|id| self.global_name(id)).collect();
+                let bound: Vec<(String, Option<String>)> = vars.iter().map(|&var| self.bind(var, TYPE_VAR, &used)).collect();
+                let text = bound.iter().fold(String::new(), |acc, &(ref name, _)| format!("{}forall {}. ", acc, name));
+                let text = format!("{}{}", text, self.print_meta_type(mtype));
+                for (&var, (_, old)) in vars.iter().zip(bound).rev() {
+                    self.unbind(var, old);
+                }
+                text
+            },
+        }
+    }
+
+    fn print_schema(&mut self, schema: &FormulaSchema) -> String {
+        match schema {
+            &FormulaSchema::Formula(ref form)                      => self.print_formula(form, FORMULA),
+            &FormulaSchema::Schema(var, ref mtype, box ref schema) => {
+                let mut symbols = Symbols::default();
+                symbols.schema(schema);
+                let used = symbols.globals.iter().map(|id| self.global_name(id)).collect();
+                let mtype_text = self.print_meta_type(mtype);
+                let (name, old) = self.bind(var, meta_var(mtype), &used);
+                let text = format!("schema {} :: {}. {}", name, mtype_text, self.print_schema(schema));
+                self.unbind(var, old);
+                text
+            },
+            &FormulaSchema::Poly(var, box ref schema)              => {
+                let mut symbols = Symbols::default();
+                symbols.schema(schema);
+                let used = symbols.globals.iter().map(|id| self.global_name(id)).collect();
+                let (name, old) = self.bind(var, TYPE_VAR, &used);
+                let text = format!("forall {} :: Type. {}", name, self.print_schema(schema));
+                self.unbind(var, old);
+                text
+            },
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use ast::{Declaration, LocalBindings};
+    use elaborate::Elaborator;
+    use error::{ErrorCollector, SourceInfo};
+    use state::{Bindings, RLangRepr};
+    use syntax;
+
+    const THEORY: &'static str = "
+let Nat :: Type
+let zero :: Term Nat
+let succ :: Term Nat -> Nat
+let le :: Formula Nat Nat
+let P :: Formula
+let Q :: Formula
+let R :: Formula
+";
+
+    fn print(text: &str) -> String {
+        let source = SourceInfo::from_text("<test>", text);
+        let errors = ErrorCollector::new();
+        let mut locals = LocalBindings::new();
+        let mut globals = Bindings::new();
+        let stmts = syntax::parse_program(&source, text);
+        let decls: Vec<Declaration> = Elaborator::new(&mut locals, &mut globals, &source, &errors).program(stmts);
+        assert_eq!(errors.len(), 0, "errors in {:?}", text);
+        decls.iter().map(|decl| decl.repr(&globals)).collect::<Vec<String>>().join("\n")
+    }
+
+    // drops the axioms a data declaration generates and states theorems as axioms, so the output checks again
+    fn restate(printed: &str) -> String {
+        printed.lines()
+            .filter(|line| !line.starts_with("    axiom "))
+            .map(|line| if line.starts_with("theorem ") { line.replacen("theorem ", "axiom ", 1).replacen(" : ", " = ", 1) } else { String::from(line) })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn round_trip(text: &str) -> String {
+        let once = restate(&print(&format!("{}{}", THEORY, text)));
+        let twice = restate(&print(&once));
+        assert_eq!(once, twice);
+        once.lines().skip(THEORY.trim().lines().count()).collect::<Vec<&str>>().join("\n")
+    }
+
+    #[test]
+    fn minimal_parentheses() {
+        assert_eq!(round_trip("axiom I1 = (P -> Q) -> R"), "axiom I1 = (P -> Q) -> R");
+        assert_eq!(round_trip("axiom I2 = P -> (Q -> R)"), "axiom I2 = P -> Q -> R");
+        assert_eq!(round_trip("axiom B1 = (P <-> Q) <-> R"), "axiom B1 = (P <-> Q) <-> R");
+        assert_eq!(round_trip("axiom B2 = P <-> (Q <-> R)"), "axiom B2 = P <-> (Q <-> R)");
+        assert_eq!(round_trip("axiom C1 = (P or Q) and R"), "axiom C1 = (P or Q) and R");
+        assert_eq!(round_trip("axiom C2 = P or (Q and R)"), "axiom C2 = P or Q and R");
+        assert_eq!(round_trip("axiom N1 = not (P and Q)"), "axiom N1 = not (P and Q)");
+        assert_eq!(round_trip("axiom N2 = ¬¬(zero = zero)"), "axiom N2 = not not zero = zero");
+        assert_eq!(round_trip("axiom F = (false -> P) -> false"), "axiom F = (false -> P) -> false");
+    }
+
+    #[test]
+    fn quantifiers_and_binders() {
+        assert_eq!(round_trip("axiom Q1 = (forall x: Nat. le x x) -> P"), "axiom Q1 = (forall x: Nat. le x x) -> P");
+        assert_eq!(round_trip("axiom Q2 = P and (∀x: Nat. le x x) and Q"), "axiom Q2 = P and (forall x: Nat. le x x) and Q");
+        assert_eq!(round_trip("axiom E = exists x. le x zero"), "axiom E = exists x: Nat. le x zero");
+        assert_eq!(round_trip("axiom G = forall zero: Nat. le zero zero"), "axiom G = forall zero: Nat. le zero zero");
+        assert_eq!(round_trip("axiom Num = le 2 zero"), "axiom Num = le (succ (succ zero)) zero");
+    }
+
+    #[test]
+    fn schemas_theorems_and_data() {
+        round_trip("axiom S = schema F :: Formula Nat. F zero -> (forall n. F n -> F (succ n)) -> forall n. F n");
+        round_trip("axiom Poly = forall A :: Type. forall x: A. x = x");
+        assert_eq!(round_trip("theorem T : ∀x. le x x → le x x := given x. assume h : le x x { h }"), "axiom T = forall x: Nat. le x x -> le x x");
+        round_trip("data List = nil | cons Nat List\naxiom L = forall l: List. exists x: Nat. exists t: List. l = nil or l = cons x t");
+    }
+
+    #[test]
+    fn imported_names_are_printed_as_written() {
+        assert_eq!(print("open import prelude.nat\naxiom A = forall x: Nat. add x zero = x"),
+            "axiom A = forall x: Nat. add x zero = x");
+        assert_eq!(print("import prelude.nat as N\naxiom A = forall x: N.Nat. N.add x N.zero = x"),
+            "axiom A = forall x: N.Nat. N.add x N.zero = x");
+        assert_eq!(print("import prelude.nat\nopen import prelude.order\nlet zero :: Term nat.Nat\naxiom A = le zero nat.zero"),
+            "let zero :: Term nat.Nat\naxiom A = le zero nat.zero");
+    }
+}
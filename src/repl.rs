@@ -0,0 +1,313 @@
+use std::io;
+use std::io::prelude::*;
+use std::path::PathBuf;
+
+use ast::*;
+use elaborate::Elaborator;
+use error;
+use error::{ErrorCollector, ErrorFormat, ErrorSink, FileLocation, SourceInfo};
+use lexer::{LexicalError, Lexer, Tok};
+use module;
+use syntax;
+
+
+const HELP: &'static str = "\
+Enter `let`, `data`, `axiom`, `theorem`, `proof` or `import` statements to extend the environment.
+An input that fails to check is rejected as a whole and leaves the environment unchanged.
+
+commands:
+    :type <expr>     print the metalogical type of a term, formula or name
+    :print <name>    print the declaration of a name
+    :env             print every declaration in the environment
+    :undo            remove the declarations of the last accepted input
+    :load <file>     check a file and add its declarations to the environment
+    :help            print this message
+    :quit            leave the REPL";
+
+
+pub struct Repl {
+    locals: LocalBindings,
+    globals: Bindings,
+    err_dest: ErrorSink,
+    history: Vec<Vec<Declaration>>,
+    inputs: usize,
+}
+
+impl Repl {
+    pub fn new(format: ErrorFormat, color: bool, prelude: bool) -> io::Result<Repl> {
+        let mut globals = Bindings::new();
+        globals.add_search_dir(PathBuf::from("."));
+        let err_dest = ErrorSink::new(format, color);
+        if prelude {
+            let errors = ErrorCollector::new();
+            module::import_prelude(&mut globals, &errors, &FileLocation::new("<prelude>", 0, 0));
+            err_dest.flush(&mut io::stdout(), &errors, &SourceInfo::from_text("<prelude>", ""))?;
+        }
+        Ok(Repl {
+            locals: LocalBindings::new(),
+            globals: globals,
+            err_dest: err_dest,
+            history: Vec::new(),
+            inputs: 0,
+        })
+    }
+
+    pub fn run(&mut self) -> io::Result<()> {
+        let stdin = io::stdin();
+        let mut lines = stdin.lock().lines();
+        loop {
+            prompt("rlang> ");
+            let mut input = match lines.next() {
+                Some(Ok(line)) => line,
+                _              => break,
+            };
+            while needs_more(&input) {
+                prompt("  ...> ");
+                match lines.next() {
+                    Some(Ok(line)) => {
+                        input.push('\n');
+                        input.push_str(&line);
+                    },
+                    _              => break,
+                }
+            }
+            if !self.execute(input.trim(), &mut io::stdout())? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn execute<W: Write>(&mut self, input: &str, out: &mut W) -> io::Result<bool> {
+        if input.is_empty() {
+            return Ok(true);
+        }
+        if input.starts_with(":") {
+            let (cmd, arg) = match input.find(char::is_whitespace) {
+                Some(index) => (&input[..index], input[index..].trim()),
+                None        => (input, ""),
+            };
+            match cmd {
+                ":quit" | ":q"  => return Ok(false),
+                ":help" | ":h"  => writeln!(out, "{}", HELP)?,
+                ":type" | ":t"  => self.type_of(arg, out)?,
+                ":print" | ":p" => self.print(arg, out)?,
+                ":env" | ":e"   => self.env(out)?,
+                ":undo" | ":u"  => self.undo(out)?,
+                ":load" | ":l"  => self.load(arg, out)?,
+                _               => writeln!(out, "unknown command `{}`; type :help for a list of commands", cmd)?,
+            }
+        } else {
+            self.inputs += 1;
+            let source = SourceInfo::from_text(&format!("<input {}>", self.inputs), input);
+            if self.commit(&source, input, out)? {
+                for decl in self.history.last().unwrap().iter() {
+                    writeln!(out, "{}", decl.repr(&self.globals))?;
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    fn commit<W: Write>(&mut self, source: &SourceInfo, text: &str, out: &mut W) -> io::Result<bool> {
+        let errors = ErrorCollector::new();
+        self.globals.checkpoint();
+        let stmts = syntax::parse_program(source, text);
+        let decls = Elaborator::new(&mut self.locals, &mut self.globals, source, &errors).program(stmts);
+        if errors.len() == 0 {
+            self.history.push(decls);
+            return Ok(true);
+        }
+        self.err_dest.flush(out, &errors, source)?;
+        self.locals = LocalBindings::new();
+        self.globals.rollback();
+        writeln!(out, "input rejected; environment unchanged")?;
+        Ok(false)
+    }
+
+    fn type_of<W: Write>(&mut self, arg: &str, out: &mut W) -> io::Result<()> {
+        if let Some(mtype) = self.globals.lookup(arg).and_then(|id| self.globals.get_type(&id)) {
+            writeln!(out, "{} :: {}", arg, mtype.repr(&self.globals))?;
+            return Ok(());
+        }
+        let source = SourceInfo::from_text("<type>", arg);
+        let errors = ErrorCollector::new();
+        match syntax::parse_formula(&source, arg).and_then(|expr| Elaborator::new(&mut self.locals, &mut self.globals, &source, &errors).expr(expr)) {
+            Ok(expr)   => writeln!(out, "{} :: {}", arg, expr.mtype(&self.globals).repr(&self.globals))?,
+            Err(error) => errors.report(error),
+        }
+        self.err_dest.flush(out, &errors, &source)?;
+        self.locals = LocalBindings::new();
+        Ok(())
+    }
+
+    fn print<W: Write>(&self, arg: &str, out: &mut W) -> io::Result<()> {
+        let id = self.globals.lookup(arg);
+        let mut found = false;
+        for decl in self.history.iter().flat_map(|decls| decls.iter()) {
+            if id == Some(decl.id()) || id.and_then(|id| self.globals.defined_by(&id)) == Some(decl.id()) {
+                writeln!(out, "{}", decl.repr(&self.globals))?;
+                found = true;
+            }
+        }
+        if !found {
+            writeln!(out, "no binding found for `{}`", arg)?;
+        }
+        Ok(())
+    }
+
+    fn env<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        if self.history.iter().all(|decls| decls.is_empty()) {
+            writeln!(out, "the environment is empty")?;
+        }
+        for decl in self.history.iter().flat_map(|decls| decls.iter()) {
+            writeln!(out, "{}", decl.repr(&self.globals))?;
+        }
+        Ok(())
+    }
+
+    fn undo<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        match self.history.pop() {
+            Some(decls) => {
+                self.locals = LocalBindings::new();
+                self.globals.rollback();
+                writeln!(out, "removed {} declarations", decls.len())
+            },
+            None        => writeln!(out, "nothing to undo"),
+        }
+    }
+
+    fn load<W: Write>(&mut self, filename: &str, out: &mut W) -> io::Result<()> {
+        let context = FileLocation::new("<repl>", 0, 0);
+        match error::read_source(filename, &context) {
+            Ok((source, text)) => {
+                if self.commit(&source, &text, out)? {
+                    writeln!(out, "loaded {} declarations from {}", self.history.last().unwrap().len(), filename)?;
+                }
+                Ok(())
+            },
+            Err(error)         => self.err_dest.report_noexcerpt(out, &error),
+        }
+    }
+}
+
+
+fn prompt(text: &str) {
+    print!("{}", text);
+    io::stdout().flush().unwrap();
+}
+
+
+fn needs_more(input: &str) -> bool {
+    if input.trim_start().starts_with(":") {
+        return false;
+    }
+    let mut lexer = Lexer::new(input);
+    let mut depth = 0i64;
+    let mut last = None;
+    for (_, tok, _) in (&mut lexer).filter_map(|token| token.ok()) {
+        match tok {
+            Tok::LParen | Tok::LBracket | Tok::LBrace => depth += 1,
+            Tok::RParen | Tok::RBracket | Tok::RBrace => depth -= 1,
+            _                                         => (),
+        }
+        if tok != Tok::Eof {
+            last = Some(tok);
+        }
+    }
+    let unterminated = lexer.take_errors().iter().any(|error| match error {
+        &LexicalError::UnterminatedComment(_) => true,
+        _                                     => false,
+    });
+    let continues = match last {
+        Some(Tok::ColonEq) | Some(Tok::Equals) | Some(Tok::Arrow) | Some(Tok::Dot) | Some(Tok::Dollar) | Some(Tok::Bar) => true,
+        _                                                                                                                  => false,
+    };
+    depth > 0 || unterminated || continues
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn execute(repl: &mut Repl, input: &str) -> String {
+        let mut out = Vec::new();
+        assert!(repl.execute(input, &mut out).unwrap());
+        String::from_utf8(out).unwrap()
+    }
+
+    fn session() -> Repl {
+        let mut repl = Repl::new(ErrorFormat::Short, false, false).unwrap();
+        execute(&mut repl, "let Nat :: Type\nlet zero :: Term Nat");
+        repl
+    }
+
+    #[test]
+    fn rejected_input_leaves_the_environment_unchanged() {
+        let mut repl = session();
+        let env = execute(&mut repl, ":env");
+        assert_eq!(execute(&mut repl, "let one :: Term Nat\nlet two :: Term Nope"),
+            "At <input 2>:2:17:\nNoBinding : no binding found for `Nope`\ninput rejected; environment unchanged\n");
+        assert_eq!(execute(&mut repl, ":env"), env);
+        assert_eq!(execute(&mut repl, ":type one"), "At <type>:1:1:\nNoBinding : no binding found for `one`\n");
+        assert_eq!(execute(&mut repl, "let one :: Term Nat"), "let one :: Term Nat\n");
+        assert_eq!(execute(&mut repl, ":load /nonexistent/missing.rl"),
+            "At <repl>:1:1:\nFileOpenFailure : could not open source file \"/nonexistent/missing.rl\" because of error `NotFound`\n");
+    }
+
+    #[test]
+    fn undo_removes_the_last_input() {
+        let mut repl = session();
+        let env = execute(&mut repl, ":env");
+        execute(&mut repl, "let one :: Term Nat");
+        assert_eq!(execute(&mut repl, ":undo"), "removed 1 declarations\n");
+        assert_eq!(execute(&mut repl, ":env"), env);
+        execute(&mut repl, "data Color = red | blue");
+        assert_eq!(execute(&mut repl, ":type red"), "red :: Term Color\n");
+        assert_eq!(execute(&mut repl, ":undo"), "removed 1 declarations\n");
+        assert_eq!(execute(&mut repl, ":type red"), "At <type>:1:1:\nNoBinding : no binding found for `red`\n");
+        assert_eq!(execute(&mut repl, "import prelude.nat as N"), "");
+        assert_eq!(execute(&mut repl, ":type N.zero"), "N.zero :: Term N.Nat\n");
+        assert_eq!(execute(&mut repl, ":undo"), "removed 0 declarations\n");
+        assert!(!repl.globals.is_loaded("prelude.nat"));
+        assert_eq!(execute(&mut repl, ":type N.zero"), "At <type>:1:1:\nNoBinding : no binding found for `N.zero`\n");
+        assert_eq!(execute(&mut repl, ":env"), env);
+        execute(&mut repl, ":undo");
+        assert_eq!(execute(&mut repl, ":undo"), "nothing to undo\n");
+        assert_eq!(execute(&mut repl, ":env"), "the environment is empty\n");
+    }
+
+    #[test]
+    fn type_of_names_and_formulas() {
+        let mut repl = session();
+        assert_eq!(execute(&mut repl, ":type zero"), "zero :: Term Nat\n");
+        assert_eq!(execute(&mut repl, ":type Nat"), "Nat :: Type\n");
+        assert_eq!(execute(&mut repl, ":type zero = zero"), "zero = zero :: Formula\n");
+        assert_eq!(execute(&mut repl, ":type lambda x: Nat. x = zero"), "lambda x: Nat. x = zero :: Formula Nat\n");
+        assert_eq!(execute(&mut repl, ":type zero = nope"), "At <type>:1:8:\nNoBinding : no binding found for `nope`\n");
+    }
+
+    #[test]
+    fn print_declarations() {
+        let mut repl = session();
+        execute(&mut repl, "data Color = red | blue");
+        assert_eq!(execute(&mut repl, ":print zero"), "let zero :: Term Nat\n");
+        assert_eq!(execute(&mut repl, ":print red"), execute(&mut repl, ":print Color"));
+        assert_eq!(execute(&mut repl, ":print zero = zero"), "no binding found for `zero = zero`\n");
+    }
+
+    #[test]
+    fn incomplete_inputs_need_more() {
+        assert!(needs_more("theorem T : P := (and_intro p"));
+        assert!(needs_more("axiom A = forall x: Nat. [P"));
+        assert!(needs_more("theorem T : P := assume h : P {"));
+        assert!(needs_more("theorem T : P :="));
+        assert!(needs_more("let f :: Term Nat ->"));
+        assert!(needs_more("let Nat :: Type #| a comment"));
+        assert!(!needs_more("let Nat :: Type #| a comment |#"));
+        assert!(!needs_more("theorem T : P -> P := assume h : P { h }"));
+        assert!(!needs_more(":type (zero"));
+        assert!(!needs_more(""));
+    }
+}
@@ -1,264 +1,920 @@
-#![allow(dead_code)]
-
-use std::borrow::{Borrow, ToOwned};
-use std::boxed::Box;
-use std::collections::HashMap;
-use std::hash::Hash;
-
-use error;
-use error::FileLocation;
-use error::Error;
-use error::ErrorKind::*;
-use types::*;
-
-
-pub struct IDTracker<T>
-where T: Hash + Eq + Clone {
-    next_id: usize,
-    name_to_ident: HashMap<T, usize>,
-    ident_to_name: HashMap<usize, T>,
-}
-
-impl<T> IDTracker<T>
-where T: Hash + Eq + Clone {
-    pub fn new() -> IDTracker<T> {
-        IDTracker {
-            next_id: 0usize,
-            name_to_ident: HashMap::new(),
-            ident_to_name: HashMap::new(),
-        }
-    }
-
-    fn new_identifier(&mut self, name: T) -> usize {
-        let id = self.next_id.clone();
-        self.name_to_ident.insert(name.clone(), id);
-        self.ident_to_name.insert(id, name);
-        self.next_id += 1;
-        id
-    }
-
-    pub fn get_id_nomake<Q: ?Sized>(&self, name: &Q) -> Option<&usize>
-    where Q: Borrow<T> {
-        self.name_to_ident.get(name.borrow())
-    }
-
-    pub fn get_id<Q: ?Sized>(&mut self, name: &Q) -> usize
-    where Q: Borrow<T> {
-        let name_copy = name.to_owned();
-        if let Some(id) = self.name_to_ident.get(name_copy.borrow()) {
-            return id.clone();
-        }
-        self.new_identifier(name_copy.borrow().clone())
-    }
-
-    pub fn get_name(&self, id: &usize) -> Option<&T> {
-        self.ident_to_name.get(id).clone()
-    }
-
-    pub fn remove(&mut self, id: &usize) {
-        let name = self.ident_to_name.remove(id).unwrap();
-        self.name_to_ident.remove(&name);
-        self.next_id -= 1;
-    }
-}
-
-
-pub enum ChainMap<K, V> {
-    Child(HashMap<K, V>, Box<ChainMap<K, V>>),
-    Base(HashMap<K, V>),
-}
-
-impl<K, V> ChainMap<K, V>
-where K: Eq + Hash {
-    pub fn new() -> ChainMap<K, V> {
-        ChainMap::Base(HashMap::new())
-    }
-
-    pub fn new_child(self) -> ChainMap<K, V> {
-        ChainMap::Child(HashMap::new(), Box::new(self))
-    }
-
-    pub fn parent(self) -> Result<ChainMap<K, V>, &'static str> {
-        match self {
-            ChainMap::Child(_, box parent) => Ok(parent),
-            ChainMap::Base(_)              => Err("cannot take parent of base map"),
-        }
-    }
-
-    pub fn is_empty(&self) -> bool {
-        match self {
-            &ChainMap::Child(ref map, box ref parent) => map.is_empty() && parent.is_empty(),
-            &ChainMap::Base(ref map)                  => map.is_empty(),
-        }
-    }
-
-    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&V>
-    where K: Borrow<Q>,
-          Q: Hash + Eq {
-        match self {
-            &ChainMap::Child(ref map, box ref parent) => match map.get(key) {
-                Some(val) => Some(val),
-                None      => parent.get(key),
-            },
-            &ChainMap::Base(ref map)                  => map.get(key),
-        }
-    }
-
-    pub fn insert(&mut self, key: K, val: V) -> Option<V> {
-        match self {
-            &mut ChainMap::Child(ref mut map, _) => map.insert(key, val),
-            &mut ChainMap::Base(ref mut map)     => map.insert(key, val),
-        }
-    }
-}
-
-
-pub struct Bindings {
-    next_local: usize,
-    id_table: IDTracker<String>,
-    val_table: ChainMap<usize, (MetaType, Option<MetaValue>)>,
-    // base_types: ChainMap<usize, InternalType>,
-    // term_types: ChainMap<usize, InternalType>,
-    // form_types: ChainMap<usize, MetaType>,
-    proven_wffs: ChainMap<usize, FormulaSchema>,
-}
-
-impl Bindings {
-    pub fn new() -> Bindings {
-        Bindings {
-            next_local: 0usize,
-            id_table: IDTracker::new(),
-            val_table: ChainMap::new(),
-            // base_types: ChainMap::new(),
-            // term_types: ChainMap::new(),
-            // form_types: ChainMap::new(),
-            proven_wffs: ChainMap::new(),
-        }
-    }
-
-    pub fn new_local(&mut self) -> usize {
-        let res = self.next_local;
-        self.next_local += 1;
-        res
-    }
-
-    pub fn new_child(self) -> Bindings {
-        Bindings {
-            next_local: self.next_local,
-            id_table: self.id_table,
-            val_table: self.val_table.new_child(),
-            // base_types: self.base_types.new_child(),
-            // term_types: self.term_types.new_child(),
-            // form_types: self.form_types.new_child(),
-            proven_wffs: self.proven_wffs.new_child(),
-        }
-    }
-
-    pub fn parent(self) -> Bindings {
-        Bindings {
-            next_local: self.next_local,
-            id_table: self.id_table,
-            val_table: self.val_table.parent().unwrap(),
-            // base_types: self.base_types.parent().unwrap(),
-            // term_types: self.term_types.parent().unwrap(),
-            // form_types: self.form_types.parent().unwrap(),
-            proven_wffs: self.proven_wffs.parent().unwrap(),
-        }
-    }
-
-    pub fn get_id(&mut self, name: &str) -> usize {
-        self.id_table.get_id(&name.to_owned())
-    }
-
-    pub fn get_name(&self, id: &usize) -> Option<&String> {
-        self.id_table.get_name(id)
-    }
-
-    pub fn get_type(&self, id: &usize) -> Option<MetaType> {
-        if let Some(&(ref mtype, _)) = self.val_table.get(id) { Some(mtype.clone()) } else { None }
-    }
-
-    pub fn get_value(&self, id: &usize) -> Option<MetaValue> {
-        if let Some(&(_, ref itype_q)) = self.val_table.get(id) { itype_q.clone() } else { None }
-    }
-
-    pub fn get_type_value(&self, id: &usize) -> Option<(MetaType, Option<MetaValue>)> {
-        if let Some(&ref pair) = self.val_table.get(id) { Some(pair.clone()) } else { None }
-    }
-
-    pub fn get_theorem(&self, id: &usize) -> Option<&FormulaSchema> {
-        self.proven_wffs.get(id)
-    }
-
-    pub fn insert_object_noval(&mut self, id: usize, mtype: MetaType, context: &FileLocation) -> error::Result<usize> {
-        if let None = self.val_table.insert(id, (mtype, None)) {
-            Ok(id)
-        } else {
-            Err(Error::new(BindingExists { name: self.get_name(&id).unwrap().clone() }, context))
-        }
-    }
-
-    pub fn insert_object(&mut self, id: usize, mtype: MetaType, mval: MetaValue, context: &FileLocation) -> error::Result<usize> {
-        if let None = self.val_table.insert(id, (mtype, Some(mval))) {
-            Ok(id)
-        } else {
-            Err(Error::new(BindingExists { name: self.get_name(&id).unwrap().clone() }, context))
-        }
-    }
-
-    pub fn insert_object_anytype(&mut self, id: usize, mtype: MetaType, mval: MetaValue, context: &FileLocation) -> error::Result<usize> {
-        if let Some((_, None)) = self.val_table.insert(id, (mtype, Some(mval))) {
-            Ok(id)
-        } else {
-            Err(Error::new(BindingExists { name: self.get_name(&id).unwrap().clone() }, context))
-        }
-    }
-
-    pub fn insert_theorem(&mut self, id: usize, stmt: FormulaSchema, context: &FileLocation) -> error::Result<usize> {
-        if let Some(_) = self.proven_wffs.insert(id, stmt) {
-            Err(Error::new(BindingExists { name: self.get_name(&id).unwrap().clone() }, context))
-        } else {
-            Ok(id)
-        }
-    }
-}
-
-
-pub trait RLangRepr {
-    fn repr(&self, globals: &Bindings) -> String;
-}
-
-impl RLangRepr for InternalType {
-    fn repr(&self, globals: &Bindings) -> String {
-        match self {
-            &InternalType::Named(name)                              => {
-                match name {
-                    Ident::Global(id) => globals.get_name(&id).unwrap().clone(),
-                    Ident::Local(local_id) => format!("#{}", local_id),
-                }
-            },
-            &InternalType::Func(box ref arg_type, box ref ret_type) => format!("({} -> {})", arg_type.repr(globals), ret_type.repr(globals)),
-        }
-    }
-}
-
-impl RLangRepr for MetaType {
-    fn repr(&self, globals: &Bindings) -> String {
-        match self {
-            &MetaType::Type                                    => String::from("Type"),
-            &MetaType::Term(ref itype)                         => {
-                format!("(Term {})", itype.repr(globals))
-            },
-            &MetaType::Formula(ref arg_types)                  => {
-                format!("(Formula {})", arg_types.iter().rev().fold(String::new(), |acc, ref itype| {
-                    format!("{} {}", acc, itype.repr(globals))
-                }))
-            },
-            &MetaType::Schema(ref arg_types, box ref ret_type) => {
-                format!("(Schema {} to {})", arg_types.iter().rev().fold(String::new(), |acc, ref mtype| {
-                    format!("{} {}", acc, mtype.repr(globals))
-                }), ret_type.repr(globals))
-            },
-        }
-    }
-}
\ No newline at end of file
+#![allow(dead_code)]
+
+use std::borrow::{Borrow, ToOwned};
+use std::boxed::Box;
+use std::cell::RefCell;
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map;
+use std::hash::Hash;
+use std::iter;
+use std::mem;
+use std::path::PathBuf;
+
+use error;
+use error::FileLocation;
+use error::Error;
+use error::ErrorKind::*;
+use pretty::Printer;
+use types::*;
+
+
+pub struct IDTracker<T>
+where T: Hash + Eq + Clone {
+    next_id: usize,
+    name_to_ident: HashMap<T, usize>,
+    ident_to_name: HashMap<usize, T>,
+}
+
+impl<T> IDTracker<T>
+where T: Hash + Eq + Clone {
+    pub fn new() -> IDTracker<T> {
+        IDTracker {
+            next_id: 0usize,
+            name_to_ident: HashMap::new(),
+            ident_to_name: HashMap::new(),
+        }
+    }
+
+    fn new_identifier(&mut self, name: T) -> usize {
+        let id = self.next_id.clone();
+        self.name_to_ident.insert(name.clone(), id);
+        self.ident_to_name.insert(id, name);
+        self.next_id += 1;
+        id
+    }
+
+    pub fn get_id_nomake<Q: ?Sized>(&self, name: &Q) -> Option<&usize>
+    where Q: Borrow<T> {
+        self.name_to_ident.get(name.borrow())
+    }
+
+    pub fn get_id<Q: ?Sized>(&mut self, name: &Q) -> usize
+    where Q: Borrow<T> {
+        let name_copy = name.to_owned();
+        if let Some(id) = self.name_to_ident.get(name_copy.borrow()) {
+            return id.clone();
+        }
+        self.new_identifier(name_copy.borrow().clone())
+    }
+
+    pub fn get_name(&self, id: &usize) -> Option<&T> {
+        self.ident_to_name.get(id).clone()
+    }
+
+    pub fn iter(&self) -> hash_map::Iter<'_, usize, T> {
+        self.ident_to_name.iter()
+    }
+
+    pub fn remove(&mut self, id: &usize) {
+        let name = self.ident_to_name.remove(id).unwrap();
+        self.name_to_ident.remove(&name);
+        self.next_id -= 1;
+    }
+}
+
+
+pub enum ChainMap<K, V> {
+    Child(HashMap<K, V>, Box<ChainMap<K, V>>),
+    Base(HashMap<K, V>),
+}
+
+impl<K, V> ChainMap<K, V>
+where K: Eq + Hash {
+    pub fn new() -> ChainMap<K, V> {
+        ChainMap::Base(HashMap::new())
+    }
+
+    pub fn new_child(self) -> ChainMap<K, V> {
+        ChainMap::Child(HashMap::new(), Box::new(self))
+    }
+
+    pub fn parent(self) -> Result<ChainMap<K, V>, &'static str> {
+        match self {
+            ChainMap::Child(_, box parent) => Ok(parent),
+            ChainMap::Base(_)              => Err("cannot take parent of base map"),
+        }
+    }
+
+    pub fn merge(self) -> Result<ChainMap<K, V>, &'static str> {
+        match self {
+            ChainMap::Child(map, box mut parent) => {
+                for (key, val) in map.into_iter() {
+                    parent.insert(key, val);
+                }
+                Ok(parent)
+            },
+            ChainMap::Base(_)                    => Err("cannot merge base map into its parent"),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            &ChainMap::Child(ref map, box ref parent) => map.is_empty() && parent.is_empty(),
+            &ChainMap::Base(ref map)                  => map.is_empty(),
+        }
+    }
+
+    pub fn contains_key<Q: ?Sized>(&self, key: &Q) -> bool
+    where K: Borrow<Q>,
+          Q: Hash + Eq {
+        self.get(key).is_some()
+    }
+
+    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&V>
+    where K: Borrow<Q>,
+          Q: Hash + Eq {
+        match self {
+            &ChainMap::Child(ref map, box ref parent) => match map.get(key) {
+                Some(val) => Some(val),
+                None      => parent.get(key),
+            },
+            &ChainMap::Base(ref map)                  => map.get(key),
+        }
+    }
+
+    pub fn insert(&mut self, key: K, val: V) -> Option<V> {
+        match self {
+            &mut ChainMap::Child(ref mut map, _) => map.insert(key, val),
+            &mut ChainMap::Base(ref mut map)     => map.insert(key, val),
+        }
+    }
+}
+
+
+fn split_qualified(name: &str) -> (&str, &str) {
+    match name.rfind('.') {
+        Some(dot) => (&name[..dot], &name[dot + 1..]),
+        None      => ("", name),
+    }
+}
+
+
+#[derive(Clone)]
+enum TypeOrigin {
+    Binder(usize, FileLocation),
+    Instance(usize, FileLocation),
+    Part(usize),
+}
+
+
+#[derive(Clone)]
+pub struct ModuleScope {
+    prefix: String,
+    aliases: HashMap<String, String>,
+    opens: Vec<(String, Option<Vec<String>>)>,
+}
+
+impl ModuleScope {
+    pub fn new(prefix: &str) -> ModuleScope {
+        ModuleScope {
+            prefix: String::from(prefix),
+            aliases: HashMap::new(),
+            opens: Vec::new(),
+        }
+    }
+
+    fn qualify(&self, name: &str) -> String {
+        if self.prefix.is_empty() {
+            String::from(name)
+        } else {
+            format!("{}.{}", self.prefix, name)
+        }
+    }
+}
+
+
+pub struct Bindings {
+    next_local: usize,
+    scope_depth: usize,
+    id_table: IDTracker<String>,
+    val_table: ChainMap<usize, (MetaType, Option<MetaValue>)>,
+    // base_types: ChainMap<usize, InternalType>,
+    // term_types: ChainMap<usize, InternalType>,
+    // form_types: ChainMap<usize, MetaType>,
+    proven_wffs: ChainMap<usize, FormulaSchema>,
+    datatypes: ChainMap<usize, DataType>,
+    definitions: ChainMap<usize, usize>,
+    sites: ChainMap<usize, FileLocation>,
+    poisoned: ChainMap<usize, FileLocation>,
+    modules: ChainMap<String, String>,
+    module_scopes: Vec<ModuleScope>,
+    saved_scopes: Vec<Vec<ModuleScope>>,
+    search_path: Vec<PathBuf>,
+    flexible: RefCell<HashMap<usize, Option<InternalType>>>,
+    origins: HashMap<usize, TypeOrigin>,
+    local_names: HashMap<usize, usize>,
+}
+
+impl Bindings {
+    pub fn new() -> Bindings {
+        Bindings {
+            next_local: 0usize,
+            scope_depth: 0usize,
+            id_table: IDTracker::new(),
+            val_table: ChainMap::new(),
+            // base_types: ChainMap::new(),
+            // term_types: ChainMap::new(),
+            // form_types: ChainMap::new(),
+            proven_wffs: ChainMap::new(),
+            datatypes: ChainMap::new(),
+            definitions: ChainMap::new(),
+            sites: ChainMap::new(),
+            poisoned: ChainMap::new(),
+            modules: ChainMap::new(),
+            module_scopes: vec![ModuleScope::new("")],
+            saved_scopes: Vec::new(),
+            search_path: Vec::new(),
+            flexible: RefCell::new(HashMap::new()),
+            origins: HashMap::new(),
+            local_names: HashMap::new(),
+        }
+    }
+
+    pub fn new_local(&mut self) -> usize {
+        let res = self.next_local;
+        self.next_local += 1;
+        res
+    }
+
+    pub fn named_local(&mut self, id: usize) -> usize {
+        let local_id = self.new_local();
+        self.local_names.insert(local_id, id);
+        local_id
+    }
+
+    pub fn rename_local(&mut self, local_id: usize) -> usize {
+        match self.local_names.get(&local_id).cloned() {
+            Some(id) => self.named_local(id),
+            None     => self.new_local(),
+        }
+    }
+
+    pub fn local_name(&self, local_id: &usize) -> Option<&str> {
+        self.local_names.get(local_id).and_then(|id| self.get_name(id)).map(|name| split_qualified(name).1)
+    }
+
+    pub fn fresh_type(&mut self) -> InternalType {
+        let local_id = self.new_local();
+        self.flexible.borrow_mut().insert(local_id, None);
+        InternalType::Named(Ident::Local(local_id))
+    }
+
+    fn fresh_type_from(&mut self, origin: TypeOrigin) -> InternalType {
+        let itype = self.fresh_type();
+        if let InternalType::Named(Ident::Local(local_id)) = itype {
+            self.origins.insert(local_id, origin);
+        }
+        itype
+    }
+
+    pub fn function_type(&mut self, itype: &InternalType) -> InternalType {
+        let itype = self.shallow_type(itype);
+        if let Some(var) = self.unbound_var(&itype) {
+            let root = self.origin_root(&var);
+            let func = InternalType::Func(Box::new(self.fresh_type()), Box::new(self.fresh_type()));
+            if let Some(root) = root {
+                for local_id in func.vars() {
+                    self.origins.insert(local_id, TypeOrigin::Part(root));
+                }
+            }
+            self.bind_type(var, &func);
+            func
+        } else {
+            itype
+        }
+    }
+
+    fn origin_root(&self, local_id: &usize) -> Option<usize> {
+        match self.origins.get(local_id) {
+            Some(&TypeOrigin::Part(root)) => Some(root),
+            Some(_)                       => Some(*local_id),
+            None                          => None,
+        }
+    }
+
+    pub fn binder_type(&mut self, id: usize, location: &FileLocation) -> InternalType {
+        self.fresh_type_from(TypeOrigin::Binder(id, location.clone()))
+    }
+
+    pub fn instantiate(&mut self, id: usize, mtype: MetaType, location: &FileLocation) -> (MetaType, Vec<InternalType>) {
+        if let MetaType::Poly(vars, box mtype) = mtype {
+            let mut instance = Vec::new();
+            let mtype = vars.into_iter().fold(mtype, |mtype, var| {
+                let fresh = self.fresh_type_from(TypeOrigin::Instance(id, location.clone()));
+                instance.push(fresh.clone());
+                mtype.substitute_type(var, &fresh)
+            });
+            (mtype, instance)
+        } else {
+            (mtype, Vec::new())
+        }
+    }
+
+    fn bound_type(&self, local_id: &usize) -> Option<InternalType> {
+        if let Some(&Some(ref itype)) = self.flexible.borrow().get(local_id) { Some(itype.clone()) } else { None }
+    }
+
+    fn unbound_var(&self, itype: &InternalType) -> Option<usize> {
+        match itype {
+            &InternalType::Named(Ident::Local(local_id)) => {
+                if let Some(&None) = self.flexible.borrow().get(&local_id) { Some(local_id) } else { None }
+            },
+            _                                            => None,
+        }
+    }
+
+    pub fn resolve_type(&self, itype: &InternalType) -> InternalType {
+        match itype {
+            &InternalType::Named(Ident::Local(local_id))            => {
+                match self.bound_type(&local_id) {
+                    Some(bound) => self.resolve_type(&bound),
+                    None        => itype.clone(),
+                }
+            },
+            &InternalType::Named(Ident::Global(_))                  => itype.clone(),
+            &InternalType::Func(box ref arg_type, box ref ret_type) => {
+                InternalType::Func(Box::new(self.resolve_type(arg_type)), Box::new(self.resolve_type(ret_type)))
+            },
+        }
+    }
+
+    pub fn resolve_term(&self, term: &Term) -> Term {
+        match term {
+            &Term::Symbol(id, ref itypes)                 => Term::Symbol(id, itypes.iter().map(|itype| self.resolve_type(itype)).collect()),
+            &Term::Application(box ref func, box ref arg) => Term::Application(Box::new(self.resolve_term(func)), Box::new(self.resolve_term(arg))),
+        }
+    }
+
+    pub fn resolve_formula(&self, form: Formula) -> Formula {
+        form.free_vars().into_iter().fold(form, |form, var| {
+            match self.bound_type(&var) {
+                Some(bound) => form.substitute_type(var, &self.resolve_type(&bound)),
+                None        => form,
+            }
+        })
+    }
+
+    fn shallow_type(&self, itype: &InternalType) -> InternalType {
+        match itype {
+            &InternalType::Named(Ident::Local(local_id)) => {
+                match self.bound_type(&local_id) {
+                    Some(bound) => self.shallow_type(&bound),
+                    None        => itype.clone(),
+                }
+            },
+            _                                            => itype.clone(),
+        }
+    }
+
+    pub fn unify(&self, lhs: &InternalType, rhs: &InternalType) -> bool {
+        let lhs_ = self.shallow_type(lhs);
+        let rhs_ = self.shallow_type(rhs);
+        if let Some(var) = self.unbound_var(&rhs_) {
+            return self.bind_type(var, lhs);
+        }
+        if let Some(var) = self.unbound_var(&lhs_) {
+            return self.bind_type(var, rhs);
+        }
+        match (lhs_, rhs_) {
+            (InternalType::Func(box arg_type, box ret_type), InternalType::Func(box arg_type_, box ret_type_)) => {
+                self.unify(&arg_type, &arg_type_) && self.unify(&ret_type, &ret_type_)
+            },
+            (lhs_, rhs_)                                                                                    => lhs_ == rhs_,
+        }
+    }
+
+    fn bind_type(&self, var: usize, itype: &InternalType) -> bool {
+        let resolved = self.resolve_type(itype);
+        if resolved == InternalType::Named(Ident::Local(var)) {
+            true
+        } else if resolved.vars().contains(&var) {
+            false
+        } else {
+            self.flexible.borrow_mut().insert(var, Some(itype.clone()));
+            true
+        }
+    }
+
+    pub fn unify_meta(&self, lhs: &MetaType, rhs: &MetaType) -> bool {
+        match (lhs, rhs) {
+            (&MetaType::Term(ref itype), &MetaType::Term(ref itype_))               => self.unify(itype, itype_),
+            (&MetaType::Formula(ref arg_types), &MetaType::Formula(ref arg_types_)) => {
+                arg_types.len() == arg_types_.len() &&
+                    arg_types.iter().zip(arg_types_.iter()).all(|(itype, itype_)| self.unify(itype, itype_))
+            },
+            (lhs, rhs)                                                              => lhs == rhs,
+        }
+    }
+
+    fn unify_instance(&self, itypes: &[InternalType], itypes_: &[InternalType]) -> bool {
+        itypes.len() == itypes_.len() && itypes.iter().zip(itypes_.iter()).all(|(itype, itype_)| self.unify(itype, itype_))
+    }
+
+    fn unify_term(&self, lhs: &Term, rhs: &Term) -> bool {
+        match (lhs, rhs) {
+            (&Term::Symbol(_, ref itypes), &Term::Symbol(_, ref itypes_))                                 => self.unify_instance(itypes, itypes_),
+            (&Term::Application(box ref func, box ref arg), &Term::Application(box ref func_, box ref arg_)) => {
+                self.unify_term(func, func_) && self.unify_term(arg, arg_)
+            },
+            _                                                                                                => false,
+        }
+    }
+
+    fn unify_formula(&self, lhs: &Formula, rhs: &Formula) -> bool {
+        match (lhs, rhs) {
+            (&Formula::False, &Formula::False)                                                                         => true,
+            (&Formula::Relation(_, ref itypes), &Formula::Relation(_, ref itypes_))                                    => {
+                self.unify_instance(itypes, itypes_)
+            },
+            (&Formula::Application(box ref pred, ref arg), &Formula::Application(box ref pred_, ref arg_))             => {
+                self.unify_formula(pred, pred_) && self.unify_term(arg, arg_)
+            },
+            (&Formula::Equality(ref itype, ref lhs, ref rhs), &Formula::Equality(ref itype_, ref lhs_, ref rhs_))      => {
+                self.unify(itype, itype_) && self.unify_term(lhs, lhs_) && self.unify_term(rhs, rhs_)
+            },
+            (&Formula::Implication(box ref lhs, box ref rhs), &Formula::Implication(box ref lhs_, box ref rhs_))       |
+            (&Formula::Conjunction(box ref lhs, box ref rhs), &Formula::Conjunction(box ref lhs_, box ref rhs_))       |
+            (&Formula::Disjunction(box ref lhs, box ref rhs), &Formula::Disjunction(box ref lhs_, box ref rhs_))       |
+            (&Formula::Biconditional(box ref lhs, box ref rhs), &Formula::Biconditional(box ref lhs_, box ref rhs_))   => {
+                self.unify_formula(lhs, lhs_) && self.unify_formula(rhs, rhs_)
+            },
+            (&Formula::Negation(box ref form), &Formula::Negation(box ref form_))                                      |
+            (&Formula::Negation(box ref form), &Formula::Implication(box ref form_, box Formula::False))               |
+            (&Formula::Implication(box ref form, box Formula::False), &Formula::Negation(box ref form_))               => {
+                self.unify_formula(form, form_)
+            },
+            (&Formula::UniversalQ(_, ref itype, box ref form), &Formula::UniversalQ(_, ref itype_, box ref form_))     |
+            (&Formula::ExistentialQ(_, ref itype, box ref form), &Formula::ExistentialQ(_, ref itype_, box ref form_)) |
+            (&Formula::Abstraction(_, ref itype, box ref form), &Formula::Abstraction(_, ref itype_, box ref form_))   => {
+                self.unify(itype, itype_) && self.unify_formula(form, form_)
+            },
+            _                                                                                                          => false,
+        }
+    }
+
+    pub fn check_inferred(&self, vars: HashSet<usize>, id: usize, location: &FileLocation) -> error::Result<()> {
+        let mut ambiguous: Vec<usize> = vars.into_iter().filter(|var| self.unbound_var(&InternalType::Named(Ident::Local(*var))).is_some()).collect();
+        ambiguous.sort();
+        match ambiguous.first().and_then(|var| self.origin_root(var)).and_then(|root| self.origins.get(&root)) {
+            Some(&TypeOrigin::Binder(id, ref site))   => {
+                let name = self.get_name(&id).unwrap().clone();
+                Err(Error::new(AmbiguousType { name: name.clone() }, site)
+                    .with_help(format!("annotate the binder with its type, as in `{}: T`", name)))
+            },
+            Some(&TypeOrigin::Instance(id, ref site)) => {
+                Err(Error::new(AmbiguousType { name: self.get_name(&id).unwrap().clone() }, site))
+            },
+            _ if ambiguous.len() > 0                  => {
+                Err(Error::new(AmbiguousType { name: self.get_name(&id).unwrap().clone() }, location))
+            },
+            _                                         => Ok(()),
+        }
+    }
+
+    pub fn explain_types(&self, error: Error, itypes: &[&InternalType]) -> Error {
+        let mut binders: Vec<usize> = Vec::new();
+        for itype in itypes.iter() {
+            self.collect_binders(itype, &mut binders);
+        }
+        binders.into_iter().fold(error, |error, local_id| {
+            if let Some(&TypeOrigin::Binder(id, ref site)) = self.origins.get(&local_id) {
+                let itype = self.resolve_type(&InternalType::Named(Ident::Local(local_id)));
+                error.with_label(site, format!("`{}` is inferred to have type `{}`", self.get_name(&id).unwrap(), itype.repr(self)))
+            } else {
+                error
+            }
+        })
+    }
+
+    fn collect_binders(&self, itype: &InternalType, binders: &mut Vec<usize>) {
+        match itype {
+            &InternalType::Named(Ident::Local(local_id))            => {
+                if let Some(root) = self.origin_root(&local_id) {
+                    if let Some(&TypeOrigin::Binder(..)) = self.origins.get(&root) {
+                        if !binders.contains(&root) {
+                            binders.push(root);
+                        }
+                    }
+                }
+                if let Some(bound) = self.bound_type(&local_id) {
+                    self.collect_binders(&bound, binders);
+                }
+            },
+            &InternalType::Named(Ident::Global(_))                  => (),
+            &InternalType::Func(box ref arg_type, box ref ret_type) => {
+                self.collect_binders(arg_type, binders);
+                self.collect_binders(ret_type, binders);
+            },
+        }
+    }
+
+    // unification is undone when the comparison fails, so a mismatch leaves no type variables bound
+    pub fn tentatively<F: FnOnce() -> bool>(&self, compare: F) -> bool {
+        let saved = self.flexible.borrow().clone();
+        let answer = compare();
+        if !answer {
+            *self.flexible.borrow_mut() = saved;
+        }
+        answer
+    }
+
+    pub fn equivalent(&self, lhs: &Formula, rhs: &Formula) -> bool {
+        self.tentatively(|| {
+            self.unify_formula(lhs, rhs) && self.resolve_formula(lhs.clone()).equivalent(&self.resolve_formula(rhs.clone()))
+        })
+    }
+
+    pub fn equivalent_term(&self, lhs: &Term, rhs: &Term) -> bool {
+        self.tentatively(|| self.unify_term(lhs, rhs) && self.resolve_term(lhs).equivalent(&self.resolve_term(rhs)))
+    }
+
+    pub fn new_child(self) -> Bindings {
+        let mut saved_scopes = self.saved_scopes;
+        saved_scopes.push(self.module_scopes.clone());
+        Bindings {
+            next_local: self.next_local,
+            scope_depth: self.scope_depth,
+            id_table: self.id_table,
+            val_table: self.val_table.new_child(),
+            // base_types: self.base_types.new_child(),
+            // term_types: self.term_types.new_child(),
+            // form_types: self.form_types.new_child(),
+            proven_wffs: self.proven_wffs.new_child(),
+            datatypes: self.datatypes.new_child(),
+            definitions: self.definitions.new_child(),
+            sites: self.sites.new_child(),
+            poisoned: self.poisoned.new_child(),
+            modules: self.modules.new_child(),
+            module_scopes: self.module_scopes,
+            saved_scopes: saved_scopes,
+            search_path: self.search_path,
+            flexible: self.flexible,
+            origins: self.origins,
+            local_names: self.local_names,
+        }
+    }
+
+    pub fn parent(self) -> Bindings {
+        let mut saved_scopes = self.saved_scopes;
+        let module_scopes = saved_scopes.pop().unwrap();
+        Bindings {
+            next_local: self.next_local,
+            scope_depth: self.scope_depth,
+            id_table: self.id_table,
+            val_table: self.val_table.parent().unwrap(),
+            // base_types: self.base_types.parent().unwrap(),
+            // term_types: self.term_types.parent().unwrap(),
+            // form_types: self.form_types.parent().unwrap(),
+            proven_wffs: self.proven_wffs.parent().unwrap(),
+            datatypes: self.datatypes.parent().unwrap(),
+            definitions: self.definitions.parent().unwrap(),
+            sites: self.sites.parent().unwrap(),
+            poisoned: self.poisoned.parent().unwrap(),
+            modules: self.modules.parent().unwrap(),
+            module_scopes: module_scopes,
+            saved_scopes: saved_scopes,
+            search_path: self.search_path,
+            flexible: self.flexible,
+            origins: self.origins,
+            local_names: self.local_names,
+        }
+    }
+
+    pub fn merge(self) -> Bindings {
+        let mut saved_scopes = self.saved_scopes;
+        saved_scopes.pop();
+        Bindings {
+            next_local: self.next_local,
+            scope_depth: self.scope_depth,
+            id_table: self.id_table,
+            val_table: self.val_table.merge().unwrap(),
+            proven_wffs: self.proven_wffs.merge().unwrap(),
+            datatypes: self.datatypes.merge().unwrap(),
+            definitions: self.definitions.merge().unwrap(),
+            sites: self.sites.merge().unwrap(),
+            poisoned: self.poisoned.merge().unwrap(),
+            modules: self.modules.merge().unwrap(),
+            module_scopes: self.module_scopes,
+            saved_scopes: saved_scopes,
+            search_path: self.search_path,
+            flexible: self.flexible,
+            origins: self.origins,
+            local_names: self.local_names,
+        }
+    }
+
+    pub fn push_scope(&mut self) {
+        self.checkpoint();
+        self.scope_depth += 1;
+    }
+
+    pub fn pop_scope(&mut self) {
+        self.scope_depth -= 1;
+        let child = mem::replace(self, Bindings::new());
+        *self = child.parent();
+    }
+
+    pub fn checkpoint(&mut self) {
+        let parent = mem::replace(self, Bindings::new());
+        *self = parent.new_child();
+    }
+
+    pub fn close_scopes(&mut self) {
+        while self.scope_depth > 0 {
+            self.pop_scope();
+        }
+    }
+
+    pub fn commit(&mut self) {
+        let child = mem::replace(self, Bindings::new());
+        *self = child.merge();
+    }
+
+    pub fn rollback(&mut self) {
+        self.close_scopes();
+        let child = mem::replace(self, Bindings::new());
+        *self = child.parent();
+    }
+
+    fn redeclares_object(&self, id: &usize) -> bool {
+        self.scope_depth == 0 && self.val_table.contains_key(id)
+    }
+
+    fn redeclares_theorem(&self, id: &usize) -> bool {
+        self.scope_depth == 0 && self.proven_wffs.contains_key(id)
+    }
+
+    fn binding_exists(&self, id: &usize, context: &FileLocation) -> Error {
+        let name = self.get_name(id).unwrap().clone();
+        let error = Error::new(BindingExists { name: name.clone() }, context);
+        match self.sites.get(id) {
+            Some(site) => error.with_label(site, format!("`{}` first declared here", name)),
+            None       => error,
+        }
+    }
+
+    pub fn poison(&mut self, id: usize, context: &FileLocation) {
+        if !self.val_table.contains_key(&id) && !self.proven_wffs.contains_key(&id) {
+            self.poisoned.insert(id, context.clone());
+        }
+    }
+
+    pub fn no_binding<F: Fn(&usize) -> bool>(&self, id: &usize, in_scope: F, context: &FileLocation) -> Error {
+        let name = self.get_name(id).unwrap().clone();
+        if let Some(site) = self.poisoned.get(id) {
+            return Error::new(DependsOnFailed { name: name.clone() }, context)
+                .with_label(site, format!("`{}` failed to check here", name));
+        }
+        let error = Error::new(NoBinding { name: name.clone() }, context);
+        match self.similar_name(&name, in_scope) {
+            Some(similar) => error.with_help(format!("did you mean `{}`?", similar)),
+            None          => error,
+        }
+    }
+
+    pub fn similar_name<F: Fn(&usize) -> bool>(&self, name: &str, in_scope: F) -> Option<&String> {
+        let (prefix, base) = split_qualified(name);
+        let len = base.chars().count();
+        self.id_table.iter()
+            .filter(|&(id, candidate)| candidate != name && split_qualified(candidate).0 == prefix
+                && (in_scope(id) || self.val_table.contains_key(id) || self.proven_wffs.contains_key(id)))
+            .map(|(_, candidate)| (error::edit_distance(base, split_qualified(candidate).1), candidate))
+            .filter(|&(dist, _)| dist < len && dist <= cmp::max(1, len / 3))
+            .min()
+            .map(|(_, candidate)| candidate)
+    }
+
+    pub fn get_id(&mut self, name: &str) -> usize {
+        self.id_table.get_id(&name.to_owned())
+    }
+
+    fn is_declared(&self, id: &usize) -> bool {
+        self.val_table.contains_key(id) || self.proven_wffs.contains_key(id) || self.poisoned.contains_key(id)
+    }
+
+    pub fn declared(&self) -> Vec<usize> {
+        self.id_table.iter().map(|(&id, _)| id)
+            .filter(|id| self.val_table.contains_key(id) || self.proven_wffs.contains_key(id))
+            .collect()
+    }
+
+    pub fn resolve(&mut self, name: &str) -> usize {
+        match self.lookup(name) {
+            Some(id) => id,
+            None     => {
+                let qualified = self.qualify(name);
+                self.get_id(&qualified)
+            },
+        }
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<usize> {
+        let declared = |id: &&usize| self.is_declared(id);
+        if let Some(&id) = self.id_table.get_id_nomake(&self.qualify(name)).filter(&declared) {
+            return Some(id);
+        }
+        if name.contains('.') {
+            return None;
+        }
+        for &(ref path, ref names) in self.module_scope().opens.iter().rev() {
+            if names.as_ref().map_or(true, |names| names.iter().any(|selected| selected == name)) {
+                if let Some(&id) = self.id_table.get_id_nomake(&format!("{}.{}", path, name)).filter(&declared) {
+                    return Some(id);
+                }
+            }
+        }
+        None
+    }
+
+    pub fn shortest_name(&self, id: &usize) -> String {
+        let name = self.get_name(id).unwrap();
+        let (prefix, base) = split_qualified(name);
+        let aliased = self.module_scope().aliases.iter()
+            .filter(|&(_, path)| path == prefix)
+            .map(|(alias, _)| format!("{}.{}", alias, base));
+        iter::once(String::from(base)).chain(aliased)
+            .filter(|candidate| self.lookup(candidate) == Some(*id))
+            .min_by_key(|candidate| (candidate.len(), candidate.clone()))
+            .unwrap_or_else(|| name.clone())
+    }
+
+    fn qualify(&self, name: &str) -> String {
+        match name.rfind('.') {
+            Some(dot) => match self.module_scope().aliases.get(&name[..dot]) {
+                Some(path) => format!("{}.{}", path, &name[dot + 1..]),
+                None       => String::from(name),
+            },
+            None      => self.module_scope().qualify(name),
+        }
+    }
+
+    pub fn declare(&mut self, name: &str) -> usize {
+        let own = self.module_scope().qualify(name);
+        self.get_id(&own)
+    }
+
+    fn module_scope(&self) -> &ModuleScope {
+        self.module_scopes.last().unwrap()
+    }
+
+    pub fn enter_module(&mut self, path: &str) {
+        self.module_scopes.push(ModuleScope::new(path));
+    }
+
+    pub fn leave_module(&mut self, path: &str, filename: &str) {
+        self.module_scopes.pop();
+        self.modules.insert(String::from(path), String::from(filename));
+    }
+
+    pub fn is_loaded(&self, path: &str) -> bool {
+        self.modules.contains_key(path)
+    }
+
+    pub fn import_cycle(&self, path: &str) -> Option<String> {
+        let loading: Vec<&str> = self.module_scopes.iter().skip(1).map(|scope| scope.prefix.as_str()).collect();
+        loading.iter().position(|&other| other == path).map(|start| {
+            let mut cycle = loading[start..].to_vec();
+            cycle.push(path);
+            cycle.join(" -> ")
+        })
+    }
+
+    pub fn add_alias(&mut self, alias: &str, path: &str) {
+        self.module_scopes.last_mut().unwrap().aliases.insert(String::from(alias), String::from(path));
+    }
+
+    pub fn add_open(&mut self, path: &str, names: Option<Vec<String>>) {
+        self.module_scopes.last_mut().unwrap().opens.push((String::from(path), names));
+    }
+
+    pub fn add_search_dir(&mut self, dir: PathBuf) {
+        self.search_path.push(dir);
+    }
+
+    pub fn pop_search_dir(&mut self) -> Option<PathBuf> {
+        self.search_path.pop()
+    }
+
+    pub fn search_path(&self) -> &[PathBuf] {
+        &self.search_path
+    }
+
+    pub fn get_name(&self, id: &usize) -> Option<&String> {
+        self.id_table.get_name(id)
+    }
+
+    pub fn get_type(&self, id: &usize) -> Option<MetaType> {
+        if let Some(&(ref mtype, _)) = self.val_table.get(id) { Some(mtype.clone()) } else { None }
+    }
+
+    pub fn get_value(&self, id: &usize) -> Option<MetaValue> {
+        if let Some(&(_, ref itype_q)) = self.val_table.get(id) { itype_q.clone() } else { None }
+    }
+
+    pub fn get_type_value(&self, id: &usize) -> Option<(MetaType, Option<MetaValue>)> {
+        if let Some(&ref pair) = self.val_table.get(id) { Some(pair.clone()) } else { None }
+    }
+
+    pub fn get_theorem(&self, id: &usize) -> Option<&FormulaSchema> {
+        self.proven_wffs.get(id)
+    }
+
+    pub fn get_site(&self, id: &usize) -> Option<&FileLocation> {
+        self.sites.get(id)
+    }
+
+    pub fn get_data(&self, id: &usize) -> Option<&DataType> {
+        self.datatypes.get(id)
+    }
+
+    pub fn defined_by(&self, id: &usize) -> Option<usize> {
+        self.definitions.get(id).cloned()
+    }
+
+    pub fn insert_object_noval(&mut self, id: usize, mtype: MetaType, context: &FileLocation) -> error::Result<usize> {
+        if self.redeclares_object(&id) {
+            return Err(self.binding_exists(&id, context));
+        }
+        if let None = self.val_table.insert(id, (mtype, None)) {
+            self.sites.insert(id, context.clone());
+            Ok(id)
+        } else {
+            Err(self.binding_exists(&id, context))
+        }
+    }
+
+    pub fn insert_object(&mut self, id: usize, mtype: MetaType, mval: MetaValue, context: &FileLocation) -> error::Result<usize> {
+        if self.redeclares_object(&id) {
+            return Err(self.binding_exists(&id, context));
+        }
+        if let None = self.val_table.insert(id, (mtype, Some(mval))) {
+            self.sites.insert(id, context.clone());
+            Ok(id)
+        } else {
+            Err(self.binding_exists(&id, context))
+        }
+    }
+
+    pub fn insert_object_anytype(&mut self, id: usize, mtype: MetaType, mval: MetaValue, context: &FileLocation) -> error::Result<usize> {
+        if let Some((_, None)) = self.val_table.insert(id, (mtype, Some(mval))) {
+            Ok(id)
+        } else {
+            Err(self.binding_exists(&id, context))
+        }
+    }
+
+    pub fn insert_theorem(&mut self, id: usize, stmt: FormulaSchema, context: &FileLocation) -> error::Result<usize> {
+        if self.redeclares_theorem(&id) {
+            return Err(self.binding_exists(&id, context));
+        }
+        if let Some(_) = self.proven_wffs.insert(id, stmt) {
+            Err(self.binding_exists(&id, context))
+        } else {
+            self.sites.insert(id, context.clone());
+            Ok(id)
+        }
+    }
+
+    pub fn insert_data(&mut self, id: usize, data: DataType) {
+        for &item in data.constructors.iter().chain(data.axioms.iter()) {
+            self.definitions.insert(item, id);
+        }
+        self.datatypes.insert(id, data);
+    }
+}
+
+
+pub trait RLangRepr {
+    fn repr(&self, globals: &Bindings) -> String;
+}
+
+impl RLangRepr for InternalType {
+    fn repr(&self, globals: &Bindings) -> String {
+        Printer::new(globals).internal_type(self)
+    }
+}
+
+impl RLangRepr for Term {
+    fn repr(&self, globals: &Bindings) -> String {
+        Printer::new(globals).term(self)
+    }
+}
+
+impl RLangRepr for Formula {
+    fn repr(&self, globals: &Bindings) -> String {
+        Printer::new(globals).formula(self)
+    }
+}
+
+impl RLangRepr for MetaType {
+    fn repr(&self, globals: &Bindings) -> String {
+        Printer::new(globals).meta_type(self)
+    }
+}
+
+impl RLangRepr for FormulaSchema {
+    fn repr(&self, globals: &Bindings) -> String {
+        Printer::new(globals).schema(self)
+    }
+}
@@ -0,0 +1,228 @@
+#![allow(dead_code)]
+
+use error::{Error, FileLocation, SourceInfo};
+use grammar;
+use lexer::Lexer;
+use module::Import;
+
+
+pub fn parse_program(source: &SourceInfo, text: &str) -> Vec<Statement> {
+    let mut lexer = Lexer::new(text);
+    let mut errors = Vec::new();
+    let stmts = match grammar::parse_Program(source, &mut errors, &mut lexer) {
+        Ok(stmts) => stmts,
+        Err(err)  => {
+            errors.push(Error::from_parse_error(err, source));
+            Vec::new()
+        },
+    };
+    errors.extend(lexer.take_errors().into_iter().map(|error| Error::from_lexical_error(error, source)));
+    errors.sort_by_key(|error| error.location().position());
+    // a declaration dropped at a token that cannot start a statement is
+    // recovered twice, once for the declaration and once to skip ahead
+    errors.dedup_by_key(|error| error.location().position());
+    let mut errors = errors.into_iter().peekable();
+    let mut program = Vec::new();
+    for stmt in stmts {
+        while errors.peek().is_some_and(|error| error.location().position() <= stmt.location().position()) {
+            program.push(Statement::Error(errors.next().unwrap()));
+        }
+        program.push(stmt);
+    }
+    program.extend(errors.map(Statement::Error));
+    program
+}
+
+pub fn parse_formula(source: &SourceInfo, text: &str) -> Result<Expr, Error> {
+    let mut lexer = Lexer::new(text);
+    let expr = grammar::parse_Expression(source, &mut Vec::new(), &mut lexer);
+    match lexer.take_errors().into_iter().next() {
+        Some(error) => Err(Error::from_lexical_error(error, source)),
+        None        => expr.map_err(|err| Error::from_parse_error(err, source)),
+    }
+}
+
+
+#[derive(Clone, Debug)]
+pub struct Name {
+    pub text: String,
+    pub location: FileLocation,
+}
+
+
+#[derive(Debug)]
+pub enum Statement {
+    Let(FileLocation, Name, MetaTypeExpr),
+    Data(FileLocation, Name, Vec<Constructor>),
+    Axiom(FileLocation, Name, Schema),
+    Theorem(FileLocation, Name, Schema),
+    Proof(FileLocation, Name, Schema),
+    Import(FileLocation, Import),
+    Dropped(FileLocation, Name),
+    Error(Error),
+}
+
+impl Statement {
+    pub fn location(&self) -> &FileLocation {
+        match self {
+            &Statement::Let(ref loc, _, _)     => loc,
+            &Statement::Data(ref loc, _, _)    => loc,
+            &Statement::Axiom(ref loc, _, _)   => loc,
+            &Statement::Theorem(ref loc, _, _) => loc,
+            &Statement::Proof(ref loc, _, _)   => loc,
+            &Statement::Import(ref loc, _)     => loc,
+            &Statement::Dropped(ref loc, _)    => loc,
+            &Statement::Error(ref error)       => error.location(),
+        }
+    }
+}
+
+
+#[derive(Debug)]
+pub struct Constructor {
+    pub name: Name,
+    pub args: Vec<TypeExpr>,
+}
+
+
+#[derive(Debug)]
+pub enum Schema {
+    Schema(FileLocation, Name, MetaTypeExpr, Box<Schema>),
+    Poly(FileLocation, Name, Box<Schema>),
+    Formula(Expr),
+    Proved(Expr, Proof),
+    Block(FileLocation, Expr, Vec<Step>, Expr, Proof),
+}
+
+
+#[derive(Debug)]
+pub struct Step {
+    pub location: FileLocation,
+    pub hyp: Name,
+    pub formula: Expr,
+    pub proof: Proof,
+}
+
+
+#[derive(Debug)]
+pub enum MetaTypeExpr {
+    Poly(Name, Box<MetaTypeExpr>),
+    Type,
+    Term(TypeExpr),
+    Formula(Vec<TypeExpr>),
+}
+
+
+#[derive(Debug)]
+pub enum TypeExpr {
+    Named(Name),
+    Func(Box<TypeExpr>, Box<TypeExpr>),
+}
+
+
+#[derive(Debug)]
+pub struct Binder {
+    pub name: Name,
+    pub itype: Option<TypeExpr>,
+}
+
+
+#[derive(Debug)]
+pub enum Expr {
+    Symbol(Name),
+    Application(Box<Expr>, Box<Expr>),
+    False(FileLocation),
+    Equality(Box<Expr>, Box<Expr>),
+    Negation(FileLocation, Box<Expr>),
+    Conjunction(Box<Expr>, Box<Expr>),
+    Disjunction(Box<Expr>, Box<Expr>),
+    Implication(Box<Expr>, Box<Expr>),
+    Biconditional(Box<Expr>, Box<Expr>),
+    UniversalQ(FileLocation, Binder, Box<Expr>),
+    ExistentialQ(FileLocation, Binder, Box<Expr>),
+    Abstraction(FileLocation, Binder, Box<Expr>),
+    Numeral(FileLocation, String),
+}
+
+
+#[derive(Debug)]
+pub enum SchemaSpec {
+    Theorem(Name),
+    Specify(Box<SchemaSpec>, SchemaArg),
+}
+
+
+#[derive(Debug)]
+pub enum SchemaArg {
+    Type(FileLocation, TypeExpr),
+    Term(Expr),
+    Formula(Expr),
+}
+
+
+#[derive(Debug)]
+pub enum Proof {
+    Given(FileLocation, Binder, Box<Proof>),
+    Contrapositive(FileLocation, Box<Proof>),
+    Obtain(FileLocation, Name, Name, Box<Proof>, Box<Proof>),
+    Assume(FileLocation, Name, Expr, Box<Proof>),
+    ModusPonens(Box<Proof>, Box<Proof>),
+    Instantiate(Box<Proof>, Expr),
+    Schema(SchemaSpec),
+    AndIntro(FileLocation, Box<Proof>, Box<Proof>),
+    AndElimLeft(FileLocation, Box<Proof>),
+    AndElimRight(FileLocation, Box<Proof>),
+    OrIntroLeft(FileLocation, Expr, Box<Proof>),
+    OrIntroRight(FileLocation, Expr, Box<Proof>),
+    OrElim(FileLocation, Box<Proof>, Box<Proof>, Box<Proof>),
+    NotIntro(FileLocation, Box<Proof>),
+    NotElim(FileLocation, Box<Proof>, Box<Proof>),
+    IffIntro(FileLocation, Box<Proof>, Box<Proof>),
+    IffElimLeft(FileLocation, Box<Proof>),
+    IffElimRight(FileLocation, Box<Proof>),
+    FalseElim(FileLocation, Expr, Box<Proof>),
+    Refl(FileLocation, Expr),
+    Symm(FileLocation, Box<Proof>),
+    Trans(FileLocation, Box<Proof>, Box<Proof>),
+    Congr(FileLocation, Expr, Box<Proof>),
+    Leibniz(FileLocation, Expr, Box<Proof>, Box<Proof>),
+    Rewrite(FileLocation, Box<Proof>, Box<Proof>),
+    ExistsIntro(FileLocation, Expr, Expr, Box<Proof>),
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Vec<String> {
+        parse_program(&SourceInfo::from_text("<test>", text), text).iter().map(|stmt| match stmt {
+            &Statement::Let(_, ref name, _)   => format!("let {}", name.text),
+            &Statement::Data(_, ref name, _)  => format!("data {}", name.text),
+            &Statement::Axiom(_, ref name, _) |
+            &Statement::Theorem(_, ref name, _) |
+            &Statement::Proof(_, ref name, _) => format!("decl {}", name.text),
+            &Statement::Import(..)            => String::from("import"),
+            &Statement::Dropped(_, ref name)  => format!("dropped {}", name.text),
+            &Statement::Error(ref error)      => format!("error {:?}", error.location().position()),
+        }).collect()
+    }
+
+    #[test]
+    fn recovery_keeps_declared_names() {
+        assert_eq!(parse("let Nat :: Type\naxiom A = = Nat\nlet zero :: Term Nat"),
+            vec!["let Nat", "dropped A", "error (1, 10)", "let zero"]);
+        assert_eq!(parse("theorem T : P := Q (\ndata D = c\n"), vec!["dropped T", "error (1, 0)", "data D"]);
+    }
+
+    #[test]
+    fn binder_dot_is_not_a_qualified_name() {
+        assert_eq!(parse("axiom A = forall x: Nat.P x\naxiom B = forall x.P x\naxiom C = exists x: (arith.Nat).arith.P x"),
+            vec!["decl A", "decl B", "decl C"]);
+    }
+
+    #[test]
+    fn recovery_reports_each_token_once() {
+        assert_eq!(parse("axiom A = = Nat Nat\nproof P = { }"), vec!["dropped A", "error (0, 10)", "dropped P", "error (1, 8)"]);
+    }
+}
@@ -1,217 +1,876 @@
-#![allow(dead_code)]
-
-use std::boxed::Box;
-// use std::cmp::{PartialEq, Eq};
-use std::collections::HashMap;
-// use std::fmt;
-
-
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum Ident {
-    Global(usize),
-    Local(usize),
-}
-
-impl Ident {
-    fn matches(self, other: Ident, assoc_sto: &mut HashMap<usize, usize>, assoc_ots: &mut HashMap<usize, usize>) -> bool {
-        match self {
-            Ident::Global(id)      => if let Ident::Global(id_) = other { id == id_ } else { false },
-            Ident::Local(local_id) => {
-                if let Ident::Local(local_id_) = other {
-                    *assoc_sto.entry(local_id).or_insert(local_id_) == local_id_ &&
-                        *assoc_ots.entry(local_id_).or_insert(local_id) == local_id
-                } else {
-                    false
-                }
-            },
-        }
-    }
-}
-
-
-#[derive(Clone, Debug, PartialEq, Eq)]
-pub enum InternalType {
-    Named(Ident),
-    Func(Box<InternalType>, Box<InternalType>),
-}
-
-
-#[derive(Clone, Debug, PartialEq, Eq)]
-pub enum MetaType {
-    Type,
-    Term(InternalType),
-    Formula(Vec<InternalType>),
-    Schema(Vec<MetaType>, Box<MetaType>),
-}
-
-
-#[derive(Clone, Debug)]
-pub enum MetaValue {
-    Type(InternalType),
-    Term(Term),
-    Formula(Formula),
-    Schema(FormulaSchema),
-}
-
-
-#[derive(Clone, Debug)]
-pub enum Term {
-    Symbol(Ident),
-    Application(Box<Term>, Box<Term>),
-}
-
-impl Term {
-    fn substitute(self, var: usize, term: &Term) -> Term {
-        match self {
-            Term::Symbol(id)                     => if id == Ident::Local(var) { term.clone() } else { Term::Symbol(id) },
-            Term::Application(box func, box arg) => Term::Application(Box::new(func.substitute(var, term)), Box::new(arg.substitute(var, term))),
-        }
-    }
-
-    // pub fn vars(&self) -> HashSet<usize> {
-    //     match self {
-    //         &Term::Variable(name)                 => {
-    //             let mut result = HashSet::new();
-    //             result.insert(name.clone());
-    //             result
-    //         },
-    //         &Term::Application(box func, box arg) => &func.vars() | &arg.vars(),
-    //         // _                                     => HashSet::new(),
-    //     }
-    // }
-
-    fn matches(&self, other: &Term, assoc_sto: &mut HashMap<usize, usize>, assoc_ots: &mut HashMap<usize, usize>) -> bool {
-        match self {
-            &Term::Symbol(id)                             => {
-                if let &Term::Symbol(id_) = other {
-                    Ident::matches(id, id_, assoc_sto, assoc_ots)
-                } else {
-                    false
-                }
-            },
-            &Term::Application(box ref func, box ref arg) => {
-                if let &Term::Application(box ref func_, box ref arg_) = other {
-                    Term::matches(func, func_, assoc_sto, assoc_ots) && Term::matches(arg, arg_, assoc_sto, assoc_ots)
-                } else {
-                    false
-                }
-            },
-        }
-    }
-}
-
-
-#[derive(Clone, Debug)]
-pub enum Formula {
-    False,
-    Relation(Ident),
-    Application(Box<Formula>, Term),
-    Implication(Box<Formula>, Box<Formula>),
-    UniversalQ(usize, InternalType, Box<Formula>),
-    // ExistentialQ(usize, InternalType, Box<Formula>),
-}
-
-impl Formula {
-    // pub fn negate(self) -> Formula {
-    //     Formula::Implication(Box::new(self), Box::new(Formula::False))
-    // }
-
-    // pub fn contrapositive(self) -> Formula {
-    //     if let Formula::Implication(box lhs, box rhs) = self {
-    //         Formula::Implication(Box::new(rhs.negate()), Box::new(lhs.negate()))
-    //     } else {
-    //         panic!("Cannot take contrapositive of {:?} : not an implication", self);
-    //     }
-    // }
-
-    pub fn instantiate(self, term: Term) -> Formula {
-        if let Formula::UniversalQ(var, _itype, box form) = self {
-            form.substitute(var, &term)
-        } else {
-            panic!("Cannot instantiate in {:?} : not a universal quantification", self);
-        }
-    }
-
-    pub fn substitute(self, var: usize, term: &Term) -> Formula {
-        match self {
-            Formula::False                           => Formula::False,
-            Formula::Relation(id)                    => Formula::Relation(id),
-            Formula::Application(box pred, arg)      => Formula::Application(Box::new(pred.substitute(var, term)), arg.substitute(var, term)),
-            Formula::Implication(box lhs, box rhs)   => Formula::Implication(Box::new(lhs.substitute(var, term)), Box::new(rhs.substitute(var, term))),
-            Formula::UniversalQ(id, itype, box form) => {
-                if id == var {
-                    Formula::UniversalQ(id, itype, Box::new(form))
-                } else {
-                    Formula::UniversalQ(id, itype, Box::new(form.substitute(var, term)))
-                }
-            },
-        }
-    }
-
-    pub fn matches(&self, other: &Formula, assoc_sto: &mut HashMap<usize, usize>, assoc_ots: &mut HashMap<usize, usize>) -> bool {
-        match self {
-            &Formula::False                                   => if let &Formula::False = other { true } else { false },
-            &Formula::Relation(id)                            => {
-                if let &Formula::Relation(id_) = other {
-                    Ident::matches(id, id_, assoc_sto, assoc_ots)
-                } else {
-                    false
-                }
-            },
-            &Formula::Application(box ref pred, ref arg)      => {
-                if let &Formula::Application(box ref pred_, ref arg_) = other {
-                    Formula::matches(pred, pred_, assoc_sto, assoc_ots) && Term::matches(arg, arg_, assoc_sto, assoc_ots)
-                } else {
-                    false
-                }
-            },
-            &Formula::Implication(box ref lhs, box ref rhs)   => {
-                if let &Formula::Implication(box ref lhs_, box ref rhs_) = other {
-                    Formula::matches(lhs, lhs_, assoc_sto, assoc_ots) && Formula::matches(rhs, rhs_, assoc_sto, assoc_ots)
-                } else {
-                    false
-                }
-            },
-            &Formula::UniversalQ(id, ref itype, box ref form) => {
-                if let &Formula::UniversalQ(id_, ref itype_, box ref form_) = other {
-                    assoc_sto.insert(id, id_);
-                    assoc_ots.insert(id_, id);
-                    let answer = itype == itype_ && Formula::matches(form, form_, assoc_sto, assoc_ots);
-                    assoc_sto.remove(&id);
-                    assoc_ots.remove(&id_);
-                    answer
-                } else {
-                    false
-                }
-            },
-        }
-    }
-
-    // fn free_vars(&self) -> HashSet<usize> {
-    //     match self {
-    //         &Formula::Application(box pred, term)   => &pred.free_vars() | &term.vars(),
-    //         &Formula::Implication(box lhs, box rhs) => &lhs.free_vars() | &rhs.free_vars(),
-    //         &Formula::UniversalQ(var, box content)  => {
-    //             let mut result = content.free_vars();
-    //             result.remove(&var);
-    //             result
-    //         },
-    //         _                                       => HashSet::new(),
-    //     }
-    // }
-
-    // pub fn is_wff(&self) -> bool {
-    //     self.free_vars().len() == 0
-    // }
-}
-
-
-#[derive(Clone, Debug)]
-pub enum FormulaSchema {
-    Formula(Formula),
-    Schema(usize, MetaType, Box<FormulaSchema>),
-}
-
-impl FormulaSchema {
-    // pub fn specify(self, )
-}
\ No newline at end of file
+#![allow(dead_code)]
+
+use std::boxed::Box;
+// use std::cmp::{PartialEq, Eq};
+use std::collections::{HashMap, HashSet};
+// use std::fmt;
+
+use state::Bindings;
+
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ident {
+    Global(usize),
+    Local(usize),
+}
+
+impl Ident {
+    fn matches(self, other: Ident, assoc_sto: &mut HashMap<usize, usize>, assoc_ots: &mut HashMap<usize, usize>) -> bool {
+        match self {
+            Ident::Global(id)      => if let Ident::Global(id_) = other { id == id_ } else { false },
+            Ident::Local(local_id) => {
+                if let Ident::Local(local_id_) = other {
+                    match (assoc_sto.get(&local_id), assoc_ots.get(&local_id_)) {
+                        (Some(&bound), Some(&bound_)) => bound == local_id_ && bound_ == local_id,
+                        (None, None)                  => local_id == local_id_,
+                        _                             => false,
+                    }
+                } else {
+                    false
+                }
+            },
+        }
+    }
+}
+
+
+fn restore(assoc: &mut HashMap<usize, usize>, id: usize, old: Option<usize>) {
+    match old {
+        Some(old_id) => { assoc.insert(id, old_id); },
+        None         => { assoc.remove(&id); },
+    }
+}
+
+
+fn type_vars(itypes: &[InternalType]) -> HashSet<usize> {
+    itypes.iter().fold(HashSet::new(), |vars, itype| &vars | &itype.vars())
+}
+
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InternalType {
+    Named(Ident),
+    Func(Box<InternalType>, Box<InternalType>),
+}
+
+
+impl InternalType {
+    pub fn vars(&self) -> HashSet<usize> {
+        match self {
+            &InternalType::Named(Ident::Local(local_id))            => {
+                let mut result = HashSet::new();
+                result.insert(local_id);
+                result
+            },
+            &InternalType::Named(Ident::Global(_))                  => HashSet::new(),
+            &InternalType::Func(box ref arg_type, box ref ret_type) => &arg_type.vars() | &ret_type.vars(),
+        }
+    }
+
+    pub fn substitute(self, var: usize, itype: &InternalType) -> InternalType {
+        match self {
+            InternalType::Named(id)                        => if id == Ident::Local(var) { itype.clone() } else { InternalType::Named(id) },
+            InternalType::Func(box arg_type, box ret_type) => {
+                InternalType::Func(Box::new(arg_type.substitute(var, itype)), Box::new(ret_type.substitute(var, itype)))
+            },
+        }
+    }
+}
+
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MetaType {
+    Type,
+    Term(InternalType),
+    Formula(Vec<InternalType>),
+    Schema(Vec<MetaType>, Box<MetaType>),
+    Poly(Vec<usize>, Box<MetaType>),
+}
+
+
+impl MetaType {
+    pub fn vars(&self) -> HashSet<usize> {
+        match self {
+            &MetaType::Type                                    => HashSet::new(),
+            &MetaType::Term(ref itype)                         => itype.vars(),
+            &MetaType::Formula(ref arg_types)                  => {
+                arg_types.iter().fold(HashSet::new(), |acc, itype| &acc | &itype.vars())
+            },
+            &MetaType::Schema(ref arg_types, box ref ret_type) => {
+                arg_types.iter().fold(ret_type.vars(), |acc, mtype| &acc | &mtype.vars())
+            },
+            &MetaType::Poly(ref vars, box ref mtype)           => {
+                mtype.vars().into_iter().filter(|var| !vars.contains(var)).collect()
+            },
+        }
+    }
+
+    pub fn substitute_type(self, var: usize, itype: &InternalType) -> MetaType {
+        match self {
+            MetaType::Type                            => MetaType::Type,
+            MetaType::Term(arg_type)                  => MetaType::Term(arg_type.substitute(var, itype)),
+            MetaType::Formula(arg_types)              => {
+                MetaType::Formula(arg_types.into_iter().map(|arg_type| arg_type.substitute(var, itype)).collect())
+            },
+            MetaType::Schema(arg_types, box ret_type) => {
+                MetaType::Schema(arg_types.into_iter().map(|mtype| mtype.substitute_type(var, itype)).collect(),
+                    Box::new(ret_type.substitute_type(var, itype)))
+            },
+            MetaType::Poly(vars, box mtype)           => {
+                if vars.contains(&var) {
+                    MetaType::Poly(vars, Box::new(mtype))
+                } else {
+                    MetaType::Poly(vars, Box::new(mtype.substitute_type(var, itype)))
+                }
+            },
+        }
+    }
+
+    pub fn unquantified(self) -> MetaType {
+        if let MetaType::Poly(_, box mtype) = self { mtype } else { self }
+    }
+}
+
+
+#[derive(Clone, Debug)]
+pub enum MetaValue {
+    Type(InternalType),
+    Term(Term),
+    Formula(Formula),
+    Schema(FormulaSchema),
+}
+
+
+#[derive(Clone, Debug)]
+pub enum Term {
+    Symbol(Ident, Vec<InternalType>),
+    Application(Box<Term>, Box<Term>),
+}
+
+impl Term {
+    pub fn head_global(&self) -> Option<usize> {
+        match self {
+            &Term::Symbol(Ident::Global(id), _)     => Some(id),
+            &Term::Symbol(Ident::Local(_), _)       => None,
+            &Term::Application(box ref func, _)     => func.head_global(),
+        }
+    }
+
+    fn substitute(self, var: usize, term: &Term) -> Term {
+        match self {
+            Term::Symbol(id, itypes)             => if id == Ident::Local(var) { term.clone() } else { Term::Symbol(id, itypes) },
+            Term::Application(box func, box arg) => Term::Application(Box::new(func.substitute(var, term)), Box::new(arg.substitute(var, term))),
+        }
+    }
+
+    fn substitute_type(self, var: usize, itype: &InternalType) -> Term {
+        match self {
+            Term::Symbol(id, itypes)             => Term::Symbol(id, itypes.into_iter().map(|itype_| itype_.substitute(var, itype)).collect()),
+            Term::Application(box func, box arg) => {
+                Term::Application(Box::new(func.substitute_type(var, itype)), Box::new(arg.substitute_type(var, itype)))
+            },
+        }
+    }
+
+    pub fn vars(&self) -> HashSet<usize> {
+        match self {
+            &Term::Symbol(Ident::Local(local_id), ref itypes) => {
+                let mut result = type_vars(itypes);
+                result.insert(local_id);
+                result
+            },
+            &Term::Symbol(Ident::Global(_), ref itypes)       => type_vars(itypes),
+            &Term::Application(box ref func, box ref arg)     => &func.vars() | &arg.vars(),
+        }
+    }
+
+    pub fn equivalent(&self, other: &Term) -> bool {
+        let mut assoc_sto: HashMap<usize, usize> = HashMap::new();
+        let mut assoc_ots: HashMap<usize, usize> = HashMap::new();
+        Term::matches(self, other, &mut assoc_sto, &mut assoc_ots)
+    }
+
+    fn rewrite(self, from: &Term, to: &Term) -> Term {
+        if self.equivalent(from) {
+            return to.clone();
+        }
+        match self {
+            Term::Symbol(id, itypes)             => Term::Symbol(id, itypes),
+            Term::Application(box func, box arg) => Term::Application(Box::new(func.rewrite(from, to)), Box::new(arg.rewrite(from, to))),
+        }
+    }
+
+    fn matches(&self, other: &Term, assoc_sto: &mut HashMap<usize, usize>, assoc_ots: &mut HashMap<usize, usize>) -> bool {
+        match self {
+            &Term::Symbol(id, ref itypes)                 => {
+                if let &Term::Symbol(id_, ref itypes_) = other {
+                    Ident::matches(id, id_, assoc_sto, assoc_ots) && itypes == itypes_
+                } else {
+                    false
+                }
+            },
+            &Term::Application(box ref func, box ref arg) => {
+                if let &Term::Application(box ref func_, box ref arg_) = other {
+                    Term::matches(func, func_, assoc_sto, assoc_ots) && Term::matches(arg, arg_, assoc_sto, assoc_ots)
+                } else {
+                    false
+                }
+            },
+        }
+    }
+}
+
+
+#[derive(Clone, Debug)]
+pub enum Formula {
+    False,
+    Relation(Ident, Vec<InternalType>),
+    Application(Box<Formula>, Term),
+    Equality(InternalType, Term, Term),
+    Implication(Box<Formula>, Box<Formula>),
+    Negation(Box<Formula>),
+    Conjunction(Box<Formula>, Box<Formula>),
+    Disjunction(Box<Formula>, Box<Formula>),
+    Biconditional(Box<Formula>, Box<Formula>),
+    UniversalQ(usize, InternalType, Box<Formula>),
+    ExistentialQ(usize, InternalType, Box<Formula>),
+    Abstraction(usize, InternalType, Box<Formula>),
+}
+
+impl Formula {
+    pub fn head_global(&self) -> Option<usize> {
+        match self {
+            &Formula::Relation(Ident::Global(id), _) => Some(id),
+            &Formula::Application(box ref pred, _)  => pred.head_global(),
+            _                                       => None,
+        }
+    }
+
+    pub fn negate(self) -> Formula {
+        Formula::Negation(Box::new(self))
+    }
+
+    pub fn equivalent(&self, other: &Formula) -> bool {
+        let mut assoc_sto: HashMap<usize, usize> = HashMap::new();
+        let mut assoc_ots: HashMap<usize, usize> = HashMap::new();
+        Formula::matches(self, other, &mut assoc_sto, &mut assoc_ots)
+    }
+
+    pub fn instantiate(self, term: Term, globals: &mut Bindings) -> Option<Formula> {
+        if let Formula::UniversalQ(var, _itype, box form) = self {
+            Some(form.substitute(var, &term, globals))
+        } else {
+            None
+        }
+    }
+
+    pub fn apply(self, term: &Term, globals: &mut Bindings) -> Formula {
+        if let Formula::Abstraction(var, _itype, box form) = self {
+            form.substitute(var, term, globals)
+        } else {
+            Formula::Application(Box::new(self), term.clone())
+        }
+    }
+
+    pub fn rewrite(self, from: &Term, to: &Term, globals: &mut Bindings) -> Formula {
+        match self {
+            Formula::Application(box pred, arg)        => Formula::Application(Box::new(pred.rewrite(from, to, globals)), arg.rewrite(from, to)),
+            Formula::Equality(itype, lhs, rhs)         => Formula::Equality(itype, lhs.rewrite(from, to), rhs.rewrite(from, to)),
+            Formula::Implication(box lhs, box rhs)     => {
+                Formula::Implication(Box::new(lhs.rewrite(from, to, globals)), Box::new(rhs.rewrite(from, to, globals)))
+            },
+            Formula::Negation(box form)                => Formula::Negation(Box::new(form.rewrite(from, to, globals))),
+            Formula::Conjunction(box lhs, box rhs)     => {
+                Formula::Conjunction(Box::new(lhs.rewrite(from, to, globals)), Box::new(rhs.rewrite(from, to, globals)))
+            },
+            Formula::Disjunction(box lhs, box rhs)     => {
+                Formula::Disjunction(Box::new(lhs.rewrite(from, to, globals)), Box::new(rhs.rewrite(from, to, globals)))
+            },
+            Formula::Biconditional(box lhs, box rhs)   => {
+                Formula::Biconditional(Box::new(lhs.rewrite(from, to, globals)), Box::new(rhs.rewrite(from, to, globals)))
+            },
+            Formula::UniversalQ(id, itype, box form)   => {
+                let (id, form) = form.rewrite_under(id, from, to, globals);
+                Formula::UniversalQ(id, itype, Box::new(form))
+            },
+            Formula::ExistentialQ(id, itype, box form) => {
+                let (id, form) = form.rewrite_under(id, from, to, globals);
+                Formula::ExistentialQ(id, itype, Box::new(form))
+            },
+            Formula::Abstraction(id, itype, box form)  => {
+                let (id, form) = form.rewrite_under(id, from, to, globals);
+                Formula::Abstraction(id, itype, Box::new(form))
+            },
+            form                                       => form,
+        }
+    }
+
+    fn rewrite_under(self, id: usize, from: &Term, to: &Term, globals: &mut Bindings) -> (usize, Formula) {
+        if from.vars().contains(&id) {
+            (id, self)
+        } else if to.vars().contains(&id) {
+            let (id, form) = self.rename(id, globals);
+            (id, form.rewrite(from, to, globals))
+        } else {
+            (id, self.rewrite(from, to, globals))
+        }
+    }
+
+    pub fn substitute(self, var: usize, term: &Term, globals: &mut Bindings) -> Formula {
+        match self {
+            Formula::False                            => Formula::False,
+            Formula::Relation(id, itypes)             => Formula::Relation(id, itypes),
+            Formula::Application(box pred, arg)       => Formula::Application(Box::new(pred.substitute(var, term, globals)), arg.substitute(var, term)),
+            Formula::Equality(itype, lhs, rhs)        => Formula::Equality(itype, lhs.substitute(var, term), rhs.substitute(var, term)),
+            Formula::Implication(box lhs, box rhs)    => {
+                Formula::Implication(Box::new(lhs.substitute(var, term, globals)), Box::new(rhs.substitute(var, term, globals)))
+            },
+            Formula::Negation(box form)               => Formula::Negation(Box::new(form.substitute(var, term, globals))),
+            Formula::Conjunction(box lhs, box rhs)    => {
+                Formula::Conjunction(Box::new(lhs.substitute(var, term, globals)), Box::new(rhs.substitute(var, term, globals)))
+            },
+            Formula::Disjunction(box lhs, box rhs)    => {
+                Formula::Disjunction(Box::new(lhs.substitute(var, term, globals)), Box::new(rhs.substitute(var, term, globals)))
+            },
+            Formula::Biconditional(box lhs, box rhs)  => {
+                Formula::Biconditional(Box::new(lhs.substitute(var, term, globals)), Box::new(rhs.substitute(var, term, globals)))
+            },
+            Formula::UniversalQ(id, itype, box form)  => {
+                let (id, form) = form.substitute_under(id, var, term, globals);
+                Formula::UniversalQ(id, itype, Box::new(form))
+            },
+            Formula::ExistentialQ(id, itype, box form) => {
+                let (id, form) = form.substitute_under(id, var, term, globals);
+                Formula::ExistentialQ(id, itype, Box::new(form))
+            },
+            Formula::Abstraction(id, itype, box form) => {
+                let (id, form) = form.substitute_under(id, var, term, globals);
+                Formula::Abstraction(id, itype, Box::new(form))
+            },
+        }
+    }
+
+    fn substitute_under(self, id: usize, var: usize, term: &Term, globals: &mut Bindings) -> (usize, Formula) {
+        if id == var || !self.free_vars().contains(&var) {
+            (id, self)
+        } else if term.vars().contains(&id) {
+            let (id, form) = self.rename(id, globals);
+            (id, form.substitute(var, term, globals))
+        } else {
+            (id, self.substitute(var, term, globals))
+        }
+    }
+
+    fn rename(self, id: usize, globals: &mut Bindings) -> (usize, Formula) {
+        let fresh = globals.rename_local(id);
+        (fresh, self.substitute(id, &Term::Symbol(Ident::Local(fresh), Vec::new()), globals))
+    }
+
+    pub fn substitute_relation(self, var: usize, pred: &Formula, globals: &mut Bindings) -> Formula {
+        match self {
+            Formula::False                            => Formula::False,
+            Formula::Relation(id, itypes)             => if id == Ident::Local(var) { pred.clone() } else { Formula::Relation(id, itypes) },
+            Formula::Application(box pred_, arg)      => pred_.substitute_relation(var, pred, globals).apply(&arg, globals),
+            Formula::Equality(itype, lhs, rhs)        => Formula::Equality(itype, lhs, rhs),
+            Formula::Implication(box lhs, box rhs)    => {
+                Formula::Implication(Box::new(lhs.substitute_relation(var, pred, globals)), Box::new(rhs.substitute_relation(var, pred, globals)))
+            },
+            Formula::Negation(box form)               => Formula::Negation(Box::new(form.substitute_relation(var, pred, globals))),
+            Formula::Conjunction(box lhs, box rhs)    => {
+                Formula::Conjunction(Box::new(lhs.substitute_relation(var, pred, globals)), Box::new(rhs.substitute_relation(var, pred, globals)))
+            },
+            Formula::Disjunction(box lhs, box rhs)    => {
+                Formula::Disjunction(Box::new(lhs.substitute_relation(var, pred, globals)), Box::new(rhs.substitute_relation(var, pred, globals)))
+            },
+            Formula::Biconditional(box lhs, box rhs)  => {
+                Formula::Biconditional(Box::new(lhs.substitute_relation(var, pred, globals)), Box::new(rhs.substitute_relation(var, pred, globals)))
+            },
+            Formula::UniversalQ(id, itype, box form)  => {
+                let (id, form) = form.substitute_relation_under(id, var, pred, globals);
+                Formula::UniversalQ(id, itype, Box::new(form))
+            },
+            Formula::ExistentialQ(id, itype, box form) => {
+                let (id, form) = form.substitute_relation_under(id, var, pred, globals);
+                Formula::ExistentialQ(id, itype, Box::new(form))
+            },
+            Formula::Abstraction(id, itype, box form) => {
+                let (id, form) = form.substitute_relation_under(id, var, pred, globals);
+                Formula::Abstraction(id, itype, Box::new(form))
+            },
+        }
+    }
+
+    fn substitute_relation_under(self, id: usize, var: usize, pred: &Formula, globals: &mut Bindings) -> (usize, Formula) {
+        if !self.free_vars().contains(&var) {
+            (id, self)
+        } else if pred.free_vars().contains(&id) {
+            let (id, form) = self.rename(id, globals);
+            (id, form.substitute_relation(var, pred, globals))
+        } else {
+            (id, self.substitute_relation(var, pred, globals))
+        }
+    }
+
+    pub fn substitute_type(self, var: usize, itype: &InternalType) -> Formula {
+        match self {
+            Formula::Relation(id, itypes)              => Formula::Relation(id, itypes.into_iter().map(|itype_| itype_.substitute(var, itype)).collect()),
+            Formula::Application(box pred, arg)        => {
+                Formula::Application(Box::new(pred.substitute_type(var, itype)), arg.substitute_type(var, itype))
+            },
+            Formula::Equality(itype_, lhs, rhs)        => {
+                Formula::Equality(itype_.substitute(var, itype), lhs.substitute_type(var, itype), rhs.substitute_type(var, itype))
+            },
+            Formula::Implication(box lhs, box rhs)     => {
+                Formula::Implication(Box::new(lhs.substitute_type(var, itype)), Box::new(rhs.substitute_type(var, itype)))
+            },
+            Formula::Negation(box form)                => Formula::Negation(Box::new(form.substitute_type(var, itype))),
+            Formula::Conjunction(box lhs, box rhs)     => {
+                Formula::Conjunction(Box::new(lhs.substitute_type(var, itype)), Box::new(rhs.substitute_type(var, itype)))
+            },
+            Formula::Disjunction(box lhs, box rhs)     => {
+                Formula::Disjunction(Box::new(lhs.substitute_type(var, itype)), Box::new(rhs.substitute_type(var, itype)))
+            },
+            Formula::Biconditional(box lhs, box rhs)   => {
+                Formula::Biconditional(Box::new(lhs.substitute_type(var, itype)), Box::new(rhs.substitute_type(var, itype)))
+            },
+            Formula::UniversalQ(id, itype_, box form)  => {
+                Formula::UniversalQ(id, itype_.substitute(var, itype), Box::new(form.substitute_type(var, itype)))
+            },
+            Formula::ExistentialQ(id, itype_, box form) => {
+                Formula::ExistentialQ(id, itype_.substitute(var, itype), Box::new(form.substitute_type(var, itype)))
+            },
+            Formula::Abstraction(id, itype_, box form) => {
+                Formula::Abstraction(id, itype_.substitute(var, itype), Box::new(form.substitute_type(var, itype)))
+            },
+            form                                       => form,
+        }
+    }
+
+    pub fn matches(&self, other: &Formula, assoc_sto: &mut HashMap<usize, usize>, assoc_ots: &mut HashMap<usize, usize>) -> bool {
+        match self {
+            &Formula::False                                   => if let &Formula::False = other { true } else { false },
+            &Formula::Relation(id, ref itypes)                => {
+                if let &Formula::Relation(id_, ref itypes_) = other {
+                    Ident::matches(id, id_, assoc_sto, assoc_ots) && itypes == itypes_
+                } else {
+                    false
+                }
+            },
+            &Formula::Application(box ref pred, ref arg)      => {
+                if let &Formula::Application(box ref pred_, ref arg_) = other {
+                    Formula::matches(pred, pred_, assoc_sto, assoc_ots) && Term::matches(arg, arg_, assoc_sto, assoc_ots)
+                } else {
+                    false
+                }
+            },
+            &Formula::Equality(ref itype, ref lhs, ref rhs)   => {
+                if let &Formula::Equality(ref itype_, ref lhs_, ref rhs_) = other {
+                    itype == itype_ && Term::matches(lhs, lhs_, assoc_sto, assoc_ots) && Term::matches(rhs, rhs_, assoc_sto, assoc_ots)
+                } else {
+                    false
+                }
+            },
+            &Formula::Implication(box ref lhs, box ref rhs)   => {
+                match other {
+                    &Formula::Implication(box ref lhs_, box ref rhs_) =>
+                        Formula::matches(lhs, lhs_, assoc_sto, assoc_ots) && Formula::matches(rhs, rhs_, assoc_sto, assoc_ots),
+                    // `not A` is `A -> false`
+                    &Formula::Negation(box ref form_)                 =>
+                        Formula::matches(rhs, &Formula::False, assoc_sto, assoc_ots) && Formula::matches(lhs, form_, assoc_sto, assoc_ots),
+                    _                                                 => false,
+                }
+            },
+            &Formula::Negation(box ref form)                  => {
+                match other {
+                    &Formula::Negation(box ref form_)                 => Formula::matches(form, form_, assoc_sto, assoc_ots),
+                    &Formula::Implication(box ref lhs_, box ref rhs_) =>
+                        Formula::matches(&Formula::False, rhs_, assoc_sto, assoc_ots) && Formula::matches(form, lhs_, assoc_sto, assoc_ots),
+                    _                                                 => false,
+                }
+            },
+            &Formula::Conjunction(box ref lhs, box ref rhs)   => {
+                if let &Formula::Conjunction(box ref lhs_, box ref rhs_) = other {
+                    Formula::matches(lhs, lhs_, assoc_sto, assoc_ots) && Formula::matches(rhs, rhs_, assoc_sto, assoc_ots)
+                } else {
+                    false
+                }
+            },
+            &Formula::Disjunction(box ref lhs, box ref rhs)   => {
+                if let &Formula::Disjunction(box ref lhs_, box ref rhs_) = other {
+                    Formula::matches(lhs, lhs_, assoc_sto, assoc_ots) && Formula::matches(rhs, rhs_, assoc_sto, assoc_ots)
+                } else {
+                    false
+                }
+            },
+            &Formula::Biconditional(box ref lhs, box ref rhs) => {
+                if let &Formula::Biconditional(box ref lhs_, box ref rhs_) = other {
+                    Formula::matches(lhs, lhs_, assoc_sto, assoc_ots) && Formula::matches(rhs, rhs_, assoc_sto, assoc_ots)
+                } else {
+                    false
+                }
+            },
+            &Formula::UniversalQ(id, ref itype, box ref form) => {
+                if let &Formula::UniversalQ(id_, ref itype_, box ref form_) = other {
+                    let old_sto = assoc_sto.insert(id, id_);
+                    let old_ots = assoc_ots.insert(id_, id);
+                    let answer = itype == itype_ && Formula::matches(form, form_, assoc_sto, assoc_ots);
+                    restore(assoc_sto, id, old_sto);
+                    restore(assoc_ots, id_, old_ots);
+                    answer
+                } else {
+                    false
+                }
+            },
+            &Formula::ExistentialQ(id, ref itype, box ref form) => {
+                if let &Formula::ExistentialQ(id_, ref itype_, box ref form_) = other {
+                    let old_sto = assoc_sto.insert(id, id_);
+                    let old_ots = assoc_ots.insert(id_, id);
+                    let answer = itype == itype_ && Formula::matches(form, form_, assoc_sto, assoc_ots);
+                    restore(assoc_sto, id, old_sto);
+                    restore(assoc_ots, id_, old_ots);
+                    answer
+                } else {
+                    false
+                }
+            },
+            &Formula::Abstraction(id, ref itype, box ref form) => {
+                if let &Formula::Abstraction(id_, ref itype_, box ref form_) = other {
+                    let old_sto = assoc_sto.insert(id, id_);
+                    let old_ots = assoc_ots.insert(id_, id);
+                    let answer = itype == itype_ && Formula::matches(form, form_, assoc_sto, assoc_ots);
+                    restore(assoc_sto, id, old_sto);
+                    restore(assoc_ots, id_, old_ots);
+                    answer
+                } else {
+                    false
+                }
+            },
+        }
+    }
+
+    pub fn free_vars(&self) -> HashSet<usize> {
+        match self {
+            &Formula::Relation(Ident::Local(local_id), ref itypes) => {
+                let mut result = type_vars(itypes);
+                result.insert(local_id);
+                result
+            },
+            &Formula::Relation(Ident::Global(_), ref itypes)       => type_vars(itypes),
+            &Formula::Application(box ref pred, ref term)          => &pred.free_vars() | &term.vars(),
+            &Formula::Equality(ref itype, ref lhs, ref rhs)        => &(&lhs.vars() | &rhs.vars()) | &itype.vars(),
+            &Formula::Implication(box ref lhs, box ref rhs)   |
+            &Formula::Conjunction(box ref lhs, box ref rhs)   |
+            &Formula::Disjunction(box ref lhs, box ref rhs)   |
+            &Formula::Biconditional(box ref lhs, box ref rhs)      => &lhs.free_vars() | &rhs.free_vars(),
+            &Formula::Negation(box ref form)                       => form.free_vars(),
+            &Formula::UniversalQ(var, ref itype, box ref content) |
+            &Formula::ExistentialQ(var, ref itype, box ref content) |
+            &Formula::Abstraction(var, ref itype, box ref content) => {
+                let mut result = content.free_vars();
+                result.remove(&var);
+                &result | &itype.vars()
+            },
+            _                                                      => HashSet::new(),
+        }
+    }
+
+    pub fn is_wff(&self) -> bool {
+        self.free_vars().len() == 0
+    }
+}
+
+
+#[derive(Clone, Debug)]
+pub struct DataType {
+    pub constructors: Vec<usize>,
+    pub axioms: Vec<usize>,
+}
+
+
+#[derive(Clone, Debug)]
+pub enum FormulaSchema {
+    Formula(Formula),
+    Schema(usize, MetaType, Box<FormulaSchema>),
+    Poly(usize, Box<FormulaSchema>),
+}
+
+impl MetaValue {
+    pub fn free_vars(&self) -> HashSet<usize> {
+        match self {
+            &MetaValue::Type(ref itype)    => itype.vars(),
+            &MetaValue::Term(ref term)     => term.vars(),
+            &MetaValue::Formula(ref form)  => form.free_vars(),
+            &MetaValue::Schema(ref schema) => schema.free_vars(),
+        }
+    }
+}
+
+
+impl FormulaSchema {
+    pub fn specify(self, value: &MetaValue, globals: &mut Bindings) -> Option<FormulaSchema> {
+        match self {
+            FormulaSchema::Schema(var, _, box schema) |
+            FormulaSchema::Poly(var, box schema)      => Some(schema.substitute(var, value, globals)),
+            FormulaSchema::Formula(_)                 => None,
+        }
+    }
+
+    pub fn free_vars(&self) -> HashSet<usize> {
+        match self {
+            &FormulaSchema::Formula(ref form)                      => form.free_vars(),
+            &FormulaSchema::Schema(var, ref mtype, box ref schema) => {
+                let mut result = schema.free_vars();
+                result.remove(&var);
+                &result | &mtype.vars()
+            },
+            &FormulaSchema::Poly(var, box ref schema)              => {
+                let mut result = schema.free_vars();
+                result.remove(&var);
+                result
+            },
+        }
+    }
+
+    fn substitute(self, var: usize, value: &MetaValue, globals: &mut Bindings) -> FormulaSchema {
+        match self {
+            FormulaSchema::Formula(form)                 => {
+                FormulaSchema::Formula(match value {
+                    &MetaValue::Type(ref itype)   => form.substitute_type(var, itype),
+                    &MetaValue::Term(ref term)    => form.substitute(var, term, globals),
+                    &MetaValue::Formula(ref pred) => form.substitute_relation(var, pred, globals),
+                    &MetaValue::Schema(_)         => form,
+                })
+            },
+            FormulaSchema::Schema(id, mtype, box schema) => {
+                let mtype = if let &MetaValue::Type(ref itype) = value { mtype.substitute_type(var, itype) } else { mtype };
+                if id == var || !schema.free_vars().contains(&var) {
+                    FormulaSchema::Schema(id, mtype, Box::new(schema))
+                } else if value.free_vars().contains(&id) {
+                    let fresh = globals.rename_local(id);
+                    let fresh_value = match mtype {
+                        MetaType::Type    => MetaValue::Type(InternalType::Named(Ident::Local(fresh))),
+                        MetaType::Term(_) => MetaValue::Term(Term::Symbol(Ident::Local(fresh), Vec::new())),
+                        _                 => MetaValue::Formula(Formula::Relation(Ident::Local(fresh), Vec::new())),
+                    };
+                    let schema = schema.substitute(id, &fresh_value, globals);
+                    FormulaSchema::Schema(fresh, mtype, Box::new(schema.substitute(var, value, globals)))
+                } else {
+                    FormulaSchema::Schema(id, mtype, Box::new(schema.substitute(var, value, globals)))
+                }
+            },
+            FormulaSchema::Poly(id, box schema)          => {
+                if id == var || !schema.free_vars().contains(&var) {
+                    FormulaSchema::Poly(id, Box::new(schema))
+                } else if value.free_vars().contains(&id) {
+                    let fresh = globals.rename_local(id);
+                    let schema = schema.substitute(id, &MetaValue::Type(InternalType::Named(Ident::Local(fresh))), globals);
+                    FormulaSchema::Poly(fresh, Box::new(schema.substitute(var, value, globals)))
+                } else {
+                    FormulaSchema::Poly(id, Box::new(schema.substitute(var, value, globals)))
+                }
+            },
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::Ident::{Global, Local};
+
+    const POOL: usize = 4;
+    const CASES: usize = 2000;
+
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, bound: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % bound as u64) as usize
+        }
+    }
+
+    fn nat() -> InternalType {
+        InternalType::Named(Global(0))
+    }
+
+    fn relation(args: Vec<Term>) -> Formula {
+        args.into_iter().fold(Formula::Relation(Global(3), Vec::new()), |pred, arg| Formula::Application(Box::new(pred), arg))
+    }
+
+    fn equivalent(lhs: &Formula, rhs: &Formula) -> bool {
+        lhs.matches(rhs, &mut HashMap::new(), &mut HashMap::new())
+    }
+
+    fn term(rng: &mut Rng, depth: usize) -> Term {
+        match rng.below(if depth == 0 { 3 } else { 4 }) {
+            0 => Term::Symbol(Global(1 + rng.below(2)), Vec::new()),
+            1 |
+            2 => Term::Symbol(Local(rng.below(POOL)), Vec::new()),
+            _ => Term::Application(Box::new(term(rng, depth - 1)), Box::new(term(rng, depth - 1))),
+        }
+    }
+
+    fn formula(rng: &mut Rng, depth: usize) -> Formula {
+        let sub = |rng: &mut Rng| Box::new(formula(rng, depth - 1));
+        match rng.below(if depth == 0 { 5 } else { 14 }) {
+            0  => Formula::False,
+            1  => relation(vec![term(rng, 2)]),
+            2  => relation(vec![term(rng, 2), term(rng, 2)]),
+            3  => Formula::Relation(Local(POOL + rng.below(POOL)), Vec::new()),
+            4  => Formula::Equality(nat(), term(rng, 2), term(rng, 2)),
+            5  => Formula::Application(Box::new(Formula::Relation(Local(POOL + rng.below(POOL)), Vec::new())), term(rng, 2)),
+            6  => Formula::Implication(sub(rng), sub(rng)),
+            7  => Formula::Negation(sub(rng)),
+            8  => Formula::Conjunction(sub(rng), sub(rng)),
+            9  => Formula::Disjunction(sub(rng), sub(rng)),
+            10 => Formula::Biconditional(sub(rng), sub(rng)),
+            11 => Formula::UniversalQ(rng.below(POOL), nat(), sub(rng)),
+            12 => Formula::ExistentialQ(rng.below(POOL), nat(), sub(rng)),
+            _  => Formula::Abstraction(rng.below(POOL), nat(), sub(rng)),
+        }
+    }
+
+    // term variables are drawn from 0..POOL and formula variables from POOL..2 * POOL
+    fn globals() -> Bindings {
+        let mut globals = Bindings::new();
+        for _ in 0..2 * POOL {
+            globals.new_local();
+        }
+        globals
+    }
+
+    // substitution without renaming, correct only when no binder captures a variable of the term
+    fn naive(form: Formula, var: usize, term: &Term) -> Formula {
+        let go = |form: Formula| Box::new(naive(form, var, term));
+        match form {
+            Formula::False                             => Formula::False,
+            Formula::Relation(id, itypes)              => Formula::Relation(id, itypes),
+            Formula::Application(box pred, arg)        => Formula::Application(go(pred), arg.substitute(var, term)),
+            Formula::Equality(itype, lhs, rhs)         => Formula::Equality(itype, lhs.substitute(var, term), rhs.substitute(var, term)),
+            Formula::Implication(box lhs, box rhs)     => Formula::Implication(go(lhs), go(rhs)),
+            Formula::Negation(box form)                => Formula::Negation(go(form)),
+            Formula::Conjunction(box lhs, box rhs)     => Formula::Conjunction(go(lhs), go(rhs)),
+            Formula::Disjunction(box lhs, box rhs)     => Formula::Disjunction(go(lhs), go(rhs)),
+            Formula::Biconditional(box lhs, box rhs)   => Formula::Biconditional(go(lhs), go(rhs)),
+            Formula::UniversalQ(id, itype, box form)   => Formula::UniversalQ(id, itype, if id == var { Box::new(form) } else { go(form) }),
+            Formula::ExistentialQ(id, itype, box form) => Formula::ExistentialQ(id, itype, if id == var { Box::new(form) } else { go(form) }),
+            Formula::Abstraction(id, itype, box form)  => Formula::Abstraction(id, itype, if id == var { Box::new(form) } else { go(form) }),
+        }
+    }
+
+    // renames every binder to an id that occurs nowhere else
+    fn freshen(form: Formula, globals: &mut Bindings) -> Formula {
+        let under = |id: usize, form: Formula, globals: &mut Bindings| {
+            let fresh = globals.new_local();
+            (fresh, Box::new(freshen(naive(form, id, &Term::Symbol(Local(fresh), Vec::new())), globals)))
+        };
+        let go = |form: Formula, globals: &mut Bindings| Box::new(freshen(form, globals));
+        match form {
+            Formula::False                             => Formula::False,
+            Formula::Relation(id, itypes)              => Formula::Relation(id, itypes),
+            Formula::Application(box pred, arg)        => Formula::Application(go(pred, globals), arg),
+            Formula::Equality(itype, lhs, rhs)         => Formula::Equality(itype, lhs, rhs),
+            Formula::Implication(box lhs, box rhs)     => Formula::Implication(go(lhs, globals), go(rhs, globals)),
+            Formula::Negation(box form)                => Formula::Negation(go(form, globals)),
+            Formula::Conjunction(box lhs, box rhs)     => Formula::Conjunction(go(lhs, globals), go(rhs, globals)),
+            Formula::Disjunction(box lhs, box rhs)     => Formula::Disjunction(go(lhs, globals), go(rhs, globals)),
+            Formula::Biconditional(box lhs, box rhs)   => Formula::Biconditional(go(lhs, globals), go(rhs, globals)),
+            Formula::UniversalQ(id, itype, box form)   => {
+                let (id, form) = under(id, form, globals);
+                Formula::UniversalQ(id, itype, form)
+            },
+            Formula::ExistentialQ(id, itype, box form) => {
+                let (id, form) = under(id, form, globals);
+                Formula::ExistentialQ(id, itype, form)
+            },
+            Formula::Abstraction(id, itype, box form)  => {
+                let (id, form) = under(id, form, globals);
+                Formula::Abstraction(id, itype, form)
+            },
+        }
+    }
+
+    fn cases<F: FnMut(Formula, usize, Term, &mut Bindings)>(seed: u64, mut check: F) {
+        let mut rng = Rng(seed);
+        for _ in 0..CASES {
+            let form = formula(&mut rng, 4);
+            let var = rng.below(POOL);
+            let term = term(&mut rng, 2);
+            check(form, var, term, &mut globals());
+        }
+    }
+
+    #[test]
+    fn substitute_renames_capturing_binder() {
+        let mut globals = globals();
+        let form = Formula::UniversalQ(1, nat(), Box::new(relation(vec![Term::Symbol(Local(0), Vec::new()), Term::Symbol(Local(1), Vec::new())])));
+        let result = form.substitute(0, &Term::Symbol(Local(1), Vec::new()), &mut globals);
+        match result {
+            Formula::UniversalQ(id, _, box Formula::Application(box Formula::Application(_, Term::Symbol(lhs, _)), Term::Symbol(rhs, _))) => {
+                assert!(id != 1);
+                assert_eq!(lhs, Local(1));
+                assert_eq!(rhs, Local(id));
+            },
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rewrite_renames_capturing_binder() {
+        let mut globals = globals();
+        let form = Formula::UniversalQ(1, nat(), Box::new(relation(vec![Term::Symbol(Global(1), Vec::new()), Term::Symbol(Local(1), Vec::new())])));
+        let result = form.rewrite(&Term::Symbol(Global(1), Vec::new()), &Term::Symbol(Local(1), Vec::new()), &mut globals);
+        match result {
+            Formula::UniversalQ(id, _, box Formula::Application(box Formula::Application(_, Term::Symbol(lhs, _)), Term::Symbol(rhs, _))) => {
+                assert!(id != 1);
+                assert_eq!(lhs, Local(1));
+                assert_eq!(rhs, Local(id));
+            },
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn substitute_stops_at_shadowing_binder() {
+        let mut globals = globals();
+        let form = Formula::UniversalQ(0, nat(), Box::new(relation(vec![Term::Symbol(Local(0), Vec::new()), Term::Symbol(Global(1), Vec::new())])));
+        let result = form.clone().substitute(0, &Term::Symbol(Global(2), Vec::new()), &mut globals);
+        assert!(equivalent(&result, &form));
+    }
+
+    #[test]
+    fn substitute_avoids_capture() {
+        cases(0x2545F4914F6CDD1D, |form, var, term, globals| {
+            let expected = naive(freshen(form.clone(), globals), var, &term);
+            let result = form.clone().substitute(var, &term, globals);
+            assert!(equivalent(&result, &expected), "{:?}[{} := {:?}] gave {:?}, expected {:?}", form, var, term, result, expected);
+        });
+    }
+
+    #[test]
+    fn substitute_preserves_free_vars() {
+        cases(0x9E3779B97F4A7C15, |form, var, term, globals| {
+            let mut expected = form.free_vars();
+            if expected.remove(&var) {
+                expected.extend(term.vars());
+            }
+            let result = form.clone().substitute(var, &term, globals);
+            assert_eq!(result.free_vars(), expected, "{:?}[{} := {:?}] gave {:?}", form, var, term, result);
+        });
+    }
+
+    #[test]
+    fn substitute_commutes_with_alpha_equivalence() {
+        cases(0xD1B54A32D192ED03, |form, var, term, globals| {
+            let variant = freshen(form.clone(), globals);
+            assert!(equivalent(&form, &variant), "{:?} is not alpha-equivalent to {:?}", form, variant);
+            let lhs = form.clone().substitute(var, &term, globals);
+            let rhs = variant.clone().substitute(var, &term, globals);
+            assert!(equivalent(&lhs, &rhs), "{:?} and {:?} differ after [{} := {:?}]", form, variant, var, term);
+        });
+    }
+}